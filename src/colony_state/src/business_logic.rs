@@ -0,0 +1,1597 @@
+use std::{collections::HashMap, iter::FromIterator};
+
+use candid::{CandidType, Deserialize, Principal};
+
+use crate::env::{TimestampMillis, MILLIS_TO_SECONDS};
+
+mod amm;
+mod buildings;
+mod children_upgrades;
+mod crafting;
+mod expedition_outcomes;
+mod leaderboards;
+mod market;
+pub mod migrations;
+mod profiles;
+mod skills;
+mod snapshot_view;
+mod wasm_registry;
+
+pub use amm::{PoolDeposit, PoolInfo, SwapArgs};
+pub use buildings::{Building, BuildingProgress};
+pub use children_upgrades::{ChildUpgrade, ChildrenUpgrade};
+pub use crafting::{Item, PlayerItems, Recipe};
+pub use expedition_outcomes::ExpeditionOutcome;
+pub use leaderboards::{LeaderboardKind, LeaderboardPage, PlayerStats};
+pub use market::{NewOrder, Order, OrderBookDepth, OrderSide, Trade};
+pub use profiles::{Profile, ProfileArgs};
+pub use skills::{PlayerProfile, SkillCurve};
+pub use snapshot_view::{ColonyView, ExpeditionView, PlayerView, SnapshotView};
+pub use wasm_registry::{WasmRegistry, WasmVersion};
+
+#[derive(CandidType, Deserialize, Debug, Default)]
+pub struct BusinessState {
+    pub colony: ColonyState,
+    pub player: HashMap<Principal, PlayerState>,
+    pub expeditions: HashMap<u64, ExpeditionState>,
+    pub expeditions_count: u64,
+    pub remote_colonies: Vec<Principal>,
+    /// The wasm modules new colonies can be installed with
+    pub wasm_registry: WasmRegistry,
+    /// The last upgrade of the colonies this one started
+    pub children_upgrade: Option<ChildrenUpgrade>,
+    pub market: market::MarketState,
+    pub amm: amm::AmmState,
+    pub crafting: crafting::CraftingState,
+    pub buildings: HashMap<Building, buildings::BuildingState>,
+    pub leaderboards: leaderboards::LeaderboardState,
+}
+
+/// Returned by everything that needs the caller to have joined this colony first
+pub const PLAYER_NOT_REGISTERED: &str = "Player not found in this world";
+
+pub fn not_registered() -> String {
+    PLAYER_NOT_REGISTERED.to_string()
+}
+
+/// The error of the endpoints a player calls, so clients can tell a caller that hasn't joined
+/// this colony apart from everything else that can go wrong
+#[derive(CandidType, Deserialize, Debug, Clone, PartialEq)]
+pub enum PlayerError {
+    NotRegistered,
+    Other(String),
+}
+
+impl From<String> for PlayerError {
+    fn from(error: String) -> Self {
+        PlayerError::Other(error)
+    }
+}
+
+#[derive(CandidType, Deserialize, Debug, Default)]
+pub struct SystemSettings {
+    /// Principals allowed to call admin-only endpoints. The principal that installed the
+    /// canister is added on init.
+    pub admins: Vec<Principal>,
+}
+
+impl SystemSettings {
+    pub fn check_admin(&self, principal: &Principal) -> Result<(), String> {
+        if self.admins.contains(principal) {
+            Ok(())
+        } else {
+            Err("Only admins can do this".to_string())
+        }
+    }
+}
+
+#[derive(CandidType, Deserialize, Debug)]
+pub struct ColonyState {
+    /// Starts at 0 for the first colony and is incremented by 1 for each
+    /// successful expedition that becomes a colony
+    pub generation: u8,
+    /// Taxes are being applied at the customs office once a player enters
+    /// a colony. Each resource carried by the player is taxed with this rate.
+    pub taxes_percent: u8,
+    /// Easy way to create x10 or x100 colonies for testing
+    pub global_resources_multiplier: u16,
+    /// Each colony defines a rate at which resources are rewarded if a player
+    /// works for that colony.
+    pub rewards_per_second: HashMap<Resources, u8>,
+    /// Food and Water a player uses up for each second of work. Supplies earned while working
+    /// count towards the upkeep.
+    pub upkeep_per_second: HashMap<Resources, u8>,
+    /// Work is only paid out for this many seconds at a time. Players who want to keep working
+    /// longer than this need to harvest or claim their work in between.
+    pub max_work_session_seconds: u64,
+    /// How players level up their skills by working, and how much each level adds to the yield
+    pub skill_curve: SkillCurve,
+    /// Taxes go here. The inventory's size is the most the coffers can hold of each resource,
+    /// and can be raised by building a Warehouse. A size of 0 means there is no limit.
+    pub coffers: Inventory,
+}
+
+/// How much of each resource the coffers of a new colony can hold, both for the first colony and
+/// for the ones started by expeditions
+const BASE_COFFERS_SIZE: u32 = 100_000;
+
+impl ColonyState {
+    /// Adds resources to the coffers, up to their capacity, and returns what didn't fit. Whoever
+    /// pays into the coffers keeps that part.
+    pub fn add_to_coffers(
+        &mut self,
+        resources: &HashMap<Resources, u64>,
+    ) -> HashMap<Resources, u64> {
+        let capacity = self.coffers.size as u64;
+        let mut rejected = HashMap::new();
+
+        for (res, val) in resources.iter() {
+            let stored = self.coffers.contents.entry(*res).or_insert(0);
+            let added = if capacity > 0 {
+                (*val).min(capacity.saturating_sub(*stored))
+            } else {
+                *val
+            };
+            *stored += added;
+
+            if added < *val {
+                rejected.insert(*res, val - added);
+            }
+        }
+
+        rejected
+    }
+}
+
+impl Default for ColonyState {
+    fn default() -> Self {
+        Self {
+            generation: 0,
+            taxes_percent: 10,
+            global_resources_multiplier: 1,
+            rewards_per_second: HashMap::from([
+                (Resources::Wood, 10),
+                (Resources::Stone, 10),
+                (Resources::Food, 10),
+                (Resources::Water, 10),
+            ]),
+            upkeep_per_second: HashMap::from([(Resources::Food, 1), (Resources::Water, 1)]),
+            max_work_session_seconds: 8 * 60 * 60,
+            skill_curve: Default::default(),
+            coffers: Inventory {
+                size: BASE_COFFERS_SIZE,
+                ..Default::default()
+            },
+        }
+    }
+}
+
+#[derive(CandidType, Deserialize, Debug, Default, Clone)]
+pub struct Inventory {
+    /// Could be used to decide if resources can be added to the inventory (e.g. a Player can
+    /// only carry so much resources at a time, or a colony needs to be upgraded to hold more resources)
+    size: u32,
+    contents: HashMap<Resources, u64>,
+    /// Resources locked by open market orders. They still belong to the owner of the inventory
+    /// but can't be spent until the order is filled or cancelled.
+    escrow: HashMap<Resources, u64>,
+}
+
+#[allow(dead_code)]
+impl Inventory {
+    pub fn has_available_resources(&self, required: &HashMap<Resources, u64>) -> bool {
+        for (res, val) in required.iter() {
+            if self.contents.get(res).lt(&Some(val)) {
+                return false;
+            }
+        }
+        true
+    }
+
+    fn subtract_resources(&mut self, requirements: &HashMap<Resources, u64>) {
+        for (res, val) in requirements.iter() {
+            self.contents.entry(*res).and_modify(|v| *v -= val);
+        }
+    }
+
+    fn add_resources(&mut self, requirements: &HashMap<Resources, u64>) {
+        for (res, val) in requirements.iter() {
+            self.contents
+                .entry(*res)
+                .and_modify(|v| *v += val)
+                .or_insert(*val);
+        }
+    }
+
+    fn get_all(&self) -> Vec<(Resources, u64)> {
+        self.contents
+            .iter()
+            .map(|(res, val)| (res.clone(), val.clone()))
+            .collect()
+    }
+
+    pub fn get(&self, res: Resources) -> u64 {
+        self.contents.get(&res).unwrap_or(&0).clone()
+    }
+
+    /// Moves `amount` of `res` from the spendable contents into escrow.
+    fn lock_resources(&mut self, res: Resources, amount: u64) -> Result<(), String> {
+        if self.get(res) < amount {
+            return Err(format!("Not enough {:?} available", res));
+        }
+
+        self.subtract_resources(&HashMap::from([(res, amount)]));
+        *self.escrow.entry(res).or_insert(0) += amount;
+
+        Ok(())
+    }
+
+    /// Moves `amount` of `res` from escrow back into the spendable contents.
+    fn unlock_resources(&mut self, res: Resources, amount: u64) {
+        self.release_escrow(res, amount);
+        self.add_resources(&HashMap::from([(res, amount)]));
+    }
+
+    /// Removes `amount` of `res` from escrow, e.g. when it changes hands in a trade.
+    fn release_escrow(&mut self, res: Resources, amount: u64) {
+        if let Some(locked) = self.escrow.get_mut(&res) {
+            *locked = locked.saturating_sub(amount);
+            if *locked == 0 {
+                self.escrow.remove(&res);
+            }
+        }
+    }
+
+    fn get_escrow(&self, res: Resources) -> u64 {
+        *self.escrow.get(&res).unwrap_or(&0)
+    }
+}
+
+#[derive(CandidType, Deserialize, Debug, Clone)]
+pub struct ExpeditionState {
+    id: u64,
+    step: ExpeditionStep,
+    proposed_by: Principal,
+    proposed_at: TimestampMillis,
+    resources_required: HashMap<Resources, u64>,
+    pub resources_pool: Inventory,
+    pub members: Vec<Principal>,
+    /// Rolled when the expedition starts
+    pub outcome: Option<ExpeditionOutcome>,
+    /// The wasm the expedition's colony was installed from
+    pub wasm_hash: Option<String>,
+    /// The canister created for the new colony. Kept when installing the colony fails, so the
+    /// next try installs into it instead of creating another one.
+    pub colony_canister: Option<Principal>,
+}
+
+impl Default for ExpeditionState {
+    fn default() -> Self {
+        Self {
+            id: Default::default(),
+            step: ExpeditionStep::Proposed,
+            proposed_by: Principal::anonymous(),
+            proposed_at: Default::default(),
+            resources_required: Default::default(),
+            resources_pool: Default::default(),
+            members: Default::default(),
+            outcome: None,
+            wasm_hash: None,
+            colony_canister: None,
+        }
+    }
+}
+
+impl ExpeditionState {
+    pub fn add_resources(&mut self, resources: &HashMap<Resources, u64>) -> Result<(), String> {
+        for (res, val) in resources.iter() {
+            self.resources_pool
+                .contents
+                .entry(*res)
+                .and_modify(|v| *v += val);
+        }
+
+        Ok(())
+    }
+
+    pub fn set_step(&mut self, step: ExpeditionStep) -> Result<(), String> {
+        self.step = step;
+
+        Ok(())
+    }
+
+    pub fn get_step(self) -> ExpeditionStep {
+        self.step
+    }
+
+    pub fn has_enough_resources(&self) -> bool {
+        let mut required = self.resources_required.clone();
+
+        // TODO: this multiplier should be stored somewhere in the world state
+        for (_, val) in required.iter_mut() {
+            *val *= 10;
+        }
+
+        self.resources_pool.has_available_resources(&required)
+    }
+}
+
+#[derive(CandidType, Deserialize, Debug, Clone)]
+pub enum ExpeditionStep {
+    /// This is the default state of an expedition. In this state we wait until the conditions
+    /// are met. Players can join the expedition in this step.
+    Proposed,
+    /// This state indicates tha the conditions for the expedition have been met, and we are ready to
+    /// start the expedition. Players cannot join the expedition at this point.
+    Ready,
+    /// The async process of starting a new expedition has started at "timestamp". We can later implement
+    /// some retry logic based on the timestamp.
+    Starting(TimestampMillis),
+    /// The new expedition was started, a new world has been spawned and we got confirmation that
+    /// the new world is ready.
+    Started(Principal),
+    /// The end of an expedition's lifecycle. We can hold on to the expedition as a log of sorts
+    /// but for all intents and purposes this is a finished task.
+    Done,
+}
+
+#[derive(CandidType, Deserialize, Debug, Hash, PartialEq, Eq, Copy, Clone, Ord, PartialOrd)]
+pub enum Resources {
+    Wood,
+    Stone,
+    /// Only available in colonies of generation > 0
+    Gold,
+    Food,
+    Water,
+}
+
+#[derive(CandidType, Deserialize, Debug, Default, Clone)]
+pub struct PlayerState {
+    status: PlayerStatus,
+    inventory: Inventory,
+    /// Shares owned in the colony's swap pools
+    lp_shares: HashMap<amm::PoolKey, u64>,
+    /// Crafted items. Items raise the yield of matching resources while the player works.
+    items: Vec<crafting::ItemState>,
+    crafting: Vec<crafting::CraftingJob>,
+    /// Seconds worked for each resource. Experience raises the player's skill level, which raises
+    /// their yield for that resource.
+    experience: HashMap<Resources, u64>,
+    profile: Profile,
+}
+
+#[allow(dead_code)]
+impl PlayerState {
+    pub fn get_status(&self) -> PlayerStatus {
+        self.status.clone()
+    }
+
+    pub fn set_status(&mut self, status: PlayerStatus) {
+        self.status = status
+    }
+
+    pub fn get_inventory(&self) -> Inventory {
+        self.inventory.clone()
+    }
+
+    /// Open orders, pool shares and crafting jobs stay behind in this colony, so a player has to
+    /// settle them before joining an expedition
+    fn check_can_leave(&self) -> Result<(), String> {
+        if !self.inventory.escrow.is_empty() {
+            return Err("Cancel your open orders before joining an expedition".to_string());
+        }
+        if !self.lp_shares.is_empty() {
+            return Err("Withdraw your pool shares before joining an expedition".to_string());
+        }
+        if !self.crafting.is_empty() {
+            return Err(
+                "Collect your crafting jobs once they are done before joining an expedition"
+                    .to_string(),
+            );
+        }
+
+        Ok(())
+    }
+}
+
+/// Lets players plan how much Food and Water they need to keep working
+#[derive(CandidType, Deserialize, Debug, PartialEq, Clone)]
+pub struct UpkeepEstimate {
+    upkeep_per_second: Vec<(Resources, u64)>,
+    /// How long the player can work before their supplies run out. Empty if they never run out.
+    supplied_seconds: Option<u64>,
+    /// Seconds worked in the current session so far
+    seconds_worked: u64,
+    /// The part of `seconds_worked` that would be paid out if the work was claimed now
+    seconds_paid: u64,
+    /// Supplies the current session would use up if the work was claimed now
+    consumed: Vec<(Resources, u64)>,
+}
+
+#[derive(CandidType, Deserialize, Debug, PartialEq, Clone)]
+pub enum PlayerStatus {
+    Idle,
+    WorkingAll(TimestampMillis),
+    WorkingFocused(TimestampMillis, Resources),
+    Traveling,
+}
+
+impl Default for PlayerStatus {
+    fn default() -> Self {
+        PlayerStatus::Idle
+    }
+}
+
+#[allow(dead_code)]
+impl BusinessState {
+    pub fn is_player_in_world(&self, principal: Principal) -> bool {
+        if let Some(_) = self.player.get(&principal) {
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn check_registered(&self, principal: &Principal) -> Result<(), PlayerError> {
+        if self.player.contains_key(principal) {
+            Ok(())
+        } else {
+            Err(PlayerError::NotRegistered)
+        }
+    }
+
+    pub fn work_set(
+        &mut self,
+        principal: Principal,
+        focus: Option<Resources>,
+        now: TimestampMillis,
+    ) -> Result<(), String> {
+        let p = self.player.get_mut(&principal).ok_or_else(not_registered)?;
+
+        if let Some(res) = focus {
+            p.status = PlayerStatus::WorkingFocused(now, res);
+        } else {
+            p.status = PlayerStatus::WorkingAll(now);
+        }
+
+        Ok(())
+    }
+
+    pub fn work_claim(&mut self, principal: Principal, now: TimestampMillis) -> Result<(), String> {
+        self.pay_out_work(principal, now)?;
+
+        let p = self.player.get_mut(&principal).ok_or_else(not_registered)?;
+
+        p.status = PlayerStatus::Idle;
+
+        Ok(())
+    }
+
+    /// Claims the work a player has built up so far without stopping. The player's working
+    /// timestamp moves forward to where the payout ended, so the same time can't be claimed twice.
+    pub fn harvest(
+        &mut self,
+        principal: Principal,
+        now: TimestampMillis,
+    ) -> Result<Vec<(Resources, u64)>, String> {
+        let (payout, paid_until) = self.pay_out_work(principal, now)?;
+
+        let p = self.player.get_mut(&principal).ok_or_else(not_registered)?;
+
+        p.status = match p.status {
+            PlayerStatus::WorkingFocused(_, res) => PlayerStatus::WorkingFocused(paid_until, res),
+            _ => PlayerStatus::WorkingAll(paid_until),
+        };
+
+        Ok(payout)
+    }
+
+    /// Seconds of work since the player started working or last harvested, capped at the
+    /// colony's maximum session length.
+    pub fn seconds_worked(
+        &self,
+        principal: &Principal,
+        now: TimestampMillis,
+    ) -> Result<u64, String> {
+        match self
+            .player
+            .get(principal)
+            .ok_or_else(not_registered)?
+            .status
+        {
+            PlayerStatus::WorkingAll(working_since)
+            | PlayerStatus::WorkingFocused(working_since, _) => {
+                Ok((now.saturating_sub(working_since) / MILLIS_TO_SECONDS)
+                    .min(self.colony.max_work_session_seconds))
+            }
+            _ => Err("The player is not currently working".to_string()),
+        }
+    }
+
+    /// Adds the player's work since `working_since` to their inventory and takes the upkeep.
+    /// Returns what was paid out and the timestamp the payout covers work up to.
+    fn pay_out_work(
+        &mut self,
+        principal: Principal,
+        now: TimestampMillis,
+    ) -> Result<(Vec<(Resources, u64)>, TimestampMillis), String> {
+        let seconds_elapsed = self.seconds_worked(&principal, now)?;
+
+        let seconds_paid = self.upkeep_paid_seconds(&principal, seconds_elapsed)?;
+        let available = self.player_unclaimed(&principal, seconds_paid)?;
+        let upkeep = self.upkeep_for(seconds_paid);
+
+        let p = self.player.get_mut(&principal).ok_or_else(not_registered)?;
+
+        for (res, val) in available.iter() {
+            *p.inventory.contents.entry(*res).or_insert(0) += val;
+        }
+
+        p.inventory.subtract_resources(&upkeep);
+
+        Self::wear_items(p, seconds_paid);
+        Self::add_experience(p, &available, seconds_paid);
+
+        // Time past the maximum session length is forfeited, otherwise we keep the part of the
+        // current second that hasn't been paid for yet
+        let paid_until = match p.status {
+            PlayerStatus::WorkingAll(working_since)
+            | PlayerStatus::WorkingFocused(working_since, _)
+                if seconds_elapsed < self.colony.max_work_session_seconds =>
+            {
+                working_since + seconds_elapsed * MILLIS_TO_SECONDS
+            }
+            _ => now,
+        };
+
+        let earned = available.iter().map(|(_, val)| val).sum();
+        self.record_stat(principal, LeaderboardKind::ResourcesEarned, earned);
+        self.record_stat(principal, LeaderboardKind::SecondsWorked, seconds_paid);
+
+        Ok((available, paid_until))
+    }
+
+    pub fn available_unclaimed(&self, seconds_elapsed: TimestampMillis) -> Vec<(Resources, u64)> {
+        self.colony
+            .rewards_per_second
+            .iter()
+            .map(|(res, val)| (res.clone(), val.clone() as u64 * seconds_elapsed))
+            .collect::<Vec<(Resources, u64)>>()
+    }
+
+    /// What a player would get for `seconds_elapsed` of work, including the bonus from their items
+    /// and skills.
+    pub fn player_unclaimed(
+        &self,
+        principal: &Principal,
+        seconds_elapsed: u64,
+    ) -> Result<Vec<(Resources, u64)>, String> {
+        let p = self.player.get(principal).ok_or_else(not_registered)?;
+
+        let mut available: HashMap<Resources, u64> =
+            HashMap::from_iter(self.available_unclaimed(seconds_elapsed));
+
+        for (res, val) in self
+            .item_bonus(p, seconds_elapsed)
+            .into_iter()
+            .chain(self.skill_bonus(p, seconds_elapsed))
+        {
+            *available.entry(res).or_insert(0) += val;
+        }
+
+        Ok(available.into_iter().collect())
+    }
+
+    /// How many seconds of work a player's Food and Water can pay for, counting what they earn
+    /// while working. `None` means the supplies never run out at this colony's rates.
+    pub fn upkeep_supplied_seconds(&self, principal: &Principal) -> Result<Option<u64>, String> {
+        let p = self.player.get(principal).ok_or_else(not_registered)?;
+
+        Ok(self
+            .colony
+            .upkeep_per_second
+            .iter()
+            .filter_map(|(res, upkeep)| {
+                let earned = *self.colony.rewards_per_second.get(res).unwrap_or(&0);
+                let deficit = upkeep.saturating_sub(earned) as u64;
+
+                p.inventory.get(*res).checked_div(deficit)
+            })
+            .min())
+    }
+
+    /// The part of `seconds_elapsed` that the player's supplies pay for. Claims for work that
+    /// wasn't paid for are dropped.
+    pub fn upkeep_paid_seconds(
+        &self,
+        principal: &Principal,
+        seconds_elapsed: u64,
+    ) -> Result<u64, String> {
+        Ok(self
+            .upkeep_supplied_seconds(principal)?
+            .map_or(seconds_elapsed, |supplied| supplied.min(seconds_elapsed)))
+    }
+
+    pub fn upkeep_for(&self, seconds: u64) -> HashMap<Resources, u64> {
+        self.colony
+            .upkeep_per_second
+            .iter()
+            .filter(|(_, val)| **val > 0)
+            .map(|(res, val)| (*res, *val as u64 * seconds))
+            .collect()
+    }
+
+    pub fn upkeep_estimate(
+        &self,
+        principal: &Principal,
+        now: TimestampMillis,
+    ) -> Result<UpkeepEstimate, String> {
+        let p = self.player.get(principal).ok_or_else(not_registered)?;
+
+        let seconds_worked = match p.status {
+            PlayerStatus::Idle | PlayerStatus::Traveling => 0,
+            _ => self.seconds_worked(principal, now)?,
+        };
+        let seconds_paid = self.upkeep_paid_seconds(principal, seconds_worked)?;
+
+        let mut upkeep_per_second: Vec<(Resources, u64)> = self.upkeep_for(1).into_iter().collect();
+        upkeep_per_second.sort();
+
+        let mut consumed: Vec<(Resources, u64)> =
+            self.upkeep_for(seconds_paid).into_iter().collect();
+        consumed.sort();
+
+        Ok(UpkeepEstimate {
+            upkeep_per_second,
+            supplied_seconds: self.upkeep_supplied_seconds(principal)?,
+            seconds_worked,
+            seconds_paid,
+            consumed,
+        })
+    }
+
+    pub fn set_upkeep_rates(&mut self, rates: HashMap<Resources, u8>) {
+        self.colony.upkeep_per_second = rates;
+    }
+
+    pub fn set_max_work_session(&mut self, seconds: u64) -> Result<(), String> {
+        if seconds == 0 {
+            return Err("The maximum work session must be at least one second".to_string());
+        }
+
+        self.colony.max_work_session_seconds = seconds;
+
+        Ok(())
+    }
+
+    pub fn propose_expedition(
+        &mut self,
+        principal: Principal,
+        now: TimestampMillis,
+    ) -> Result<(), String> {
+        let p = self.player.get_mut(&principal).ok_or_else(not_registered)?;
+        p.check_can_leave()?;
+
+        let requirements = HashMap::from([
+            (Resources::Wood, 60),
+            (Resources::Stone, 60),
+            (Resources::Food, 60),
+            (Resources::Water, 60),
+        ]);
+
+        if p.inventory.has_available_resources(&requirements) {
+            p.inventory.subtract_resources(&requirements);
+        } else {
+            return Err(
+                "The player doesn't have enough resources to propose an expedition".to_string(),
+            );
+        }
+
+        let id = self.expeditions_count;
+        let contributed = requirements.values().sum();
+
+        let proposed = ExpeditionState {
+            step: ExpeditionStep::Proposed,
+            proposed_by: principal,
+            proposed_at: now,
+            resources_required: requirements.clone(),
+            resources_pool: Inventory {
+                size: 0,
+                escrow: Default::default(),
+                contents: requirements,
+            },
+            members: Vec::from([principal]),
+            id: self.expeditions_count,
+            outcome: None,
+            wasm_hash: None,
+            colony_canister: None,
+        };
+
+        self.expeditions.insert(id, proposed);
+        self.expeditions_count += 1;
+
+        self.record_stat(principal, LeaderboardKind::ExpeditionsJoined, 1);
+        self.record_stat(
+            principal,
+            LeaderboardKind::ExpeditionContributions,
+            contributed,
+        );
+
+        Ok(())
+    }
+
+    pub fn join_expedition(
+        &mut self,
+        principal: &Principal,
+        expedition_id: u64,
+    ) -> Result<(), String> {
+        let p = self.player.get_mut(principal).ok_or_else(not_registered)?;
+        p.check_can_leave()?;
+
+        let entry = self
+            .expeditions
+            .get_mut(&expedition_id)
+            .ok_or_else(|| "Can't find expedition".to_string())?;
+        let requirements = entry.resources_required.clone();
+
+        if entry.members.contains(principal) {
+            return Err("The player is already a member of this expedition".to_string());
+        }
+
+        // subtract resources from the player
+        if p.inventory.has_available_resources(&requirements) {
+            p.inventory.subtract_resources(&requirements);
+        } else {
+            return Err(
+                "The player doesn't have enough resources to propose an expedition".to_string(),
+            );
+        }
+
+        entry.add_resources(&requirements)?;
+        entry.members.push(*principal);
+
+        self.record_stat(*principal, LeaderboardKind::ExpeditionsJoined, 1);
+        self.record_stat(
+            *principal,
+            LeaderboardKind::ExpeditionContributions,
+            requirements.values().sum(),
+        );
+
+        Ok(())
+    }
+
+    pub fn add_player(&mut self, principal: Principal, now: TimestampMillis) -> Result<(), String> {
+        if self.player.contains_key(&principal) {
+            return Err("The player already exists in this world".to_string());
+        }
+
+        self.player.insert(
+            principal,
+            PlayerState {
+                profile: Profile::new(now),
+                ..Default::default()
+            },
+        );
+
+        Ok(())
+    }
+
+    /// Add a player that is traveling from another world.
+    pub fn add_traveler(
+        &mut self,
+        principal: Principal,
+        player_state: PlayerState,
+    ) -> Result<(), String> {
+        if self.player.contains_key(&principal) {
+            return Err("The player already exists in this world".to_string());
+        }
+
+        // Customs office: apply this colony's taxes percent to the player's inventory. The taxes
+        // go to the coffers, and whatever doesn't fit in them isn't taken.
+        let percentage = self.colony.taxes_percent;
+
+        let taxes: HashMap<Resources, u64> = player_state
+            .inventory
+            .contents
+            .iter()
+            .map(|(k, v)| (*k, v * percentage as u64 / 100))
+            .collect();
+        let rejected = self.colony.add_to_coffers(&taxes);
+
+        let taxed_inventory: Vec<(Resources, u64)> = player_state
+            .inventory
+            .contents
+            .iter()
+            .map(|(k, v)| (*k, v - taxes[k] + rejected.get(k).unwrap_or(&0)))
+            .collect();
+
+        let taxes_paid = player_state.inventory.contents.values().sum::<u64>()
+            - taxed_inventory.iter().map(|(_, v)| v).sum::<u64>();
+
+        let profile = self.arriving_profile(&principal, player_state.profile.clone());
+
+        self.player.insert(
+            principal,
+            PlayerState {
+                inventory: Inventory {
+                    size: 0,
+                    escrow: Default::default(),
+                    contents: HashMap::from_iter(taxed_inventory),
+                },
+                profile,
+                // Players settle their pool shares and crafting jobs before they leave, see
+                // `check_can_leave`. Whatever is left belongs to the colony they left.
+                lp_shares: Default::default(),
+                crafting: Default::default(),
+                ..player_state
+            },
+        );
+
+        self.record_stat(principal, LeaderboardKind::TaxesPaid, taxes_paid);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set_work() -> Result<(), String> {
+        let mut business_state = BusinessState::default();
+
+        let user1: Principal = Principal::from_slice(&[1]);
+
+        business_state.player.insert(user1, PlayerState::default());
+
+        assert_eq!(
+            business_state.player.get(&user1).unwrap().status,
+            PlayerStatus::Idle
+        );
+
+        business_state.work_set(user1, None, 1)?;
+
+        assert_eq!(
+            business_state.player.get(&user1).unwrap().status,
+            PlayerStatus::WorkingAll(1)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_available_unclaimed() -> Result<(), String> {
+        let mut business_state = BusinessState::default();
+
+        let user1: Principal = Principal::from_slice(&[1]);
+
+        business_state.player.insert(user1, PlayerState::default());
+
+        business_state.work_set(user1, None, 1)?;
+
+        let mut res = business_state.available_unclaimed(5);
+
+        assert_eq!(res.len(), 4);
+        // println!("{:?}", res);
+        assert_eq!(
+            res.sort(),
+            [
+                (Resources::Stone, 5),
+                (Resources::Water, 5),
+                (Resources::Food, 5),
+                (Resources::Wood, 5)
+            ]
+            .sort()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_work_claim() -> Result<(), String> {
+        let mut business_state = BusinessState::default();
+
+        let user1: Principal = Principal::from_slice(&[1]);
+
+        business_state.player.insert(user1, PlayerState::default());
+
+        business_state.work_set(user1, None, 1)?;
+
+        assert_eq!(
+            business_state
+                .player
+                .get(&user1)
+                .unwrap()
+                .inventory
+                .contents
+                .len(),
+            0
+        );
+
+        business_state.work_claim(user1, MILLIS_TO_SECONDS * 6 + 1)?;
+
+        assert_eq!(
+            business_state
+                .player
+                .get(&user1)
+                .unwrap()
+                .inventory
+                .contents
+                .len(),
+            4
+        );
+
+        println!("{:?}", business_state.player.get(&user1).unwrap().inventory);
+
+        business_state
+            .player
+            .get_mut(&user1)
+            .unwrap()
+            .set_status(PlayerStatus::Idle);
+
+        assert_eq!(
+            business_state.work_claim(user1, MILLIS_TO_SECONDS * 6 + 1),
+            Err("The player is not currently working".to_string())
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_player_has_resources() -> Result<(), String> {
+        let mut business_state = BusinessState::default();
+        // These tests only look at the inventory, keep upkeep out of the numbers
+        business_state.colony.upkeep_per_second.clear();
+
+        let user1: Principal = Principal::from_slice(&[1]);
+
+        business_state.player.insert(user1, PlayerState::default());
+
+        business_state.work_set(user1, None, 1)?;
+
+        assert_eq!(
+            business_state
+                .player
+                .get(&user1)
+                .unwrap()
+                .inventory
+                .contents
+                .len(),
+            0
+        );
+
+        business_state.work_claim(user1, MILLIS_TO_SECONDS * 60 + 1)?;
+
+        assert_eq!(
+            business_state
+                .player
+                .get(&user1)
+                .unwrap()
+                .inventory
+                .has_available_resources(&HashMap::from([(Resources::Wood, 100)])),
+            true
+        );
+
+        assert_eq!(
+            business_state
+                .player
+                .get(&user1)
+                .unwrap()
+                .inventory
+                .has_available_resources(&HashMap::from([
+                    (Resources::Wood, 600),
+                    (Resources::Stone, 600),
+                    (Resources::Food, 600),
+                    (Resources::Water, 600),
+                ])),
+            true
+        );
+
+        assert_eq!(
+            business_state
+                .player
+                .get(&user1)
+                .unwrap()
+                .inventory
+                .has_available_resources(&HashMap::from([(Resources::Wood, 700)])),
+            false
+        );
+
+        assert_eq!(
+            business_state
+                .player
+                .get(&user1)
+                .unwrap()
+                .inventory
+                .has_available_resources(&HashMap::from([
+                    (Resources::Wood, 600),
+                    (Resources::Stone, 700),
+                    (Resources::Food, 600),
+                    (Resources::Water, 600),
+                ])),
+            false
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_player_remove_resources() -> Result<(), String> {
+        let mut business_state = BusinessState::default();
+        // These tests only look at the inventory, keep upkeep out of the numbers
+        business_state.colony.upkeep_per_second.clear();
+
+        let user1: Principal = Principal::from_slice(&[1]);
+
+        business_state.player.insert(user1, PlayerState::default());
+
+        business_state.work_set(user1, None, 1)?;
+
+        assert_eq!(
+            business_state
+                .player
+                .get(&user1)
+                .unwrap()
+                .inventory
+                .contents
+                .len(),
+            0
+        );
+
+        business_state.work_claim(user1, MILLIS_TO_SECONDS * 60 + 1)?;
+
+        business_state
+            .player
+            .get_mut(&user1)
+            .unwrap()
+            .inventory
+            .subtract_resources(&HashMap::from([
+                (Resources::Wood, 300),
+                (Resources::Stone, 300),
+                (Resources::Food, 300),
+                (Resources::Water, 300),
+            ]));
+
+        assert_eq!(
+            business_state
+                .player
+                .get(&user1)
+                .unwrap()
+                .inventory
+                .has_available_resources(&HashMap::from([(Resources::Wood, 100)])),
+            true
+        );
+
+        assert_eq!(
+            business_state
+                .player
+                .get(&user1)
+                .unwrap()
+                .inventory
+                .has_available_resources(&HashMap::from([(Resources::Wood, 350)])),
+            false
+        );
+
+        assert_eq!(
+            business_state
+                .player
+                .get(&user1)
+                .unwrap()
+                .inventory
+                .has_available_resources(&HashMap::from([
+                    (Resources::Wood, 300),
+                    (Resources::Stone, 300),
+                    (Resources::Food, 300),
+                    (Resources::Water, 300),
+                ])),
+            true
+        );
+
+        assert_eq!(
+            business_state
+                .player
+                .get(&user1)
+                .unwrap()
+                .inventory
+                .has_available_resources(&HashMap::from([
+                    (Resources::Wood, 600),
+                    (Resources::Stone, 700),
+                    (Resources::Food, 600),
+                    (Resources::Water, 600),
+                ])),
+            false
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_upkeep_paid_from_earnings() -> Result<(), String> {
+        let mut business_state = BusinessState::default();
+
+        let user1: Principal = Principal::from_slice(&[1]);
+
+        business_state.player.insert(user1, PlayerState::default());
+
+        assert_eq!(business_state.upkeep_supplied_seconds(&user1)?, None);
+
+        business_state.work_set(user1, None, 0)?;
+        business_state.work_claim(user1, MILLIS_TO_SECONDS * 60)?;
+
+        let inventory = &business_state.player.get(&user1).unwrap().inventory;
+        assert_eq!(inventory.get(Resources::Wood), 600);
+        assert_eq!(inventory.get(Resources::Food), 540);
+        assert_eq!(inventory.get(Resources::Water), 540);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_upkeep_reduces_claim_when_supplies_run_out() -> Result<(), String> {
+        let mut business_state = BusinessState::default();
+
+        let user1: Principal = Principal::from_slice(&[1]);
+
+        business_state.player.insert(
+            user1,
+            PlayerState {
+                inventory: Inventory {
+                    contents: HashMap::from([(Resources::Food, 100), (Resources::Water, 300)]),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+        );
+
+        // Working costs 5 more Food and Water per second than the colony rewards
+        business_state.set_upkeep_rates(HashMap::from([
+            (Resources::Food, 15),
+            (Resources::Water, 15),
+        ]));
+
+        assert_eq!(business_state.upkeep_supplied_seconds(&user1)?, Some(20));
+
+        business_state.work_set(user1, None, 0)?;
+
+        let estimate = business_state.upkeep_estimate(&user1, MILLIS_TO_SECONDS * 60)?;
+        assert_eq!(estimate.seconds_worked, 60);
+        assert_eq!(estimate.seconds_paid, 20);
+        assert_eq!(
+            estimate.consumed,
+            vec![(Resources::Food, 300), (Resources::Water, 300)]
+        );
+
+        business_state.work_claim(user1, MILLIS_TO_SECONDS * 60)?;
+
+        // Only the 20 seconds the food lasted for are paid out
+        let inventory = &business_state.player.get(&user1).unwrap().inventory;
+        assert_eq!(inventory.get(Resources::Wood), 200);
+        assert_eq!(inventory.get(Resources::Food), 0);
+        assert_eq!(inventory.get(Resources::Water), 200);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_work_session_is_capped() -> Result<(), String> {
+        let mut business_state = BusinessState::default();
+        business_state.colony.upkeep_per_second.clear();
+        business_state.set_max_work_session(100)?;
+
+        let user1: Principal = Principal::from_slice(&[1]);
+
+        business_state.player.insert(user1, PlayerState::default());
+
+        business_state.work_set(user1, None, 0)?;
+        assert_eq!(
+            business_state.seconds_worked(&user1, MILLIS_TO_SECONDS * 1000),
+            Ok(100)
+        );
+
+        business_state.work_claim(user1, MILLIS_TO_SECONDS * 1000)?;
+
+        let inventory = &business_state.player.get(&user1).unwrap().inventory;
+        assert_eq!(inventory.get(Resources::Wood), 1000);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_harvest_keeps_working() -> Result<(), String> {
+        let mut business_state = BusinessState::default();
+        business_state.colony.upkeep_per_second.clear();
+
+        let user1: Principal = Principal::from_slice(&[1]);
+
+        business_state.player.insert(user1, PlayerState::default());
+
+        business_state.work_set(user1, Some(Resources::Stone), 0)?;
+
+        let mut harvested = business_state.harvest(user1, MILLIS_TO_SECONDS * 10 + 500)?;
+        harvested.sort();
+        assert_eq!(harvested[0], (Resources::Wood, 100));
+
+        // The half second that wasn't paid for stays in the session
+        assert_eq!(
+            business_state.player.get(&user1).unwrap().status,
+            PlayerStatus::WorkingFocused(MILLIS_TO_SECONDS * 10, Resources::Stone)
+        );
+
+        business_state.work_claim(user1, MILLIS_TO_SECONDS * 20)?;
+
+        let p = business_state.player.get(&user1).unwrap();
+        assert_eq!(p.status, PlayerStatus::Idle);
+        assert_eq!(p.inventory.get(Resources::Wood), 200);
+
+        assert_eq!(
+            business_state.harvest(user1, MILLIS_TO_SECONDS * 30),
+            Err("The player is not currently working".to_string())
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_propose_expedition() -> Result<(), String> {
+        let mut business_state = BusinessState::default();
+
+        let user1: Principal = Principal::from_slice(&[1]);
+
+        business_state.player.insert(
+            user1,
+            PlayerState {
+                status: PlayerStatus::Idle,
+                inventory: Inventory {
+                    size: 0,
+                    escrow: Default::default(),
+                    contents: HashMap::from([
+                        (Resources::Wood, 100),
+                        (Resources::Stone, 100),
+                        (Resources::Food, 100),
+                        (Resources::Water, 100),
+                    ]),
+                },
+                ..Default::default()
+            },
+        );
+
+        business_state.propose_expedition(user1, 2)?;
+
+        assert_eq!(business_state.expeditions.len(), 1);
+
+        assert_eq!(
+            business_state.propose_expedition(user1, 2),
+            Err("The player doesn't have enough resources to propose an expedition".to_string())
+        );
+
+        assert_eq!(business_state.expeditions.len(), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_join_expedition() -> Result<(), String> {
+        let mut business_state = BusinessState::default();
+
+        let user1: Principal = Principal::from_slice(&[1]);
+
+        business_state.player.insert(
+            user1,
+            PlayerState {
+                status: PlayerStatus::Idle,
+                inventory: Inventory {
+                    size: 0,
+                    escrow: Default::default(),
+                    contents: HashMap::from([
+                        (Resources::Wood, 100),
+                        (Resources::Stone, 100),
+                        (Resources::Food, 100),
+                        (Resources::Water, 100),
+                    ]),
+                },
+                ..Default::default()
+            },
+        );
+
+        let user2: Principal = Principal::from_slice(&[2]);
+
+        business_state.player.insert(
+            user2,
+            PlayerState {
+                status: PlayerStatus::Idle,
+                inventory: Inventory {
+                    size: 0,
+                    escrow: Default::default(),
+                    contents: HashMap::from([
+                        (Resources::Wood, 100),
+                        (Resources::Stone, 100),
+                        (Resources::Food, 100),
+                        (Resources::Water, 100),
+                    ]),
+                },
+                ..Default::default()
+            },
+        );
+
+        business_state.propose_expedition(user1, 2)?;
+
+        assert_eq!(business_state.expeditions.get(&0).unwrap().members.len(), 1);
+
+        business_state.join_expedition(&user2, 0)?;
+
+        assert_eq!(business_state.expeditions.get(&0).unwrap().members.len(), 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_players_settle_before_joining_an_expedition() -> Result<(), String> {
+        let mut business_state = BusinessState::default();
+
+        let user1: Principal = Principal::from_slice(&[1]);
+        let user2: Principal = Principal::from_slice(&[2]);
+        for user in [user1, user2] {
+            business_state.player.insert(
+                user,
+                PlayerState {
+                    inventory: Inventory {
+                        size: 0,
+                        escrow: Default::default(),
+                        contents: HashMap::from([
+                            (Resources::Wood, 300),
+                            (Resources::Stone, 100),
+                            (Resources::Food, 100),
+                            (Resources::Water, 100),
+                        ]),
+                    },
+                    ..Default::default()
+                },
+            );
+        }
+        business_state.propose_expedition(user2, 2)?;
+
+        let p = business_state.player.get_mut(&user1).unwrap();
+        p.inventory.lock_resources(Resources::Wood, 10)?;
+        assert_eq!(
+            business_state.propose_expedition(user1, 2),
+            Err("Cancel your open orders before joining an expedition".to_string())
+        );
+        assert_eq!(
+            business_state.join_expedition(&user1, 0),
+            Err("Cancel your open orders before joining an expedition".to_string())
+        );
+
+        let p = business_state.player.get_mut(&user1).unwrap();
+        p.inventory.unlock_resources(Resources::Wood, 10);
+        p.lp_shares.insert((Resources::Wood, Resources::Stone), 5);
+        assert_eq!(
+            business_state.join_expedition(&user1, 0),
+            Err("Withdraw your pool shares before joining an expedition".to_string())
+        );
+
+        let p = business_state.player.get_mut(&user1).unwrap();
+        p.lp_shares.clear();
+        business_state.craft(user1, Item::Bucket, 0)?;
+        assert_eq!(
+            business_state.join_expedition(&user1, 0),
+            Err(
+                "Collect your crafting jobs once they are done before joining an expedition"
+                    .to_string()
+            )
+        );
+
+        business_state.collect_crafted(user1, 60 * 60 * MILLIS_TO_SECONDS)?;
+        business_state.join_expedition(&user1, 0)?;
+        assert_eq!(business_state.expeditions[&0].members, vec![user2, user1]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_expedition_has_enough_resources() -> Result<(), String> {
+        let mut business_state = BusinessState::default();
+
+        let user1: Principal = Principal::from_slice(&[1]);
+
+        business_state.player.insert(
+            user1,
+            PlayerState {
+                status: PlayerStatus::Idle,
+                inventory: Inventory {
+                    size: 0,
+                    escrow: Default::default(),
+                    contents: HashMap::from([
+                        (Resources::Wood, 100),
+                        (Resources::Stone, 100),
+                        (Resources::Food, 100),
+                        (Resources::Water, 100),
+                    ]),
+                },
+                ..Default::default()
+            },
+        );
+
+        business_state.propose_expedition(user1, 2)?;
+
+        assert_eq!(
+            business_state
+                .expeditions
+                .get(&0)
+                .unwrap()
+                .has_enough_resources(),
+            false
+        );
+
+        business_state
+            .expeditions
+            .get_mut(&0)
+            .unwrap()
+            .add_resources(&HashMap::from([
+                (Resources::Wood, 1000),
+                (Resources::Stone, 1000),
+                (Resources::Food, 1000),
+                (Resources::Water, 1000),
+            ]))?;
+
+        assert_eq!(
+            business_state
+                .expeditions
+                .get(&0)
+                .unwrap()
+                .has_enough_resources(),
+            true
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_add_player() -> Result<(), String> {
+        let mut business_state = BusinessState::default();
+
+        let user1: Principal = Principal::from_slice(&[1]);
+
+        assert_eq!(business_state.player.contains_key(&user1), false);
+
+        business_state.add_player(user1, 0)?;
+
+        assert_eq!(business_state.player.contains_key(&user1), true);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_is_player_in_world() -> Result<(), String> {
+        let mut business_state = BusinessState::default();
+        let user1: Principal = Principal::from_slice(&[1]);
+
+        assert_eq!(business_state.is_player_in_world(user1), false);
+
+        business_state.add_player(user1, 0)?;
+
+        assert_eq!(business_state.is_player_in_world(user1), true);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_inventory_getters() -> Result<(), String> {
+        let player_state = PlayerState {
+            status: PlayerStatus::Idle,
+            inventory: Inventory {
+                size: 0,
+                escrow: Default::default(),
+                contents: HashMap::from([
+                    (Resources::Wood, 100),
+                    (Resources::Stone, 100),
+                    (Resources::Food, 100),
+                    (Resources::Water, 100),
+                ]),
+            },
+            ..Default::default()
+        };
+
+        let mut a = player_state.inventory.get_all();
+        let mut b = vec![
+            (Resources::Wood, 100),
+            (Resources::Stone, 100),
+            (Resources::Food, 100),
+            (Resources::Water, 100),
+        ];
+        a.sort();
+        b.sort();
+        assert_eq!(a, b);
+
+        assert_eq!(player_state.inventory.get(Resources::Wood), 100);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_tax_inbound_inventory() -> Result<(), String> {
+        let mut business_state = BusinessState::default();
+
+        let user1: Principal = Principal::from_slice(&[1]);
+        let player_state = PlayerState {
+            status: PlayerStatus::Idle,
+            inventory: Inventory {
+                size: 0,
+                escrow: Default::default(),
+                contents: HashMap::from([
+                    (Resources::Wood, 100),
+                    (Resources::Stone, 100),
+                    (Resources::Food, 100),
+                    (Resources::Water, 100),
+                ]),
+            },
+            ..Default::default()
+        };
+
+        assert_eq!(business_state.player.contains_key(&user1), false);
+
+        business_state.add_traveler(user1, player_state)?;
+
+        assert_eq!(business_state.player.contains_key(&user1), true);
+
+        assert_eq!(
+            business_state
+                .player
+                .get(&user1)
+                .unwrap()
+                .inventory
+                .has_available_resources(&HashMap::from([
+                    (Resources::Wood, 90),
+                    (Resources::Stone, 90),
+                    (Resources::Food, 90),
+                    (Resources::Water, 90),
+                ])),
+            true
+        );
+
+        assert_eq!(
+            business_state
+                .player
+                .get(&user1)
+                .unwrap()
+                .inventory
+                .has_available_resources(&HashMap::from([
+                    (Resources::Wood, 91),
+                    (Resources::Stone, 91),
+                    (Resources::Food, 91),
+                    (Resources::Water, 91),
+                ])),
+            false
+        );
+        assert_eq!(business_state.colony.coffers.get(Resources::Wood), 10);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_taxes_above_the_coffers_capacity_stay_with_the_player() -> Result<(), String> {
+        let mut business_state = BusinessState::default();
+        business_state.colony.coffers.size = 50;
+        business_state
+            .colony
+            .add_to_coffers(&HashMap::from([(Resources::Wood, 45)]));
+
+        let user1: Principal = Principal::from_slice(&[1]);
+        let player_state = PlayerState {
+            inventory: Inventory {
+                size: 0,
+                escrow: Default::default(),
+                contents: HashMap::from([(Resources::Wood, 100), (Resources::Stone, 100)]),
+            },
+            ..Default::default()
+        };
+        business_state.add_traveler(user1, player_state)?;
+
+        let inventory = &business_state.player[&user1].inventory;
+        assert_eq!(inventory.get(Resources::Wood), 95);
+        assert_eq!(inventory.get(Resources::Stone), 90);
+        assert_eq!(business_state.colony.coffers.get(Resources::Wood), 50);
+        assert_eq!(business_state.colony.coffers.get(Resources::Stone), 10);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,619 @@
+use std::collections::HashMap;
+
+use candid::{CandidType, Deserialize, Principal};
+
+use super::migrations::v1;
+use super::{not_registered, BusinessState, Resources};
+
+/// Swap fees are expressed in basis points, 1/100th of a percent
+const FEE_DENOMINATOR: u128 = 10_000;
+
+/// Pools are always stored under the pair sorted by `Resources`' ordering, so (Wood, Stone) and
+/// (Stone, Wood) end up in the same pool.
+pub type PoolKey = (Resources, Resources);
+
+fn pool_key(a: Resources, b: Resources) -> PoolKey {
+    if a <= b {
+        (a, b)
+    } else {
+        (b, a)
+    }
+}
+
+#[derive(CandidType, Deserialize, Debug)]
+pub struct AmmState {
+    pools: HashMap<PoolKey, Pool>,
+    /// Taken from the input of every swap and sent to the colony's coffers
+    swap_fee_basis_points: u16,
+}
+
+impl Default for AmmState {
+    fn default() -> Self {
+        Self {
+            pools: Default::default(),
+            swap_fee_basis_points: 30,
+        }
+    }
+}
+
+#[derive(CandidType, Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct Pool {
+    /// Reserve of the first resource of the pool key
+    reserve_a: u64,
+    /// Reserve of the second resource of the pool key
+    reserve_b: u64,
+    total_shares: u64,
+    /// Shares owned by the colony itself, minted when a pool is seeded from the coffers.
+    /// Player shares are recorded on each `PlayerState`.
+    colony_shares: u64,
+}
+
+/// Resources deposited into a pool, either by a player or from the coffers
+#[derive(CandidType, Deserialize, Debug, Clone)]
+pub struct PoolDeposit {
+    pub a: Resources,
+    pub b: Resources,
+    pub amount_a: u64,
+    pub amount_b: u64,
+}
+
+#[derive(CandidType, Deserialize, Debug, Clone)]
+pub struct SwapArgs {
+    pub from: Resources,
+    pub to: Resources,
+    pub amount_in: u64,
+    /// The swap fails if it would pay out less than this
+    pub min_amount_out: u64,
+}
+
+#[derive(CandidType, Deserialize, Debug, Clone, PartialEq)]
+pub struct PoolInfo {
+    a: Resources,
+    b: Resources,
+    pool: Pool,
+}
+
+impl Pool {
+    /// Shares minted for depositing up to `amount_a` and `amount_b`, and the amounts that are
+    /// actually taken to keep the pool's ratio.
+    fn shares_for_deposit(&self, amount_a: u64, amount_b: u64) -> (u64, u64, u64) {
+        if self.total_shares == 0 {
+            return (
+                isqrt(amount_a as u128 * amount_b as u128),
+                amount_a,
+                amount_b,
+            );
+        }
+
+        let total = self.total_shares as u128;
+        let shares = (amount_a as u128 * total / self.reserve_a as u128)
+            .min(amount_b as u128 * total / self.reserve_b as u128);
+
+        let used_a = (shares * self.reserve_a as u128).div_ceil(total);
+        let used_b = (shares * self.reserve_b as u128).div_ceil(total);
+
+        (shares as u64, used_a as u64, used_b as u64)
+    }
+
+    fn deposit(&mut self, amount_a: u64, amount_b: u64) -> Result<(u64, u64, u64), String> {
+        let (shares, used_a, used_b) = self.shares_for_deposit(amount_a, amount_b);
+
+        if shares == 0 {
+            return Err("The deposit is too small to mint any pool shares".to_string());
+        }
+
+        self.reserve_a += used_a;
+        self.reserve_b += used_b;
+        self.total_shares += shares;
+
+        Ok((shares, used_a, used_b))
+    }
+
+    fn withdraw(&mut self, shares: u64) -> Result<(u64, u64), String> {
+        if shares > self.total_shares {
+            return Err("The pool doesn't have that many shares".to_string());
+        }
+
+        let total = self.total_shares as u128;
+        let amount_a = (shares as u128 * self.reserve_a as u128 / total) as u64;
+        let amount_b = (shares as u128 * self.reserve_b as u128 / total) as u64;
+
+        match (
+            self.reserve_a.checked_sub(amount_a),
+            self.reserve_b.checked_sub(amount_b),
+            self.total_shares.checked_sub(shares),
+        ) {
+            (Some(reserve_a), Some(reserve_b), Some(total_shares)) => {
+                self.reserve_a = reserve_a;
+                self.reserve_b = reserve_b;
+                self.total_shares = total_shares;
+
+                Ok((amount_a, amount_b))
+            }
+            _ => Err("The pool doesn't have that many shares".to_string()),
+        }
+    }
+
+    fn reserves(&self, key: PoolKey, from: Resources) -> (u64, u64) {
+        if from == key.0 {
+            (self.reserve_a, self.reserve_b)
+        } else {
+            (self.reserve_b, self.reserve_a)
+        }
+    }
+}
+
+impl BusinessState {
+    /// Seeds a pool with resources from the colony's coffers. The shares minted for the deposit
+    /// belong to the colony.
+    pub fn seed_pool(&mut self, deposit: PoolDeposit) -> Result<u64, String> {
+        let (key, amount_a, amount_b) = Self::normalize_deposit(&deposit)?;
+
+        let required = HashMap::from([(key.0, amount_a), (key.1, amount_b)]);
+        if !self.colony.coffers.has_available_resources(&required) {
+            return Err("The colony's coffers don't have enough resources".to_string());
+        }
+
+        let pool = self.amm.pools.entry(key).or_default();
+        let (shares, used_a, used_b) = pool.deposit(amount_a, amount_b)?;
+        pool.colony_shares += shares;
+
+        self.colony
+            .coffers
+            .subtract_resources(&HashMap::from([(key.0, used_a), (key.1, used_b)]));
+
+        Ok(shares)
+    }
+
+    /// Adds liquidity to an existing pool. At most `amount_a` and `amount_b` are taken from the
+    /// player, in the ratio of the pool's current reserves.
+    pub fn add_liquidity(
+        &mut self,
+        principal: Principal,
+        deposit: PoolDeposit,
+    ) -> Result<u64, String> {
+        let (key, amount_a, amount_b) = Self::normalize_deposit(&deposit)?;
+
+        let p = self.player.get_mut(&principal).ok_or_else(not_registered)?;
+
+        let pool = match self.amm.pools.get_mut(&key) {
+            Some(pool) if pool.total_shares > 0 => pool,
+            _ => return Err("This pool hasn't been seeded yet".to_string()),
+        };
+
+        let required = HashMap::from([(key.0, amount_a), (key.1, amount_b)]);
+        if !p.inventory.has_available_resources(&required) {
+            return Err("The player doesn't have enough resources".to_string());
+        }
+
+        let (shares, used_a, used_b) = pool.deposit(amount_a, amount_b)?;
+
+        p.inventory
+            .subtract_resources(&HashMap::from([(key.0, used_a), (key.1, used_b)]));
+        *p.lp_shares.entry(key).or_insert(0) += shares;
+
+        Ok(shares)
+    }
+
+    /// Burns a player's pool shares and pays out their part of both reserves.
+    pub fn remove_liquidity(
+        &mut self,
+        principal: Principal,
+        a: Resources,
+        b: Resources,
+        shares: u64,
+    ) -> Result<Vec<(Resources, u64)>, String> {
+        let key = pool_key(a, b);
+
+        let p = self.player.get_mut(&principal).ok_or_else(not_registered)?;
+
+        if shares == 0 || *p.lp_shares.get(&key).unwrap_or(&0) < shares {
+            return Err("The player doesn't own enough shares of this pool".to_string());
+        }
+
+        let pool = self
+            .amm
+            .pools
+            .get_mut(&key)
+            .ok_or_else(|| "Can't find pool".to_string())?;
+
+        let (amount_a, amount_b) = pool.withdraw(shares)?;
+
+        let owned = p.lp_shares.entry(key).or_insert(0);
+        *owned -= shares;
+        if *owned == 0 {
+            p.lp_shares.remove(&key);
+        }
+
+        let payout = vec![(key.0, amount_a), (key.1, amount_b)];
+        p.inventory.add_resources(&payout.iter().cloned().collect());
+
+        Ok(payout)
+    }
+
+    /// How much of `to` a swap of `amount_in` would pay out right now, after the fee.
+    pub fn swap_quote(
+        &self,
+        from: Resources,
+        to: Resources,
+        amount_in: u64,
+    ) -> Result<u64, String> {
+        let (_, amount_out) = self.swap_amounts(from, to, amount_in)?;
+
+        Ok(amount_out)
+    }
+
+    /// Swaps resources against a pool using the constant product formula. The fee is taken from
+    /// the input and goes to the colony's coffers.
+    pub fn swap(&mut self, principal: Principal, args: SwapArgs) -> Result<u64, String> {
+        if !self.player.contains_key(&principal) {
+            return Err(not_registered());
+        }
+
+        let (fee, amount_out) = self.swap_amounts(args.from, args.to, args.amount_in)?;
+
+        if amount_out < args.min_amount_out {
+            return Err(format!(
+                "The swap would pay out {} {:?}, less than the minimum of {}",
+                amount_out, args.to, args.min_amount_out
+            ));
+        }
+
+        let p = self.player.get_mut(&principal).ok_or_else(not_registered)?;
+
+        if !p
+            .inventory
+            .has_available_resources(&HashMap::from([(args.from, args.amount_in)]))
+        {
+            return Err("The player doesn't have enough resources".to_string());
+        }
+
+        p.inventory
+            .subtract_resources(&HashMap::from([(args.from, args.amount_in)]));
+        p.inventory
+            .add_resources(&HashMap::from([(args.to, amount_out)]));
+
+        // A fee that doesn't fit in the coffers stays in the pool
+        let rejected = self
+            .colony
+            .add_to_coffers(&HashMap::from([(args.from, fee)]));
+
+        let key = pool_key(args.from, args.to);
+        let pool = self.amm.pools.get_mut(&key).unwrap();
+        let amount_in = args.amount_in - fee + rejected.get(&args.from).unwrap_or(&0);
+        if args.from == key.0 {
+            pool.reserve_a += amount_in;
+            pool.reserve_b -= amount_out;
+        } else {
+            pool.reserve_b += amount_in;
+            pool.reserve_a -= amount_out;
+        }
+
+        Ok(amount_out)
+    }
+
+    /// Returns the fee and the amount paid out for a swap
+    fn swap_amounts(
+        &self,
+        from: Resources,
+        to: Resources,
+        amount_in: u64,
+    ) -> Result<(u64, u64), String> {
+        if from == to {
+            return Err("A swap needs two different resources".to_string());
+        }
+
+        let key = pool_key(from, to);
+        let pool = match self.amm.pools.get(&key) {
+            Some(pool) if pool.total_shares > 0 => pool,
+            _ => return Err("Can't find pool".to_string()),
+        };
+
+        let fee =
+            (amount_in as u128 * self.amm.swap_fee_basis_points as u128 / FEE_DENOMINATOR) as u64;
+        let amount_in = (amount_in - fee) as u128;
+
+        let (reserve_in, reserve_out) = pool.reserves(key, from);
+        let amount_out = reserve_out as u128 * amount_in / (reserve_in as u128 + amount_in);
+
+        if amount_out == 0 {
+            return Err("The swap is too small".to_string());
+        }
+
+        Ok((fee, amount_out as u64))
+    }
+
+    pub fn set_swap_fee(&mut self, basis_points: u16) -> Result<(), String> {
+        if basis_points as u128 > FEE_DENOMINATOR {
+            return Err("The swap fee can't be more than 100%".to_string());
+        }
+
+        self.amm.swap_fee_basis_points = basis_points;
+
+        Ok(())
+    }
+
+    pub fn pools(&self) -> Vec<PoolInfo> {
+        let mut pools: Vec<PoolInfo> = self
+            .amm
+            .pools
+            .iter()
+            .map(|(key, pool)| PoolInfo {
+                a: key.0,
+                b: key.1,
+                pool: pool.clone(),
+            })
+            .collect();
+        pools.sort_by_key(|p| (p.a, p.b));
+
+        pools
+    }
+
+    fn normalize_deposit(deposit: &PoolDeposit) -> Result<(PoolKey, u64, u64), String> {
+        if deposit.a == deposit.b {
+            return Err("A pool needs two different resources".to_string());
+        }
+
+        if deposit.amount_a == 0 || deposit.amount_b == 0 {
+            return Err("Both amounts must be greater than zero".to_string());
+        }
+
+        let key = pool_key(deposit.a, deposit.b);
+        if key.0 == deposit.a {
+            Ok((key, deposit.amount_a, deposit.amount_b))
+        } else {
+            Ok((key, deposit.amount_b, deposit.amount_a))
+        }
+    }
+}
+
+fn isqrt(n: u128) -> u64 {
+    if n < 2 {
+        return n as u64;
+    }
+
+    let mut x = n;
+    let mut y = x.div_ceil(2);
+    while y < x {
+        x = y;
+        y = (x + n / x) / 2;
+    }
+
+    x as u64
+}
+
+impl From<v1::AmmState> for AmmState {
+    fn from(amm: v1::AmmState) -> Self {
+        Self {
+            pools: amm
+                .pools
+                .into_iter()
+                .map(|(key, p)| {
+                    let pool = Pool {
+                        reserve_a: p.reserve_a,
+                        reserve_b: p.reserve_b,
+                        total_shares: p.total_shares,
+                        colony_shares: p.colony_shares,
+                    };
+                    (key, pool)
+                })
+                .collect(),
+            swap_fee_basis_points: amm.swap_fee_basis_points,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::business_logic::{Inventory, PlayerState};
+
+    fn setup() -> (BusinessState, Principal) {
+        let mut business_state = BusinessState::default();
+        business_state.colony.coffers.add_resources(&HashMap::from([
+            (Resources::Wood, 10_000),
+            (Resources::Stone, 10_000),
+        ]));
+
+        let user1: Principal = Principal::from_slice(&[1]);
+        business_state.player.insert(
+            user1,
+            PlayerState {
+                inventory: Inventory {
+                    contents: HashMap::from([(Resources::Wood, 1000), (Resources::Stone, 1000)]),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+        );
+
+        (business_state, user1)
+    }
+
+    fn deposit(a: Resources, b: Resources, amount_a: u64, amount_b: u64) -> PoolDeposit {
+        PoolDeposit {
+            a,
+            b,
+            amount_a,
+            amount_b,
+        }
+    }
+
+    #[test]
+    fn test_seed_pool_from_coffers() -> Result<(), String> {
+        let (mut business_state, _) = setup();
+
+        let shares =
+            business_state.seed_pool(deposit(Resources::Wood, Resources::Stone, 4000, 1000))?;
+        assert_eq!(shares, 2000);
+
+        assert_eq!(business_state.colony.coffers.get(Resources::Wood), 6000);
+        assert_eq!(business_state.colony.coffers.get(Resources::Stone), 9000);
+
+        let pool = business_state
+            .amm
+            .pools
+            .get(&(Resources::Wood, Resources::Stone))
+            .unwrap();
+        assert_eq!((pool.reserve_a, pool.reserve_b), (4000, 1000));
+        assert_eq!(pool.colony_shares, 2000);
+
+        assert_eq!(
+            business_state.seed_pool(deposit(Resources::Wood, Resources::Gold, 1, 1)),
+            Err("The colony's coffers don't have enough resources".to_string())
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_swap_with_fee_and_slippage() -> Result<(), String> {
+        let (mut business_state, user1) = setup();
+
+        business_state.seed_pool(deposit(Resources::Wood, Resources::Stone, 5000, 5000))?;
+
+        // 1000 in, 3 as fee, 997 go into the pool: 5000 * 997 / 5997 = 831
+        assert_eq!(
+            business_state.swap_quote(Resources::Wood, Resources::Stone, 1000),
+            Ok(831)
+        );
+
+        assert_eq!(
+            business_state.swap(
+                user1,
+                SwapArgs {
+                    from: Resources::Wood,
+                    to: Resources::Stone,
+                    amount_in: 1000,
+                    min_amount_out: 900,
+                }
+            ),
+            Err("The swap would pay out 831 Stone, less than the minimum of 900".to_string())
+        );
+
+        let out = business_state.swap(
+            user1,
+            SwapArgs {
+                from: Resources::Wood,
+                to: Resources::Stone,
+                amount_in: 1000,
+                min_amount_out: 800,
+            },
+        )?;
+        assert_eq!(out, 831);
+
+        let inventory = &business_state.player.get(&user1).unwrap().inventory;
+        assert_eq!(inventory.get(Resources::Wood), 0);
+        assert_eq!(inventory.get(Resources::Stone), 1831);
+
+        assert_eq!(business_state.colony.coffers.get(Resources::Wood), 5003);
+
+        let pool = business_state
+            .amm
+            .pools
+            .get(&(Resources::Wood, Resources::Stone))
+            .unwrap();
+        assert_eq!((pool.reserve_a, pool.reserve_b), (5997, 4169));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_add_and_remove_liquidity() -> Result<(), String> {
+        let (mut business_state, user1) = setup();
+
+        assert_eq!(
+            business_state
+                .add_liquidity(user1, deposit(Resources::Wood, Resources::Stone, 100, 100)),
+            Err("This pool hasn't been seeded yet".to_string())
+        );
+
+        business_state.seed_pool(deposit(Resources::Wood, Resources::Stone, 2000, 1000))?;
+
+        // Only 200 wood fit the pool's 2:1 ratio for 100 stone
+        let shares = business_state
+            .add_liquidity(user1, deposit(Resources::Wood, Resources::Stone, 500, 100))?;
+        assert_eq!(shares, 141);
+
+        let p = business_state.player.get(&user1).unwrap();
+        assert_eq!(
+            p.lp_shares.get(&(Resources::Wood, Resources::Stone)),
+            Some(&141)
+        );
+        assert_eq!(p.inventory.get(Resources::Wood), 800);
+        assert_eq!(p.inventory.get(Resources::Stone), 900);
+
+        assert_eq!(
+            business_state.remove_liquidity(user1, Resources::Wood, Resources::Stone, 142),
+            Err("The player doesn't own enough shares of this pool".to_string())
+        );
+
+        // Asking for (Stone, Wood) reaches the same pool
+        let payout =
+            business_state.remove_liquidity(user1, Resources::Stone, Resources::Wood, 141)?;
+        assert_eq!(payout, vec![(Resources::Wood, 199), (Resources::Stone, 99)]);
+
+        let p = business_state.player.get(&user1).unwrap();
+        assert!(p.lp_shares.is_empty());
+        assert_eq!(p.inventory.get(Resources::Wood), 999);
+        assert_eq!(p.inventory.get(Resources::Stone), 999);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_shares_dont_travel() -> Result<(), String> {
+        let (mut origin, user1) = setup();
+        origin.seed_pool(deposit(Resources::Wood, Resources::Stone, 2000, 1000))?;
+        origin.add_liquidity(user1, deposit(Resources::Wood, Resources::Stone, 500, 100))?;
+
+        let (mut destination, _) = setup();
+        destination.player.clear();
+        destination.seed_pool(deposit(Resources::Wood, Resources::Stone, 20, 10))?;
+
+        let traveler = origin.player.remove(&user1).unwrap();
+        destination.add_traveler(user1, traveler)?;
+
+        assert_eq!(
+            destination.remove_liquidity(user1, Resources::Wood, Resources::Stone, 141),
+            Err("The player doesn't own enough shares of this pool".to_string())
+        );
+
+        // Shares the pool never minted can't be redeemed either, whatever the player holds
+        destination
+            .player
+            .get_mut(&user1)
+            .unwrap()
+            .lp_shares
+            .insert((Resources::Wood, Resources::Stone), 141);
+        assert_eq!(
+            destination.remove_liquidity(user1, Resources::Wood, Resources::Stone, 141),
+            Err("The pool doesn't have that many shares".to_string())
+        );
+        assert_eq!(
+            destination.amm.pools[&(Resources::Wood, Resources::Stone)].reserve_a,
+            20
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_set_swap_fee() -> Result<(), String> {
+        let (mut business_state, _) = setup();
+
+        business_state.set_swap_fee(0)?;
+        business_state.seed_pool(deposit(Resources::Wood, Resources::Stone, 5000, 5000))?;
+
+        assert_eq!(
+            business_state.swap_quote(Resources::Stone, Resources::Wood, 1000),
+            Ok(833)
+        );
+        assert_eq!(
+            business_state.set_swap_fee(10_001),
+            Err("The swap fee can't be more than 100%".to_string())
+        );
+
+        Ok(())
+    }
+}
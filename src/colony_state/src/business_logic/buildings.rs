@@ -0,0 +1,346 @@
+use std::collections::HashMap;
+
+use candid::{CandidType, Deserialize, Principal};
+
+use super::migrations::v1;
+use super::{not_registered, BusinessState, ColonyState, Inventory, Resources};
+
+/// Buildings can't be levelled up past this
+pub const MAX_BUILDING_LEVEL: u8 = 10;
+
+/// How much each Warehouse level adds to the coffers' capacity, per resource
+const WAREHOUSE_CAPACITY_PER_LEVEL: u32 = 50_000;
+
+/// How much each level of a production building adds to its resource's rewards_per_second
+const REWARDS_PER_LEVEL: u8 = 2;
+
+#[derive(CandidType, Deserialize, Debug, Hash, PartialEq, Eq, Copy, Clone, Ord, PartialOrd)]
+pub enum Building {
+    /// Raises the rewards for Wood
+    Sawmill,
+    /// Raises the rewards for Stone
+    Quarry,
+    /// Raises the rewards for Food
+    Farm,
+    /// Raises the rewards for Water
+    Well,
+    /// Raises the capacity of the colony's coffers
+    Warehouse,
+}
+
+impl Building {
+    const ALL: [Building; 5] = [
+        Building::Sawmill,
+        Building::Quarry,
+        Building::Farm,
+        Building::Well,
+        Building::Warehouse,
+    ];
+
+    /// Resources needed to build the first level. Each following level costs the same amount
+    /// times the level being built.
+    fn base_cost(&self) -> HashMap<Resources, u64> {
+        match self {
+            Building::Sawmill => HashMap::from([(Resources::Wood, 200), (Resources::Stone, 400)]),
+            Building::Quarry => HashMap::from([(Resources::Wood, 400), (Resources::Stone, 200)]),
+            Building::Farm => HashMap::from([(Resources::Wood, 300), (Resources::Water, 300)]),
+            Building::Well => HashMap::from([(Resources::Wood, 100), (Resources::Stone, 500)]),
+            Building::Warehouse => HashMap::from([
+                (Resources::Wood, 500),
+                (Resources::Stone, 500),
+                (Resources::Food, 200),
+            ]),
+        }
+    }
+
+    fn cost_for_level(&self, level: u8) -> HashMap<Resources, u64> {
+        self.base_cost()
+            .into_iter()
+            .map(|(res, val)| (res, val * level as u64))
+            .collect()
+    }
+
+    /// Applies the effect of reaching a new level to the colony
+    fn level_up(&self, colony: &mut ColonyState) {
+        let res = match self {
+            Building::Sawmill => Resources::Wood,
+            Building::Quarry => Resources::Stone,
+            Building::Farm => Resources::Food,
+            Building::Well => Resources::Water,
+            // Coffers without a limit stay that way
+            Building::Warehouse if colony.coffers.size == 0 => return,
+            Building::Warehouse => {
+                colony.coffers.size = colony
+                    .coffers
+                    .size
+                    .saturating_add(WAREHOUSE_CAPACITY_PER_LEVEL);
+                return;
+            }
+        };
+
+        let rate = colony.rewards_per_second.entry(res).or_insert(0);
+        *rate = rate.saturating_add(REWARDS_PER_LEVEL);
+    }
+}
+
+#[derive(CandidType, Deserialize, Debug, Default, Clone)]
+pub struct BuildingState {
+    level: u8,
+    /// Resources contributed towards the next level
+    progress: Inventory,
+}
+
+/// What `ColonyInfo` shows for each building
+#[derive(CandidType, Deserialize, Debug, Clone, PartialEq)]
+pub struct BuildingProgress {
+    building: Building,
+    level: u8,
+    /// Resources contributed towards the next level
+    contributed: Vec<(Resources, u64)>,
+    /// Resources needed for the next level. Empty once the building is at its maximum level.
+    required: Vec<(Resources, u64)>,
+}
+
+impl BusinessState {
+    /// Takes resources from a player to fund the next level of a building. Only what the building
+    /// still needs is taken. Once every requirement is met the building levels up, its effect is
+    /// applied to the colony and funding for the next level starts from zero.
+    pub fn contribute_to_building(
+        &mut self,
+        principal: Principal,
+        building: Building,
+        resources: HashMap<Resources, u64>,
+    ) -> Result<BuildingProgress, String> {
+        let level = self.buildings.get(&building).map_or(0, |state| state.level);
+
+        if level >= MAX_BUILDING_LEVEL {
+            return Err(format!("{:?} is already at its maximum level", building));
+        }
+
+        let p = self.player.get_mut(&principal).ok_or_else(not_registered)?;
+
+        if !p.inventory.has_available_resources(&resources) {
+            return Err("The player doesn't have enough resources".to_string());
+        }
+
+        let required = building.cost_for_level(level + 1);
+        let buildings = &self.buildings;
+        let contributed = |res: Resources| {
+            buildings
+                .get(&building)
+                .map_or(0, |state| state.progress.get(res))
+        };
+
+        let accepted: HashMap<Resources, u64> = resources
+            .iter()
+            .filter_map(|(res, val)| {
+                let missing = required.get(res)?.saturating_sub(contributed(*res));
+                Some((*res, missing.min(*val)))
+            })
+            .filter(|(_, val)| *val > 0)
+            .collect();
+
+        if accepted.is_empty() {
+            return Err(format!(
+                "{:?} doesn't need any of these resources",
+                building
+            ));
+        }
+
+        p.inventory.subtract_resources(&accepted);
+        let state = self.buildings.entry(building).or_default();
+        state.progress.add_resources(&accepted);
+
+        if state.progress.has_available_resources(&required) {
+            state.level += 1;
+            state.progress = Inventory::default();
+            building.level_up(&mut self.colony);
+        }
+
+        Ok(self.building_progress(building))
+    }
+
+    pub fn building_progress(&self, building: Building) -> BuildingProgress {
+        let state = self.buildings.get(&building).cloned().unwrap_or_default();
+
+        let mut contributed = state.progress.get_all();
+        contributed.sort();
+
+        let mut required: Vec<(Resources, u64)> = if state.level < MAX_BUILDING_LEVEL {
+            building
+                .cost_for_level(state.level + 1)
+                .into_iter()
+                .collect()
+        } else {
+            vec![]
+        };
+        required.sort();
+
+        BuildingProgress {
+            building,
+            level: state.level,
+            contributed,
+            required,
+        }
+    }
+
+    pub fn all_building_progress(&self) -> Vec<BuildingProgress> {
+        Building::ALL
+            .iter()
+            .map(|building| self.building_progress(*building))
+            .collect()
+    }
+}
+
+impl From<v1::BuildingState> for BuildingState {
+    fn from(state: v1::BuildingState) -> Self {
+        Self {
+            level: state.level,
+            progress: state.progress.into(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::business_logic::PlayerState;
+
+    fn setup() -> (BusinessState, Principal, Principal) {
+        let mut business_state = BusinessState::default();
+
+        let user1: Principal = Principal::from_slice(&[1]);
+        let user2: Principal = Principal::from_slice(&[2]);
+
+        for user in [user1, user2] {
+            let mut player = PlayerState::default();
+            player.inventory.add_resources(&HashMap::from([
+                (Resources::Wood, 1000),
+                (Resources::Stone, 1000),
+                (Resources::Food, 1000),
+            ]));
+            business_state.player.insert(user, player);
+        }
+
+        (business_state, user1, user2)
+    }
+
+    #[test]
+    fn test_players_fund_a_building_together() -> Result<(), String> {
+        let (mut business_state, user1, user2) = setup();
+
+        let progress = business_state.contribute_to_building(
+            user1,
+            Building::Sawmill,
+            HashMap::from([(Resources::Wood, 200), (Resources::Stone, 100)]),
+        )?;
+        assert_eq!(progress.level, 0);
+        assert_eq!(
+            progress.contributed,
+            vec![(Resources::Wood, 200), (Resources::Stone, 100)]
+        );
+
+        // Only the missing 300 stone are taken, and the food isn't needed at all
+        let progress = business_state.contribute_to_building(
+            user2,
+            Building::Sawmill,
+            HashMap::from([(Resources::Stone, 500), (Resources::Food, 100)]),
+        )?;
+        assert_eq!(progress.level, 1);
+        assert!(progress.contributed.is_empty());
+        assert_eq!(
+            progress.required,
+            vec![(Resources::Wood, 400), (Resources::Stone, 800)]
+        );
+
+        let p = business_state.player.get(&user2).unwrap();
+        assert_eq!(p.inventory.get(Resources::Stone), 700);
+        assert_eq!(p.inventory.get(Resources::Food), 1000);
+
+        assert_eq!(
+            business_state
+                .colony
+                .rewards_per_second
+                .get(&Resources::Wood),
+            Some(&12)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_warehouse_raises_coffers_capacity() -> Result<(), String> {
+        let (mut business_state, user1, _) = setup();
+
+        let capacity = business_state.colony.coffers.size;
+
+        business_state.contribute_to_building(
+            user1,
+            Building::Warehouse,
+            HashMap::from([
+                (Resources::Wood, 500),
+                (Resources::Stone, 500),
+                (Resources::Food, 200),
+            ]),
+        )?;
+
+        assert_eq!(
+            business_state.colony.coffers.size,
+            capacity + WAREHOUSE_CAPACITY_PER_LEVEL
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_contribution_errors() {
+        let (mut business_state, user1, _) = setup();
+
+        assert_eq!(
+            business_state.contribute_to_building(
+                user1,
+                Building::Farm,
+                HashMap::from([(Resources::Stone, 100)]),
+            ),
+            Err("Farm doesn't need any of these resources".to_string())
+        );
+        assert_eq!(
+            business_state.contribute_to_building(
+                user1,
+                Building::Farm,
+                HashMap::from([(Resources::Water, 100)]),
+            ),
+            Err("The player doesn't have enough resources".to_string())
+        );
+        assert_eq!(
+            business_state.contribute_to_building(
+                Principal::from_slice(&[9]),
+                Building::Quarry,
+                HashMap::from([(Resources::Wood, 100)]),
+            ),
+            Err(not_registered())
+        );
+
+        // Failed contributions leave no trace
+        assert!(business_state.buildings.is_empty());
+
+        business_state
+            .buildings
+            .entry(Building::Farm)
+            .or_default()
+            .level = MAX_BUILDING_LEVEL;
+
+        assert_eq!(
+            business_state.contribute_to_building(
+                user1,
+                Building::Farm,
+                HashMap::from([(Resources::Wood, 100)]),
+            ),
+            Err("Farm is already at its maximum level".to_string())
+        );
+        assert!(business_state
+            .building_progress(Building::Farm)
+            .required
+            .is_empty());
+    }
+}
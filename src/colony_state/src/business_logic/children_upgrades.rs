@@ -0,0 +1,183 @@
+use candid::{CandidType, Deserialize, Principal};
+
+use super::BusinessState;
+use crate::env::{TimestampMillis, MILLIS_TO_SECONDS};
+
+/// How long an upgrade of the children can go unfinished before a new one may replace it. An
+/// upgrade whose call never came back would otherwise block every later one.
+const CHILDREN_UPGRADE_TIMEOUT: TimestampMillis = 60 * 60 * MILLIS_TO_SECONDS;
+
+/// An upgrade of every colony this one started to the wasm `wasm_hash`
+#[derive(CandidType, Deserialize, Debug, Clone, PartialEq)]
+pub struct ChildrenUpgrade {
+    pub wasm_hash: String,
+    /// Whether each child upgrades its own children too
+    pub cascade: bool,
+    pub started_at: TimestampMillis,
+    /// Unset while children are still being upgraded
+    pub finished_at: Option<TimestampMillis>,
+    pub children: Vec<ChildUpgrade>,
+}
+
+#[derive(CandidType, Deserialize, Debug, Clone, PartialEq)]
+pub struct ChildUpgrade {
+    pub canister_id: Principal,
+    pub result: Result<(), String>,
+    /// How the upgrade of the child's own children went, when cascading
+    pub children: Option<ChildrenUpgrade>,
+}
+
+impl BusinessState {
+    /// Starts recording a new upgrade of the children and returns the wasm to upgrade them with.
+    /// The upgrade is known by the time it started at.
+    pub fn start_children_upgrade(
+        &mut self,
+        wasm_hash: String,
+        cascade: bool,
+        now: TimestampMillis,
+    ) -> Result<Vec<u8>, String> {
+        if let Some(ChildrenUpgrade {
+            started_at,
+            finished_at: None,
+            ..
+        }) = self.children_upgrade
+        {
+            if now < started_at.saturating_add(CHILDREN_UPGRADE_TIMEOUT) {
+                return Err("The children are already being upgraded".to_string());
+            }
+        }
+
+        let wasm = self
+            .wasm_registry
+            .module(&wasm_hash)
+            .ok_or_else(|| format!("There is no wasm {}", wasm_hash))?
+            .to_vec();
+
+        self.children_upgrade = Some(ChildrenUpgrade {
+            wasm_hash,
+            cascade,
+            started_at: now,
+            finished_at: None,
+            children: vec![],
+        });
+
+        Ok(wasm)
+    }
+
+    /// Records how a child went for the upgrade that started at `started_at`, unless a newer
+    /// upgrade has replaced it
+    pub fn record_child_upgrade(&mut self, started_at: TimestampMillis, child: ChildUpgrade) {
+        if let Some(upgrade) = self.current_children_upgrade(started_at) {
+            upgrade.children.push(child);
+        }
+    }
+
+    pub fn finish_children_upgrade(
+        &mut self,
+        started_at: TimestampMillis,
+        now: TimestampMillis,
+    ) -> Option<ChildrenUpgrade> {
+        let upgrade = self.current_children_upgrade(started_at)?;
+        upgrade.finished_at = Some(now);
+
+        Some(upgrade.clone())
+    }
+
+    fn current_children_upgrade(
+        &mut self,
+        started_at: TimestampMillis,
+    ) -> Option<&mut ChildrenUpgrade> {
+        self.children_upgrade
+            .as_mut()
+            .filter(|upgrade| upgrade.started_at == started_at)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_one_upgrade_at_a_time() {
+        let mut business_state = BusinessState::default();
+        let sha256 =
+            business_state
+                .wasm_registry
+                .add(b"\0asm".to_vec(), None, Principal::anonymous(), 0);
+
+        assert_eq!(
+            business_state.start_children_upgrade("beef".to_string(), false, 10),
+            Err("There is no wasm beef".to_string())
+        );
+        assert_eq!(
+            business_state.start_children_upgrade(sha256.clone(), false, 10),
+            Ok(b"\0asm".to_vec())
+        );
+        assert_eq!(
+            business_state.start_children_upgrade(sha256.clone(), false, 20),
+            Err("The children are already being upgraded".to_string())
+        );
+
+        business_state.record_child_upgrade(
+            10,
+            ChildUpgrade {
+                canister_id: Principal::from_slice(&[1]),
+                result: Ok(()),
+                children: None,
+            },
+        );
+        let upgrade = business_state.finish_children_upgrade(10, 30).unwrap();
+        assert_eq!(upgrade.started_at, 10);
+        assert_eq!(upgrade.finished_at, Some(30));
+        assert_eq!(upgrade.children.len(), 1);
+
+        assert!(business_state
+            .start_children_upgrade(sha256, true, 40)
+            .is_ok());
+    }
+
+    #[test]
+    fn test_stuck_upgrade_times_out() {
+        let mut business_state = BusinessState::default();
+        let sha256 =
+            business_state
+                .wasm_registry
+                .add(b"\0asm".to_vec(), None, Principal::anonymous(), 0);
+        let child = ChildUpgrade {
+            canister_id: Principal::from_slice(&[1]),
+            result: Ok(()),
+            children: None,
+        };
+
+        // The first upgrade never finishes
+        business_state
+            .start_children_upgrade(sha256.clone(), false, 10)
+            .unwrap();
+        assert_eq!(
+            business_state.start_children_upgrade(
+                sha256.clone(),
+                false,
+                10 + CHILDREN_UPGRADE_TIMEOUT - 1
+            ),
+            Err("The children are already being upgraded".to_string())
+        );
+
+        let restarted_at = 10 + CHILDREN_UPGRADE_TIMEOUT;
+        assert!(business_state
+            .start_children_upgrade(sha256, false, restarted_at)
+            .is_ok());
+
+        // Whatever the stuck upgrade reports late doesn't end up in the new one
+        business_state.record_child_upgrade(10, child.clone());
+        assert_eq!(
+            business_state.finish_children_upgrade(10, restarted_at),
+            None
+        );
+
+        business_state.record_child_upgrade(restarted_at, child);
+        let upgrade = business_state
+            .finish_children_upgrade(restarted_at, restarted_at + 1)
+            .unwrap();
+        assert_eq!(upgrade.children.len(), 1);
+    }
+}
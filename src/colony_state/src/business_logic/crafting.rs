@@ -0,0 +1,394 @@
+use std::collections::HashMap;
+
+use candid::{CandidType, Deserialize, Principal};
+
+use super::migrations::v1;
+use super::{not_registered, BusinessState, PlayerState, Resources};
+use crate::env::{TimestampMillis, MILLIS_TO_SECONDS};
+
+#[derive(CandidType, Deserialize, Debug, Hash, PartialEq, Eq, Copy, Clone, Ord, PartialOrd)]
+pub enum Item {
+    Axe,
+    Pickaxe,
+    Bucket,
+    Sickle,
+}
+
+#[derive(CandidType, Deserialize, Debug, Clone, PartialEq)]
+pub struct Recipe {
+    item: Item,
+    inputs: HashMap<Resources, u64>,
+    crafting_seconds: u64,
+    /// Resources whose yield the item raises while the player works
+    boosts: Vec<Resources>,
+    /// Extra yield, as a percent of the colony's rewards_per_second for the boosted resources
+    bonus_percent: u64,
+    /// Seconds of work the item lasts before it breaks
+    durability: u64,
+}
+
+#[derive(CandidType, Deserialize, Debug)]
+pub struct CraftingState {
+    recipes: HashMap<Item, Recipe>,
+}
+
+impl Default for CraftingState {
+    fn default() -> Self {
+        let recipe = |item, inputs: &[(Resources, u64)], boosts: &[Resources]| Recipe {
+            item,
+            inputs: inputs.iter().cloned().collect(),
+            crafting_seconds: 60,
+            boosts: boosts.to_vec(),
+            bonus_percent: 50,
+            durability: 60 * 60,
+        };
+
+        Self {
+            recipes: HashMap::from([
+                (
+                    Item::Axe,
+                    recipe(
+                        Item::Axe,
+                        &[(Resources::Wood, 50), (Resources::Stone, 100)],
+                        &[Resources::Wood],
+                    ),
+                ),
+                (
+                    Item::Pickaxe,
+                    recipe(
+                        Item::Pickaxe,
+                        &[(Resources::Wood, 100), (Resources::Stone, 50)],
+                        &[Resources::Stone, Resources::Gold],
+                    ),
+                ),
+                (
+                    Item::Bucket,
+                    recipe(Item::Bucket, &[(Resources::Wood, 100)], &[Resources::Water]),
+                ),
+                (
+                    Item::Sickle,
+                    recipe(
+                        Item::Sickle,
+                        &[(Resources::Wood, 50), (Resources::Stone, 50)],
+                        &[Resources::Food],
+                    ),
+                ),
+            ]),
+        }
+    }
+}
+
+/// An item owned by a player
+#[derive(CandidType, Deserialize, Debug, Clone, PartialEq)]
+pub struct ItemState {
+    item: Item,
+    /// Seconds of work left before the item breaks
+    durability: u64,
+}
+
+/// An item that is being crafted and can be collected once `ready_at` has passed
+#[derive(CandidType, Deserialize, Debug, Clone, PartialEq)]
+pub struct CraftingJob {
+    item: Item,
+    ready_at: TimestampMillis,
+}
+
+#[derive(CandidType, Deserialize, Debug, Clone, PartialEq)]
+pub struct PlayerItems {
+    items: Vec<ItemState>,
+    crafting: Vec<CraftingJob>,
+}
+
+impl BusinessState {
+    /// Takes the recipe's inputs from the player and starts crafting the item.
+    pub fn craft(
+        &mut self,
+        principal: Principal,
+        item: Item,
+        now: TimestampMillis,
+    ) -> Result<(), String> {
+        let recipe = self
+            .crafting
+            .recipes
+            .get(&item)
+            .ok_or_else(|| format!("There is no recipe for {:?}", item))?;
+
+        let p = self.player.get_mut(&principal).ok_or_else(not_registered)?;
+
+        if !p.inventory.has_available_resources(&recipe.inputs) {
+            return Err(format!(
+                "The player doesn't have enough resources to craft {:?}",
+                item
+            ));
+        }
+
+        p.inventory.subtract_resources(&recipe.inputs);
+        p.crafting.push(CraftingJob {
+            item,
+            ready_at: now + recipe.crafting_seconds * MILLIS_TO_SECONDS,
+        });
+
+        Ok(())
+    }
+
+    /// Moves every finished crafting job into the player's items and returns what was collected.
+    pub fn collect_crafted(
+        &mut self,
+        principal: Principal,
+        now: TimestampMillis,
+    ) -> Result<Vec<Item>, String> {
+        let recipes = &self.crafting.recipes;
+
+        let p = self.player.get_mut(&principal).ok_or_else(not_registered)?;
+
+        let (finished, pending): (Vec<CraftingJob>, Vec<CraftingJob>) =
+            p.crafting.drain(..).partition(|job| job.ready_at <= now);
+        p.crafting = pending;
+
+        let mut collected = Vec::new();
+        for job in finished {
+            let durability = recipes.get(&job.item).map_or(0, |r| r.durability);
+            p.items.push(ItemState {
+                item: job.item,
+                durability,
+            });
+            collected.push(job.item);
+        }
+
+        Ok(collected)
+    }
+
+    pub fn recipes(&self) -> Vec<Recipe> {
+        let mut recipes: Vec<Recipe> = self.crafting.recipes.values().cloned().collect();
+        recipes.sort_by_key(|r| r.item);
+
+        recipes
+    }
+
+    pub fn player_items(&self, principal: Principal) -> Result<PlayerItems, String> {
+        let p = self.player.get(&principal).ok_or_else(not_registered)?;
+
+        Ok(PlayerItems {
+            items: p.items.clone(),
+            crafting: p.crafting.clone(),
+        })
+    }
+
+    /// Extra resources the player's items add on top of `available_unclaimed` for the given time
+    /// worked. Only the first working item of each kind counts, and it only helps for as long as
+    /// its durability lasts.
+    pub(super) fn item_bonus(
+        &self,
+        player: &PlayerState,
+        seconds_elapsed: u64,
+    ) -> HashMap<Resources, u64> {
+        let mut bonus = HashMap::new();
+
+        for index in active_items(player) {
+            let item = &player.items[index];
+            let recipe = match self.crafting.recipes.get(&item.item) {
+                Some(recipe) => recipe,
+                None => continue,
+            };
+            let seconds = seconds_elapsed.min(item.durability);
+
+            for res in recipe.boosts.iter() {
+                let rate = *self.colony.rewards_per_second.get(res).unwrap_or(&0) as u64;
+                *bonus.entry(*res).or_insert(0) += rate * seconds * recipe.bonus_percent / 100;
+            }
+        }
+
+        bonus.retain(|_, val| *val > 0);
+        bonus
+    }
+
+    /// Wears out the items that were used for `seconds_elapsed` of work. Broken items are removed.
+    pub(super) fn wear_items(player: &mut PlayerState, seconds_elapsed: u64) {
+        for index in active_items(player) {
+            let item = &mut player.items[index];
+            item.durability = item.durability.saturating_sub(seconds_elapsed);
+        }
+
+        player.items.retain(|item| item.durability > 0);
+    }
+}
+
+/// Indexes of the items that are in use: the first item of each kind that isn't broken
+fn active_items(player: &PlayerState) -> Vec<usize> {
+    let mut seen = Vec::new();
+    let mut active = Vec::new();
+
+    for (index, item) in player.items.iter().enumerate() {
+        if item.durability > 0 && !seen.contains(&item.item) {
+            seen.push(item.item);
+            active.push(index);
+        }
+    }
+
+    active
+}
+
+impl From<v1::CraftingState> for CraftingState {
+    fn from(crafting: v1::CraftingState) -> Self {
+        Self {
+            recipes: crafting
+                .recipes
+                .into_iter()
+                .map(|(item, r)| {
+                    let recipe = Recipe {
+                        item: r.item,
+                        inputs: r.inputs,
+                        crafting_seconds: r.crafting_seconds,
+                        boosts: r.boosts,
+                        bonus_percent: r.bonus_percent,
+                        durability: r.durability,
+                    };
+                    (item, recipe)
+                })
+                .collect(),
+        }
+    }
+}
+
+impl From<v1::ItemState> for ItemState {
+    fn from(item: v1::ItemState) -> Self {
+        Self {
+            item: item.item,
+            durability: item.durability,
+        }
+    }
+}
+
+impl From<v1::CraftingJob> for CraftingJob {
+    fn from(job: v1::CraftingJob) -> Self {
+        Self {
+            item: job.item,
+            ready_at: job.ready_at,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::business_logic::Inventory;
+
+    fn setup() -> (BusinessState, Principal) {
+        let mut business_state = BusinessState::default();
+
+        let user1: Principal = Principal::from_slice(&[1]);
+        business_state.player.insert(
+            user1,
+            PlayerState {
+                inventory: Inventory {
+                    contents: HashMap::from([(Resources::Wood, 200), (Resources::Stone, 200)]),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+        );
+
+        (business_state, user1)
+    }
+
+    #[test]
+    fn test_craft_and_collect() -> Result<(), String> {
+        let (mut business_state, user1) = setup();
+
+        business_state.craft(user1, Item::Axe, 0)?;
+
+        let p = business_state.player.get(&user1).unwrap();
+        assert_eq!(p.inventory.get(Resources::Wood), 150);
+        assert_eq!(p.inventory.get(Resources::Stone), 100);
+
+        assert_eq!(
+            business_state.collect_crafted(user1, 59 * MILLIS_TO_SECONDS),
+            Ok(vec![])
+        );
+        assert_eq!(
+            business_state.collect_crafted(user1, 60 * MILLIS_TO_SECONDS),
+            Ok(vec![Item::Axe])
+        );
+
+        assert_eq!(
+            business_state.player_items(user1),
+            Ok(PlayerItems {
+                items: vec![ItemState {
+                    item: Item::Axe,
+                    durability: 3600,
+                }],
+                crafting: vec![],
+            })
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_craft_without_resources() -> Result<(), String> {
+        let (mut business_state, user1) = setup();
+
+        business_state.craft(user1, Item::Bucket, 0)?;
+        business_state.craft(user1, Item::Bucket, 0)?;
+
+        assert_eq!(
+            business_state.craft(user1, Item::Bucket, 0),
+            Err("The player doesn't have enough resources to craft Bucket".to_string())
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_items_boost_work_and_wear_out() -> Result<(), String> {
+        let (mut business_state, user1) = setup();
+
+        business_state.craft(user1, Item::Axe, 0)?;
+        business_state.collect_crafted(user1, 60 * MILLIS_TO_SECONDS)?;
+
+        let p = business_state.player.get_mut(&user1).unwrap();
+        p.items[0].durability = 100;
+
+        business_state.work_set(user1, None, 0)?;
+        business_state.work_claim(user1, 300 * MILLIS_TO_SECONDS)?;
+
+        // 10 wood per second for 300 seconds, plus 50% for the 100 seconds the axe lasted
+        let p = business_state.player.get(&user1).unwrap();
+        assert_eq!(p.inventory.get(Resources::Wood), 150 + 3000 + 500);
+        assert_eq!(p.inventory.get(Resources::Stone), 100 + 3000);
+        assert!(p.items.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_only_one_item_of_each_kind_counts() -> Result<(), String> {
+        let (business_state, _) = setup();
+
+        let player = PlayerState {
+            items: vec![
+                ItemState {
+                    item: Item::Pickaxe,
+                    durability: 0,
+                },
+                ItemState {
+                    item: Item::Pickaxe,
+                    durability: 10,
+                },
+                ItemState {
+                    item: Item::Pickaxe,
+                    durability: 10,
+                },
+            ],
+            ..Default::default()
+        };
+
+        // Gold isn't rewarded in a generation 0 colony, so it gets no bonus either
+        assert_eq!(
+            business_state.item_bonus(&player, 60),
+            HashMap::from([(Resources::Stone, 50)])
+        );
+
+        Ok(())
+    }
+}
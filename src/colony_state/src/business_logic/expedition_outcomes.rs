@@ -0,0 +1,208 @@
+use std::collections::HashMap;
+
+use candid::{CandidType, Deserialize};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use super::{BusinessState, ExpeditionState, Inventory, Resources, BASE_COFFERS_SIZE};
+
+/// What a new colony rewards per second before the expedition's roll changes it
+const CHILD_REWARDS_PER_SECOND: u8 = 100;
+
+/// What happened to an expedition on its way to the new colony. Rolled once, when the expedition
+/// starts, and kept if starting the new colony has to be retried.
+#[derive(CandidType, Deserialize, Debug, Clone, PartialEq)]
+pub struct ExpeditionOutcome {
+    /// The random value the outcome was rolled from. The same seed always gives the same outcome
+    /// for the same expedition.
+    pub seed: u32,
+    /// Percent of every resource in the pool that was lost in transit
+    pub lost_percent: u8,
+    /// Resources found on the way. They end up in the new colony's coffers.
+    pub found: Vec<(Resources, u64)>,
+    /// How good the land of the new colony is
+    pub rewards_per_second: HashMap<Resources, u8>,
+}
+
+/// Chance in percent that part of the pool is lost in transit. Bigger groups travel safer.
+fn loss_chance(members: usize) -> u32 {
+    40u32.saturating_sub(5 * members as u32).max(5)
+}
+
+/// Chance in percent to find Gold on the way. Bigger expeditions explore more.
+fn discovery_chance(pool_total: u64) -> u32 {
+    (10 + pool_total / 1_000).min(50) as u32
+}
+
+impl ExpeditionOutcome {
+    fn roll(expedition: &ExpeditionState, seed: u32) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed as u64);
+        let pool_total: u64 = expedition.resources_pool.contents.values().sum();
+
+        let lost_percent = if rng.gen_range(0, 100) < loss_chance(expedition.members.len()) {
+            rng.gen_range(10, 31)
+        } else {
+            0
+        };
+
+        let found = if rng.gen_range(0, 100) < discovery_chance(pool_total) {
+            vec![(Resources::Gold, pool_total * rng.gen_range(5, 16) / 100)]
+        } else {
+            vec![]
+        };
+
+        // Every resource rewards between 80% and 120% of the usual amount
+        let rewards_per_second = [
+            Resources::Wood,
+            Resources::Stone,
+            Resources::Gold,
+            Resources::Food,
+            Resources::Water,
+        ]
+        .iter()
+        .map(|res| {
+            let percent: u16 = rng.gen_range(80, 121);
+            (
+                *res,
+                (CHILD_REWARDS_PER_SECOND as u16 * percent / 100) as u8,
+            )
+        })
+        .collect();
+
+        Self {
+            seed,
+            lost_percent,
+            found,
+            rewards_per_second,
+        }
+    }
+
+    /// The coffers the new colony starts with: the expedition's pool after the losses, plus
+    /// everything that was found. They can hold as much as the coffers of any new colony, or
+    /// more if that's what arrives.
+    pub fn arrival_coffers(&self, resources_pool: &Inventory) -> Inventory {
+        let mut coffers = resources_pool.clone();
+
+        for val in coffers.contents.values_mut() {
+            *val -= *val * self.lost_percent as u64 / 100;
+        }
+        coffers.add_resources(&self.found.iter().cloned().collect());
+
+        let largest = coffers.contents.values().copied().max().unwrap_or(0);
+        coffers.size = BASE_COFFERS_SIZE.max(largest.min(u32::MAX as u64) as u32);
+
+        coffers
+    }
+}
+
+impl BusinessState {
+    /// Rolls what happens to an expedition on its way, unless that was rolled already.
+    pub fn roll_expedition_outcome(
+        &mut self,
+        expedition_id: u64,
+        seed: u32,
+    ) -> Result<ExpeditionOutcome, String> {
+        let expedition = self
+            .expeditions
+            .get_mut(&expedition_id)
+            .ok_or_else(|| "Can't find expedition".to_string())?;
+
+        let outcome = match &expedition.outcome {
+            Some(outcome) => outcome.clone(),
+            None => ExpeditionOutcome::roll(expedition, seed),
+        };
+        expedition.outcome = Some(outcome.clone());
+
+        Ok(outcome)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use candid::Principal;
+
+    fn expedition(members: u8, pool: u64) -> ExpeditionState {
+        ExpeditionState {
+            members: (0..members).map(|i| Principal::from_slice(&[i])).collect(),
+            resources_pool: Inventory {
+                contents: HashMap::from([(Resources::Wood, pool), (Resources::Stone, pool)]),
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_odds() {
+        assert_eq!(loss_chance(1), 35);
+        assert_eq!(loss_chance(4), 20);
+        assert_eq!(loss_chance(100), 5);
+
+        assert_eq!(discovery_chance(0), 10);
+        assert_eq!(discovery_chance(8_000), 18);
+        assert_eq!(discovery_chance(1_000_000), 50);
+    }
+
+    #[test]
+    fn test_outcomes_are_reproducible() {
+        let expedition = expedition(2, 1_000);
+
+        for seed in 0..100 {
+            let outcome = ExpeditionOutcome::roll(&expedition, seed);
+            assert_eq!(outcome, ExpeditionOutcome::roll(&expedition, seed));
+
+            assert!(outcome.lost_percent == 0 || (10..=30).contains(&outcome.lost_percent));
+            assert!(outcome
+                .rewards_per_second
+                .values()
+                .all(|rate| (80..=120).contains(rate)));
+            for (res, val) in outcome.found.iter() {
+                assert_eq!(*res, Resources::Gold);
+                assert!((100..=300).contains(val));
+            }
+        }
+
+        // Over enough rolls, every kind of outcome shows up
+        let outcomes: Vec<ExpeditionOutcome> = (0..100)
+            .map(|seed| ExpeditionOutcome::roll(&expedition, seed))
+            .collect();
+        assert!(outcomes.iter().any(|o| o.lost_percent > 0));
+        assert!(outcomes.iter().any(|o| o.lost_percent == 0));
+        assert!(outcomes.iter().any(|o| !o.found.is_empty()));
+    }
+
+    #[test]
+    fn test_arrival_coffers() {
+        let expedition = expedition(1, 1_000);
+        let outcome = ExpeditionOutcome {
+            seed: 0,
+            lost_percent: 20,
+            found: vec![(Resources::Gold, 150)],
+            rewards_per_second: Default::default(),
+        };
+
+        let coffers = outcome.arrival_coffers(&expedition.resources_pool);
+        assert_eq!(coffers.get(Resources::Wood), 800);
+        assert_eq!(coffers.get(Resources::Stone), 800);
+        assert_eq!(coffers.get(Resources::Gold), 150);
+        assert_eq!(coffers.size, BASE_COFFERS_SIZE);
+    }
+
+    #[test]
+    fn test_outcome_is_rolled_once() -> Result<(), String> {
+        let mut business_state = BusinessState::default();
+        business_state.expeditions.insert(0, expedition(1, 1_000));
+
+        let first = business_state.roll_expedition_outcome(0, 1)?;
+        assert_eq!(business_state.roll_expedition_outcome(0, 2)?, first);
+        assert_eq!(business_state.expeditions[&0].outcome, Some(first));
+
+        assert_eq!(
+            business_state.roll_expedition_outcome(1, 1),
+            Err("Can't find expedition".to_string())
+        );
+
+        Ok(())
+    }
+}
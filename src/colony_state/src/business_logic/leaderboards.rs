@@ -0,0 +1,227 @@
+use std::collections::{BTreeSet, HashMap};
+
+use candid::{CandidType, Deserialize, Principal};
+
+use super::migrations::v1;
+use super::BusinessState;
+
+/// The most entries a single leaderboard page can hold
+const MAX_PAGE_SIZE: u64 = 100;
+
+#[derive(CandidType, Deserialize, Debug, Hash, PartialEq, Eq, Copy, Clone)]
+pub enum LeaderboardKind {
+    /// Resources paid out for work, in units of any resource
+    ResourcesEarned,
+    SecondsWorked,
+    /// Expeditions the player proposed or joined
+    ExpeditionsJoined,
+    /// Resources put into expedition pools, in units of any resource
+    ExpeditionContributions,
+    /// Resources taken at the customs office when arriving at this colony
+    TaxesPaid,
+}
+
+#[derive(CandidType, Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct PlayerStats {
+    resources_earned: u64,
+    seconds_worked: u64,
+    expeditions_joined: u64,
+    expedition_contributions: u64,
+    taxes_paid: u64,
+}
+
+impl PlayerStats {
+    fn score_mut(&mut self, kind: LeaderboardKind) -> &mut u64 {
+        match kind {
+            LeaderboardKind::ResourcesEarned => &mut self.resources_earned,
+            LeaderboardKind::SecondsWorked => &mut self.seconds_worked,
+            LeaderboardKind::ExpeditionsJoined => &mut self.expeditions_joined,
+            LeaderboardKind::ExpeditionContributions => &mut self.expedition_contributions,
+            LeaderboardKind::TaxesPaid => &mut self.taxes_paid,
+        }
+    }
+}
+
+/// Running totals per player, and for each leaderboard the players sorted by their score. Both
+/// are updated as the stats change, so reading a page of a leaderboard never has to go through
+/// all the players.
+#[derive(CandidType, Deserialize, Debug, Default)]
+pub struct LeaderboardState {
+    stats: HashMap<Principal, PlayerStats>,
+    /// (score, principal) in ascending order; leaderboards read them back to front
+    rankings: HashMap<LeaderboardKind, BTreeSet<(u64, Principal)>>,
+}
+
+impl LeaderboardState {
+    fn add(&mut self, principal: Principal, kind: LeaderboardKind, amount: u64) {
+        if amount == 0 {
+            return;
+        }
+
+        let score = self.stats.entry(principal).or_default().score_mut(kind);
+        let previous = *score;
+        *score += amount;
+
+        let ranking = self.rankings.entry(kind).or_default();
+        ranking.remove(&(previous, principal));
+        ranking.insert((*score, principal));
+    }
+}
+
+#[derive(CandidType, Deserialize, Debug, Clone, PartialEq)]
+pub struct LeaderboardEntry {
+    /// Starts at 1. Players with the same score are ordered by principal.
+    rank: u64,
+    principal: Principal,
+    score: u64,
+}
+
+#[derive(CandidType, Deserialize, Debug, Clone, PartialEq)]
+pub struct LeaderboardPage {
+    kind: LeaderboardKind,
+    /// Number of players on the leaderboard
+    total: u64,
+    entries: Vec<LeaderboardEntry>,
+}
+
+impl BusinessState {
+    pub(super) fn record_stat(&mut self, principal: Principal, kind: LeaderboardKind, amount: u64) {
+        self.leaderboards.add(principal, kind, amount);
+    }
+
+    /// A page of up to `limit` entries of a leaderboard, starting after the first `offset` ranks.
+    pub fn leaderboard(&self, kind: LeaderboardKind, offset: u64, limit: u64) -> LeaderboardPage {
+        let ranking = self.leaderboards.rankings.get(&kind);
+
+        let entries = ranking
+            .into_iter()
+            .flat_map(|ranking| ranking.iter().rev())
+            .enumerate()
+            .skip(offset as usize)
+            .take(limit.min(MAX_PAGE_SIZE) as usize)
+            .map(|(index, (score, principal))| LeaderboardEntry {
+                rank: index as u64 + 1,
+                principal: *principal,
+                score: *score,
+            })
+            .collect();
+
+        LeaderboardPage {
+            kind,
+            total: ranking.map_or(0, |ranking| ranking.len() as u64),
+            entries,
+        }
+    }
+
+    pub fn player_stats(&self, principal: &Principal) -> PlayerStats {
+        self.leaderboards
+            .stats
+            .get(principal)
+            .cloned()
+            .unwrap_or_default()
+    }
+}
+
+impl From<v1::LeaderboardState> for LeaderboardState {
+    fn from(leaderboards: v1::LeaderboardState) -> Self {
+        Self {
+            stats: leaderboards
+                .stats
+                .into_iter()
+                .map(|(principal, s)| {
+                    let stats = PlayerStats {
+                        resources_earned: s.resources_earned,
+                        seconds_worked: s.seconds_worked,
+                        expeditions_joined: s.expeditions_joined,
+                        expedition_contributions: s.expedition_contributions,
+                        taxes_paid: s.taxes_paid,
+                    };
+                    (principal, stats)
+                })
+                .collect(),
+            rankings: leaderboards.rankings,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::business_logic::{Inventory, PlayerState, Resources};
+    use crate::env::MILLIS_TO_SECONDS;
+
+    fn ranking(page: &LeaderboardPage) -> Vec<(u64, Principal, u64)> {
+        page.entries
+            .iter()
+            .map(|e| (e.rank, e.principal, e.score))
+            .collect()
+    }
+
+    #[test]
+    fn test_leaderboard_pages() {
+        let mut business_state = BusinessState::default();
+
+        let users: Vec<Principal> = (1..=5).map(|i| Principal::from_slice(&[i])).collect();
+        for (i, user) in users.iter().enumerate() {
+            business_state.record_stat(*user, LeaderboardKind::TaxesPaid, 10 * (i as u64 + 1));
+        }
+
+        // Scores are updated in place, user 1 moves from last to first
+        business_state.record_stat(users[0], LeaderboardKind::TaxesPaid, 100);
+
+        let page = business_state.leaderboard(LeaderboardKind::TaxesPaid, 0, 2);
+        assert_eq!(page.total, 5);
+        assert_eq!(ranking(&page), vec![(1, users[0], 110), (2, users[4], 50)]);
+
+        let page = business_state.leaderboard(LeaderboardKind::TaxesPaid, 2, 10);
+        assert_eq!(
+            ranking(&page),
+            vec![(3, users[3], 40), (4, users[2], 30), (5, users[1], 20)]
+        );
+
+        let page = business_state.leaderboard(LeaderboardKind::SecondsWorked, 0, 10);
+        assert_eq!(page.total, 0);
+        assert!(page.entries.is_empty());
+    }
+
+    #[test]
+    fn test_stats_follow_game_actions() -> Result<(), String> {
+        let mut business_state = BusinessState::default();
+        business_state.colony.upkeep_per_second.clear();
+
+        let user1: Principal = Principal::from_slice(&[1]);
+        let user2: Principal = Principal::from_slice(&[2]);
+
+        business_state.player.insert(user1, PlayerState::default());
+
+        business_state.work_set(user1, None, 0)?;
+        business_state.work_claim(user1, MILLIS_TO_SECONDS * 10)?;
+
+        business_state.propose_expedition(user1, 0)?;
+
+        business_state.add_traveler(
+            user2,
+            PlayerState {
+                inventory: Inventory {
+                    contents: HashMap::from([(Resources::Wood, 500), (Resources::Stone, 500)]),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+        )?;
+
+        assert_eq!(
+            business_state.player_stats(&user1),
+            PlayerStats {
+                resources_earned: 400,
+                seconds_worked: 10,
+                expeditions_joined: 1,
+                expedition_contributions: 240,
+                taxes_paid: 0,
+            }
+        );
+        assert_eq!(business_state.player_stats(&user2).taxes_paid, 100);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,554 @@
+use std::{cmp::Reverse, collections::HashMap};
+
+use candid::{CandidType, Deserialize, Principal};
+
+use super::migrations::v1;
+use super::{not_registered, BusinessState, Resources};
+use crate::env::TimestampMillis;
+
+/// How many trades we keep around for the `getRecentTrades` query. Older trades are dropped.
+const MAX_RECENT_TRADES: usize = 100;
+
+#[derive(CandidType, Deserialize, Debug, Default)]
+pub struct MarketState {
+    /// Open orders, keyed by order id. Filled and cancelled orders are removed.
+    orders: HashMap<u64, Order>,
+    orders_count: u64,
+    /// The most recent trades, oldest first.
+    trades: Vec<Trade>,
+    trades_count: u64,
+}
+
+#[derive(CandidType, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum OrderSide {
+    /// Buy `base`, paying with `quote`
+    Bid,
+    /// Sell `base`, receiving `quote`
+    Ask,
+}
+
+/// The arguments a player sends when placing an order
+#[derive(CandidType, Deserialize, Debug, Clone)]
+pub struct NewOrder {
+    pub side: OrderSide,
+    pub base: Resources,
+    pub quote: Resources,
+    /// Units of `quote` per unit of `base`
+    pub price: u64,
+    /// Units of `base` to buy or sell
+    pub amount: u64,
+}
+
+#[derive(CandidType, Deserialize, Debug, Clone)]
+pub struct Order {
+    id: u64,
+    owner: Principal,
+    side: OrderSide,
+    base: Resources,
+    quote: Resources,
+    price: u64,
+    amount: u64,
+    /// Units of `base` that haven't been filled yet
+    remaining: u64,
+    placed_at: TimestampMillis,
+}
+
+impl Order {
+    /// The resource and amount that stay in escrow while this order is open
+    fn escrowed(&self) -> (Resources, u64) {
+        match self.side {
+            OrderSide::Bid => (self.quote, self.remaining * self.price),
+            OrderSide::Ask => (self.base, self.remaining),
+        }
+    }
+}
+
+#[derive(CandidType, Deserialize, Debug, Clone)]
+pub struct Trade {
+    id: u64,
+    base: Resources,
+    quote: Resources,
+    /// Trades always execute at the price of the resting (maker) order
+    price: u64,
+    amount: u64,
+    buyer: Principal,
+    seller: Principal,
+    maker_order_id: u64,
+    taker_order_id: u64,
+    executed_at: TimestampMillis,
+}
+
+/// Open volume aggregated per price level. Bids are sorted from the highest price, asks from the
+/// lowest, so the first entry on each side is the best price.
+#[derive(CandidType, Deserialize, Debug, Default, PartialEq)]
+pub struct OrderBookDepth {
+    bids: Vec<(u64, u64)>,
+    asks: Vec<(u64, u64)>,
+}
+
+impl BusinessState {
+    /// Places a limit order and matches it against the book. Whatever can't be filled right away
+    /// rests in the book until it is filled or cancelled. Returns the id of the new order.
+    pub fn place_order(
+        &mut self,
+        principal: Principal,
+        new_order: NewOrder,
+        now: TimestampMillis,
+    ) -> Result<u64, String> {
+        if new_order.base == new_order.quote {
+            return Err("An order needs two different resources".to_string());
+        }
+
+        if new_order.price == 0 || new_order.amount == 0 {
+            return Err("Price and amount must be greater than zero".to_string());
+        }
+
+        if new_order.amount.checked_mul(new_order.price).is_none() {
+            return Err("The order is too large".to_string());
+        }
+
+        let mut order = Order {
+            id: self.market.orders_count,
+            owner: principal,
+            side: new_order.side,
+            base: new_order.base,
+            quote: new_order.quote,
+            price: new_order.price,
+            amount: new_order.amount,
+            remaining: new_order.amount,
+            placed_at: now,
+        };
+
+        let (res, amount) = order.escrowed();
+
+        self.player
+            .get_mut(&principal)
+            .ok_or_else(not_registered)?
+            .inventory
+            .lock_resources(res, amount)?;
+
+        self.market.orders_count += 1;
+
+        self.match_order(&mut order, now);
+
+        let id = order.id;
+        if order.remaining > 0 {
+            self.market.orders.insert(id, order);
+        }
+
+        Ok(id)
+    }
+
+    /// Removes an open order from the book and returns its escrow to the owner.
+    pub fn cancel_order(&mut self, principal: Principal, order_id: u64) -> Result<(), String> {
+        match self.market.orders.get(&order_id) {
+            Some(order) if order.owner == principal => {}
+            Some(_) => return Err("Only the owner can cancel an order".to_string()),
+            None => return Err("Can't find order".to_string()),
+        }
+
+        let order = self.market.orders.remove(&order_id).unwrap();
+        let (res, amount) = order.escrowed();
+
+        if let Some(p) = self.player.get_mut(&principal) {
+            p.inventory.unlock_resources(res, amount);
+        }
+
+        Ok(())
+    }
+
+    /// Matches `taker` against the opposite side of its book in price-time priority: best price
+    /// first, and the oldest order (lowest id) first among equal prices.
+    fn match_order(&mut self, taker: &mut Order, now: TimestampMillis) {
+        let mut makers: Vec<(u64, u64)> = self
+            .market
+            .orders
+            .values()
+            .filter(|o| o.base == taker.base && o.quote == taker.quote && o.side != taker.side)
+            .filter(|o| match taker.side {
+                OrderSide::Bid => o.price <= taker.price,
+                OrderSide::Ask => o.price >= taker.price,
+            })
+            .map(|o| (o.price, o.id))
+            .collect();
+
+        match taker.side {
+            OrderSide::Bid => makers.sort(),
+            OrderSide::Ask => makers.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1))),
+        }
+
+        for (price, maker_id) in makers {
+            if taker.remaining == 0 {
+                break;
+            }
+
+            let maker = match self.market.orders.get_mut(&maker_id) {
+                Some(maker) => maker,
+                None => continue,
+            };
+
+            let amount = taker.remaining.min(maker.remaining);
+            maker.remaining -= amount;
+            taker.remaining -= amount;
+
+            let maker_owner = maker.owner;
+            if maker.remaining == 0 {
+                self.market.orders.remove(&maker_id);
+            }
+
+            let (buyer, seller, bid_price) = match taker.side {
+                OrderSide::Bid => (taker.owner, maker_owner, taker.price),
+                OrderSide::Ask => (maker_owner, taker.owner, price),
+            };
+
+            self.settle_trade(
+                buyer,
+                seller,
+                taker.base,
+                taker.quote,
+                amount,
+                price,
+                bid_price,
+            );
+
+            self.record_trade(Trade {
+                id: self.market.trades_count,
+                base: taker.base,
+                quote: taker.quote,
+                price,
+                amount,
+                buyer,
+                seller,
+                maker_order_id: maker_id,
+                taker_order_id: taker.id,
+                executed_at: now,
+            });
+        }
+    }
+
+    /// Moves escrowed resources between the two sides of a trade. The buyer locked `bid_price`
+    /// per unit, so anything above the execution `price` is refunded.
+    #[allow(clippy::too_many_arguments)]
+    fn settle_trade(
+        &mut self,
+        buyer: Principal,
+        seller: Principal,
+        base: Resources,
+        quote: Resources,
+        amount: u64,
+        price: u64,
+        bid_price: u64,
+    ) {
+        if let Some(p) = self.player.get_mut(&seller) {
+            p.inventory.release_escrow(base, amount);
+            p.inventory
+                .add_resources(&HashMap::from([(quote, amount * price)]));
+        }
+
+        if let Some(p) = self.player.get_mut(&buyer) {
+            p.inventory.release_escrow(quote, amount * bid_price);
+            p.inventory.add_resources(&HashMap::from([(base, amount)]));
+
+            if bid_price > price {
+                p.inventory
+                    .add_resources(&HashMap::from([(quote, amount * (bid_price - price))]));
+            }
+        }
+    }
+
+    fn record_trade(&mut self, trade: Trade) {
+        self.market.trades_count += 1;
+        self.market.trades.push(trade);
+
+        if self.market.trades.len() > MAX_RECENT_TRADES {
+            let overflow = self.market.trades.len() - MAX_RECENT_TRADES;
+            self.market.trades.drain(..overflow);
+        }
+    }
+
+    pub fn order_book_depth(&self, base: Resources, quote: Resources) -> OrderBookDepth {
+        let mut bids: HashMap<u64, u64> = HashMap::new();
+        let mut asks: HashMap<u64, u64> = HashMap::new();
+
+        for order in self
+            .market
+            .orders
+            .values()
+            .filter(|o| o.base == base && o.quote == quote)
+        {
+            let side = match order.side {
+                OrderSide::Bid => &mut bids,
+                OrderSide::Ask => &mut asks,
+            };
+            *side.entry(order.price).or_insert(0) += order.remaining;
+        }
+
+        let mut bids: Vec<(u64, u64)> = bids.into_iter().collect();
+        let mut asks: Vec<(u64, u64)> = asks.into_iter().collect();
+        bids.sort_by_key(|(price, _)| Reverse(*price));
+        asks.sort_by_key(|(price, _)| *price);
+
+        OrderBookDepth { bids, asks }
+    }
+
+    /// Most recent trades for a pair, newest first.
+    pub fn recent_trades(&self, base: Resources, quote: Resources) -> Vec<Trade> {
+        self.market
+            .trades
+            .iter()
+            .rev()
+            .filter(|t| t.base == base && t.quote == quote)
+            .cloned()
+            .collect()
+    }
+
+    /// Open orders placed by a player, oldest first.
+    pub fn player_orders(&self, principal: Principal) -> Vec<Order> {
+        let mut orders: Vec<Order> = self
+            .market
+            .orders
+            .values()
+            .filter(|o| o.owner == principal)
+            .cloned()
+            .collect();
+        orders.sort_by_key(|o| o.id);
+
+        orders
+    }
+}
+
+impl From<v1::MarketState> for MarketState {
+    fn from(market: v1::MarketState) -> Self {
+        Self {
+            orders: market
+                .orders
+                .into_iter()
+                .map(|(id, o)| {
+                    let order = Order {
+                        id: o.id,
+                        owner: o.owner,
+                        side: o.side,
+                        base: o.base,
+                        quote: o.quote,
+                        price: o.price,
+                        amount: o.amount,
+                        remaining: o.remaining,
+                        placed_at: o.placed_at,
+                    };
+                    (id, order)
+                })
+                .collect(),
+            orders_count: market.orders_count,
+            trades: market
+                .trades
+                .into_iter()
+                .map(|t| Trade {
+                    id: t.id,
+                    base: t.base,
+                    quote: t.quote,
+                    price: t.price,
+                    amount: t.amount,
+                    buyer: t.buyer,
+                    seller: t.seller,
+                    maker_order_id: t.maker_order_id,
+                    taker_order_id: t.taker_order_id,
+                    executed_at: t.executed_at,
+                })
+                .collect(),
+            trades_count: market.trades_count,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::business_logic::{Inventory, PlayerState};
+
+    fn player_with(contents: &[(Resources, u64)]) -> PlayerState {
+        PlayerState {
+            inventory: Inventory {
+                contents: contents.iter().cloned().collect(),
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    fn setup() -> (BusinessState, Principal, Principal) {
+        let mut business_state = BusinessState::default();
+
+        let user1: Principal = Principal::from_slice(&[1]);
+        let user2: Principal = Principal::from_slice(&[2]);
+
+        business_state.player.insert(
+            user1,
+            player_with(&[(Resources::Wood, 1000), (Resources::Stone, 1000)]),
+        );
+        business_state.player.insert(
+            user2,
+            player_with(&[(Resources::Wood, 1000), (Resources::Stone, 1000)]),
+        );
+
+        (business_state, user1, user2)
+    }
+
+    fn order(side: OrderSide, price: u64, amount: u64) -> NewOrder {
+        NewOrder {
+            side,
+            base: Resources::Wood,
+            quote: Resources::Stone,
+            price,
+            amount,
+        }
+    }
+
+    #[test]
+    fn test_place_order_escrows_resources() -> Result<(), String> {
+        let (mut business_state, user1, _) = setup();
+
+        business_state.place_order(user1, order(OrderSide::Bid, 2, 100), 1)?;
+        business_state.place_order(user1, order(OrderSide::Ask, 5, 50), 1)?;
+
+        let inventory = &business_state.player.get(&user1).unwrap().inventory;
+        assert_eq!(inventory.get(Resources::Stone), 800);
+        assert_eq!(inventory.get_escrow(Resources::Stone), 200);
+        assert_eq!(inventory.get(Resources::Wood), 950);
+        assert_eq!(inventory.get_escrow(Resources::Wood), 50);
+
+        assert_eq!(
+            business_state.place_order(user1, order(OrderSide::Ask, 5, 951), 1),
+            Err("Not enough Wood available".to_string())
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_cancel_order_returns_escrow() -> Result<(), String> {
+        let (mut business_state, user1, user2) = setup();
+
+        let id = business_state.place_order(user1, order(OrderSide::Bid, 2, 100), 1)?;
+
+        assert_eq!(
+            business_state.cancel_order(user2, id),
+            Err("Only the owner can cancel an order".to_string())
+        );
+
+        business_state.cancel_order(user1, id)?;
+
+        let inventory = &business_state.player.get(&user1).unwrap().inventory;
+        assert_eq!(inventory.get(Resources::Stone), 1000);
+        assert_eq!(inventory.get_escrow(Resources::Stone), 0);
+        assert_eq!(
+            business_state.cancel_order(user1, id),
+            Err("Can't find order".to_string())
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_matching_price_time_priority() -> Result<(), String> {
+        let (mut business_state, user1, user2) = setup();
+
+        let expensive = business_state.place_order(user1, order(OrderSide::Ask, 4, 10), 1)?;
+        let first = business_state.place_order(user1, order(OrderSide::Ask, 3, 10), 2)?;
+        let second = business_state.place_order(user1, order(OrderSide::Ask, 3, 10), 3)?;
+
+        // Crosses both orders at 3, oldest first, and leaves the order at 4 untouched
+        business_state.place_order(user2, order(OrderSide::Bid, 3, 15), 4)?;
+
+        let trades = business_state.recent_trades(Resources::Wood, Resources::Stone);
+        assert_eq!(trades.len(), 2);
+        assert_eq!(trades[1].maker_order_id, first);
+        assert_eq!(trades[1].amount, 10);
+        assert_eq!(trades[0].maker_order_id, second);
+        assert_eq!(trades[0].amount, 5);
+
+        let open: Vec<(u64, u64)> = business_state
+            .player_orders(user1)
+            .iter()
+            .map(|o| (o.id, o.remaining))
+            .collect();
+        assert_eq!(open, vec![(expensive, 10), (second, 5)]);
+
+        let buyer = &business_state.player.get(&user2).unwrap().inventory;
+        assert_eq!(buyer.get(Resources::Wood), 1015);
+        assert_eq!(buyer.get(Resources::Stone), 955);
+
+        let seller = &business_state.player.get(&user1).unwrap().inventory;
+        assert_eq!(seller.get(Resources::Stone), 1045);
+        assert_eq!(seller.get_escrow(Resources::Wood), 15);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_taker_gets_maker_price() -> Result<(), String> {
+        let (mut business_state, user1, user2) = setup();
+
+        business_state.place_order(user1, order(OrderSide::Ask, 2, 10), 1)?;
+        business_state.place_order(user2, order(OrderSide::Bid, 5, 10), 2)?;
+
+        // The bid locked 50 stone but only paid 20, the rest is refunded
+        let buyer = &business_state.player.get(&user2).unwrap().inventory;
+        assert_eq!(buyer.get(Resources::Stone), 980);
+        assert_eq!(buyer.get_escrow(Resources::Stone), 0);
+        assert_eq!(buyer.get(Resources::Wood), 1010);
+
+        assert!(business_state.player_orders(user2).is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_order_book_depth() -> Result<(), String> {
+        let (mut business_state, user1, user2) = setup();
+
+        business_state.place_order(user1, order(OrderSide::Bid, 2, 10), 1)?;
+        business_state.place_order(user2, order(OrderSide::Bid, 2, 5), 1)?;
+        business_state.place_order(user2, order(OrderSide::Bid, 3, 1), 1)?;
+        business_state.place_order(user1, order(OrderSide::Ask, 6, 7), 1)?;
+        business_state.place_order(user1, order(OrderSide::Ask, 5, 4), 1)?;
+
+        assert_eq!(
+            business_state.order_book_depth(Resources::Wood, Resources::Stone),
+            OrderBookDepth {
+                bids: vec![(3, 1), (2, 15)],
+                asks: vec![(5, 4), (6, 7)],
+            }
+        );
+        assert_eq!(
+            business_state.order_book_depth(Resources::Stone, Resources::Wood),
+            OrderBookDepth::default()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_orders() {
+        let (mut business_state, user1, _) = setup();
+
+        assert_eq!(
+            business_state.place_order(
+                user1,
+                NewOrder {
+                    quote: Resources::Wood,
+                    ..order(OrderSide::Bid, 1, 1)
+                },
+                1
+            ),
+            Err("An order needs two different resources".to_string())
+        );
+        assert_eq!(
+            business_state.place_order(user1, order(OrderSide::Bid, 0, 1), 1),
+            Err("Price and amount must be greater than zero".to_string())
+        );
+        assert_eq!(
+            business_state.place_order(Principal::anonymous(), order(OrderSide::Bid, 1, 1), 1),
+            Err("Player not found in this world".to_string())
+        );
+    }
+}
@@ -0,0 +1,510 @@
+//! Frozen copies of the business state as it was stored by older versions of the canister, and
+//! how each of them turns into the next version. Once a version has been released its types here
+//! must never change, or the canisters still holding it can't be upgraded anymore. Types whose
+//! fields are private to their module are converted from their frozen copy in that module.
+
+use candid::Principal;
+
+use super::{
+    BusinessState, ColonyState, ExpeditionOutcome, ExpeditionState, Inventory, PlayerState,
+};
+
+/// The layout before the stable state had a version: no market, pools, crafting, buildings,
+/// leaderboards, upkeep, skills or profiles.
+pub mod v0 {
+    use std::collections::HashMap;
+
+    use candid::{CandidType, Deserialize, Principal};
+
+    use crate::business_logic::{ExpeditionStep, PlayerStatus, Resources};
+    use crate::env::TimestampMillis;
+
+    #[derive(CandidType, Deserialize, Debug, Default)]
+    pub struct BusinessState {
+        pub colony: ColonyState,
+        pub player: HashMap<Principal, PlayerState>,
+        pub expeditions: HashMap<u64, ExpeditionState>,
+        pub expeditions_count: u64,
+        pub remote_colonies: Vec<Principal>,
+        pub wasm_store: Vec<u8>,
+    }
+
+    #[derive(CandidType, Deserialize, Debug, Default)]
+    pub struct ColonyState {
+        pub generation: u8,
+        pub taxes_percent: u8,
+        pub global_resources_multiplier: u16,
+        pub rewards_per_second: HashMap<Resources, u8>,
+        pub coffers: Inventory,
+    }
+
+    #[derive(CandidType, Deserialize, Debug, Default)]
+    pub struct Inventory {
+        pub size: u32,
+        pub contents: HashMap<Resources, u64>,
+    }
+
+    #[derive(CandidType, Deserialize, Debug)]
+    pub struct ExpeditionState {
+        pub id: u64,
+        pub step: ExpeditionStep,
+        pub proposed_by: Principal,
+        pub proposed_at: TimestampMillis,
+        pub resources_required: HashMap<Resources, u64>,
+        pub resources_pool: Inventory,
+        pub members: Vec<Principal>,
+    }
+
+    #[derive(CandidType, Deserialize, Debug)]
+    pub struct PlayerState {
+        pub status: PlayerStatus,
+        pub inventory: Inventory,
+    }
+}
+
+/// The layout before the wasm registry, with a single wasm in `wasm_store`, and before
+/// expeditions remembered the wasm and canister of their colony.
+pub mod v1 {
+    use std::collections::{BTreeSet, HashMap};
+
+    use candid::{CandidType, Deserialize, Principal};
+
+    use crate::business_logic::{
+        Building, ExpeditionStep, Item, LeaderboardKind, OrderSide, PlayerStatus, Resources,
+    };
+    use crate::env::TimestampMillis;
+
+    #[derive(CandidType, Deserialize, Debug, Default)]
+    pub struct BusinessState {
+        pub colony: ColonyState,
+        pub player: HashMap<Principal, PlayerState>,
+        pub expeditions: HashMap<u64, ExpeditionState>,
+        pub expeditions_count: u64,
+        pub remote_colonies: Vec<Principal>,
+        pub wasm_store: Vec<u8>,
+        pub market: MarketState,
+        pub amm: AmmState,
+        pub crafting: CraftingState,
+        pub buildings: HashMap<Building, BuildingState>,
+        pub leaderboards: LeaderboardState,
+    }
+
+    #[derive(CandidType, Deserialize, Debug)]
+    pub struct ColonyState {
+        pub generation: u8,
+        pub taxes_percent: u8,
+        pub global_resources_multiplier: u16,
+        pub rewards_per_second: HashMap<Resources, u8>,
+        pub upkeep_per_second: HashMap<Resources, u8>,
+        pub max_work_session_seconds: u64,
+        pub skill_curve: SkillCurve,
+        pub coffers: Inventory,
+    }
+
+    impl Default for ColonyState {
+        fn default() -> Self {
+            Self {
+                generation: 0,
+                taxes_percent: 10,
+                global_resources_multiplier: 1,
+                rewards_per_second: HashMap::from([
+                    (Resources::Wood, 10),
+                    (Resources::Stone, 10),
+                    (Resources::Food, 10),
+                    (Resources::Water, 10),
+                ]),
+                upkeep_per_second: HashMap::from([(Resources::Food, 1), (Resources::Water, 1)]),
+                max_work_session_seconds: 8 * 60 * 60,
+                skill_curve: Default::default(),
+                coffers: Inventory {
+                    size: 100_000,
+                    ..Default::default()
+                },
+            }
+        }
+    }
+
+    #[derive(CandidType, Deserialize, Debug)]
+    pub struct SkillCurve {
+        pub level_thresholds: Vec<u64>,
+        pub bonus_percent_per_level: u64,
+    }
+
+    impl Default for SkillCurve {
+        fn default() -> Self {
+            Self {
+                level_thresholds: (1..=10)
+                    .map(|level| level * (level + 1) / 2 * 60 * 60)
+                    .collect(),
+                bonus_percent_per_level: 5,
+            }
+        }
+    }
+
+    #[derive(CandidType, Deserialize, Debug, Default)]
+    pub struct Inventory {
+        pub size: u32,
+        pub contents: HashMap<Resources, u64>,
+        pub escrow: HashMap<Resources, u64>,
+    }
+
+    #[derive(CandidType, Deserialize, Debug)]
+    pub struct ExpeditionState {
+        pub id: u64,
+        pub step: ExpeditionStep,
+        pub proposed_by: Principal,
+        pub proposed_at: TimestampMillis,
+        pub resources_required: HashMap<Resources, u64>,
+        pub resources_pool: Inventory,
+        pub members: Vec<Principal>,
+        pub outcome: Option<ExpeditionOutcome>,
+    }
+
+    #[derive(CandidType, Deserialize, Debug)]
+    pub struct ExpeditionOutcome {
+        pub seed: u32,
+        pub lost_percent: u8,
+        pub found: Vec<(Resources, u64)>,
+        pub rewards_per_second: HashMap<Resources, u8>,
+    }
+
+    #[derive(CandidType, Deserialize, Debug)]
+    pub struct PlayerState {
+        pub status: PlayerStatus,
+        pub inventory: Inventory,
+        pub lp_shares: HashMap<(Resources, Resources), u64>,
+        pub items: Vec<ItemState>,
+        pub crafting: Vec<CraftingJob>,
+        pub experience: HashMap<Resources, u64>,
+        pub profile: Profile,
+    }
+
+    #[derive(CandidType, Deserialize, Debug, Default)]
+    pub struct Profile {
+        pub display_name: Option<String>,
+        pub bio: String,
+        pub avatar_url: Option<String>,
+        pub joined_at: TimestampMillis,
+    }
+
+    #[derive(CandidType, Deserialize, Debug, Default)]
+    pub struct MarketState {
+        pub orders: HashMap<u64, Order>,
+        pub orders_count: u64,
+        pub trades: Vec<Trade>,
+        pub trades_count: u64,
+    }
+
+    #[derive(CandidType, Deserialize, Debug)]
+    pub struct Order {
+        pub id: u64,
+        pub owner: Principal,
+        pub side: OrderSide,
+        pub base: Resources,
+        pub quote: Resources,
+        pub price: u64,
+        pub amount: u64,
+        pub remaining: u64,
+        pub placed_at: TimestampMillis,
+    }
+
+    #[derive(CandidType, Deserialize, Debug)]
+    pub struct Trade {
+        pub id: u64,
+        pub base: Resources,
+        pub quote: Resources,
+        pub price: u64,
+        pub amount: u64,
+        pub buyer: Principal,
+        pub seller: Principal,
+        pub maker_order_id: u64,
+        pub taker_order_id: u64,
+        pub executed_at: TimestampMillis,
+    }
+
+    #[derive(CandidType, Deserialize, Debug)]
+    pub struct AmmState {
+        pub pools: HashMap<(Resources, Resources), Pool>,
+        pub swap_fee_basis_points: u16,
+    }
+
+    impl Default for AmmState {
+        fn default() -> Self {
+            Self {
+                pools: Default::default(),
+                swap_fee_basis_points: 30,
+            }
+        }
+    }
+
+    #[derive(CandidType, Deserialize, Debug)]
+    pub struct Pool {
+        pub reserve_a: u64,
+        pub reserve_b: u64,
+        pub total_shares: u64,
+        pub colony_shares: u64,
+    }
+
+    #[derive(CandidType, Deserialize, Debug)]
+    pub struct CraftingState {
+        pub recipes: HashMap<Item, Recipe>,
+    }
+
+    impl Default for CraftingState {
+        fn default() -> Self {
+            let recipe = |item, inputs: &[(Resources, u64)], boosts: &[Resources]| Recipe {
+                item,
+                inputs: inputs.iter().cloned().collect(),
+                crafting_seconds: 60,
+                boosts: boosts.to_vec(),
+                bonus_percent: 50,
+                durability: 60 * 60,
+            };
+
+            Self {
+                recipes: HashMap::from([
+                    (
+                        Item::Axe,
+                        recipe(
+                            Item::Axe,
+                            &[(Resources::Wood, 50), (Resources::Stone, 100)],
+                            &[Resources::Wood],
+                        ),
+                    ),
+                    (
+                        Item::Pickaxe,
+                        recipe(
+                            Item::Pickaxe,
+                            &[(Resources::Wood, 100), (Resources::Stone, 50)],
+                            &[Resources::Stone, Resources::Gold],
+                        ),
+                    ),
+                    (
+                        Item::Bucket,
+                        recipe(Item::Bucket, &[(Resources::Wood, 100)], &[Resources::Water]),
+                    ),
+                    (
+                        Item::Sickle,
+                        recipe(
+                            Item::Sickle,
+                            &[(Resources::Wood, 50), (Resources::Stone, 50)],
+                            &[Resources::Food],
+                        ),
+                    ),
+                ]),
+            }
+        }
+    }
+
+    #[derive(CandidType, Deserialize, Debug)]
+    pub struct Recipe {
+        pub item: Item,
+        pub inputs: HashMap<Resources, u64>,
+        pub crafting_seconds: u64,
+        pub boosts: Vec<Resources>,
+        pub bonus_percent: u64,
+        pub durability: u64,
+    }
+
+    #[derive(CandidType, Deserialize, Debug)]
+    pub struct ItemState {
+        pub item: Item,
+        pub durability: u64,
+    }
+
+    #[derive(CandidType, Deserialize, Debug)]
+    pub struct CraftingJob {
+        pub item: Item,
+        pub ready_at: TimestampMillis,
+    }
+
+    #[derive(CandidType, Deserialize, Debug, Default)]
+    pub struct BuildingState {
+        pub level: u8,
+        pub progress: Inventory,
+    }
+
+    #[derive(CandidType, Deserialize, Debug, Default)]
+    pub struct LeaderboardState {
+        pub stats: HashMap<Principal, PlayerStats>,
+        pub rankings: HashMap<LeaderboardKind, BTreeSet<(u64, Principal)>>,
+    }
+
+    #[derive(CandidType, Deserialize, Debug, Default)]
+    pub struct PlayerStats {
+        pub resources_earned: u64,
+        pub seconds_worked: u64,
+        pub expeditions_joined: u64,
+        pub expedition_contributions: u64,
+        pub taxes_paid: u64,
+    }
+}
+
+impl From<v0::Inventory> for v1::Inventory {
+    fn from(inventory: v0::Inventory) -> Self {
+        Self {
+            size: inventory.size,
+            contents: inventory.contents,
+            escrow: Default::default(),
+        }
+    }
+}
+
+impl From<v0::BusinessState> for v1::BusinessState {
+    fn from(state: v0::BusinessState) -> Self {
+        Self {
+            // Coffers of size 0 had no limit before and still don't
+            colony: v1::ColonyState {
+                generation: state.colony.generation,
+                taxes_percent: state.colony.taxes_percent,
+                global_resources_multiplier: state.colony.global_resources_multiplier,
+                rewards_per_second: state.colony.rewards_per_second,
+                coffers: state.colony.coffers.into(),
+                ..Default::default()
+            },
+            // Nobody knows when these players joined, their profiles start at 0
+            player: state
+                .player
+                .into_iter()
+                .map(|(principal, p)| {
+                    let player = v1::PlayerState {
+                        status: p.status,
+                        inventory: p.inventory.into(),
+                        lp_shares: Default::default(),
+                        items: Default::default(),
+                        crafting: Default::default(),
+                        experience: Default::default(),
+                        profile: Default::default(),
+                    };
+                    (principal, player)
+                })
+                .collect(),
+            expeditions: state
+                .expeditions
+                .into_iter()
+                .map(|(id, e)| {
+                    let expedition = v1::ExpeditionState {
+                        id: e.id,
+                        step: e.step,
+                        proposed_by: e.proposed_by,
+                        proposed_at: e.proposed_at,
+                        resources_required: e.resources_required,
+                        resources_pool: e.resources_pool.into(),
+                        members: e.members,
+                        outcome: None,
+                    };
+                    (id, expedition)
+                })
+                .collect(),
+            expeditions_count: state.expeditions_count,
+            remote_colonies: state.remote_colonies,
+            wasm_store: state.wasm_store,
+            ..Default::default()
+        }
+    }
+}
+
+impl From<v1::Inventory> for Inventory {
+    fn from(inventory: v1::Inventory) -> Self {
+        Self {
+            size: inventory.size,
+            contents: inventory.contents,
+            escrow: inventory.escrow,
+        }
+    }
+}
+
+impl From<v1::ColonyState> for ColonyState {
+    fn from(colony: v1::ColonyState) -> Self {
+        Self {
+            generation: colony.generation,
+            taxes_percent: colony.taxes_percent,
+            global_resources_multiplier: colony.global_resources_multiplier,
+            rewards_per_second: colony.rewards_per_second,
+            upkeep_per_second: colony.upkeep_per_second,
+            max_work_session_seconds: colony.max_work_session_seconds,
+            skill_curve: colony.skill_curve.into(),
+            coffers: colony.coffers.into(),
+        }
+    }
+}
+
+impl From<v1::PlayerState> for PlayerState {
+    fn from(p: v1::PlayerState) -> Self {
+        Self {
+            status: p.status,
+            inventory: p.inventory.into(),
+            lp_shares: p.lp_shares,
+            items: p.items.into_iter().map(Into::into).collect(),
+            crafting: p.crafting.into_iter().map(Into::into).collect(),
+            experience: p.experience,
+            profile: p.profile.into(),
+        }
+    }
+}
+
+impl From<v1::ExpeditionState> for ExpeditionState {
+    fn from(e: v1::ExpeditionState) -> Self {
+        Self {
+            id: e.id,
+            step: e.step,
+            proposed_by: e.proposed_by,
+            proposed_at: e.proposed_at,
+            resources_required: e.resources_required,
+            resources_pool: e.resources_pool.into(),
+            members: e.members,
+            outcome: e.outcome.map(|outcome| ExpeditionOutcome {
+                seed: outcome.seed,
+                lost_percent: outcome.lost_percent,
+                found: outcome.found,
+                rewards_per_second: outcome.rewards_per_second,
+            }),
+            wasm_hash: None,
+            colony_canister: None,
+        }
+    }
+}
+
+impl From<v1::BusinessState> for BusinessState {
+    fn from(state: v1::BusinessState) -> Self {
+        let mut business_state = Self {
+            colony: state.colony.into(),
+            player: state
+                .player
+                .into_iter()
+                .map(|(principal, p)| (principal, p.into()))
+                .collect(),
+            expeditions: state
+                .expeditions
+                .into_iter()
+                .map(|(id, e)| (id, e.into()))
+                .collect(),
+            expeditions_count: state.expeditions_count,
+            remote_colonies: state.remote_colonies,
+            market: state.market.into(),
+            amm: state.amm.into(),
+            crafting: state.crafting.into(),
+            buildings: state
+                .buildings
+                .into_iter()
+                .map(|(building, b)| (building, b.into()))
+                .collect(),
+            leaderboards: state.leaderboards.into(),
+            ..Default::default()
+        };
+
+        // Nobody knows who uploaded the stored wasm, or when. It stays the active one.
+        if !state.wasm_store.is_empty() {
+            let sha256 =
+                business_state
+                    .wasm_registry
+                    .add(state.wasm_store, None, Principal::anonymous(), 0);
+            business_state
+                .wasm_registry
+                .activate(&sha256)
+                .expect("The wasm was just added");
+        }
+
+        business_state
+    }
+}
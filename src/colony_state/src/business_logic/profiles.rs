@@ -0,0 +1,305 @@
+use candid::{CandidType, Deserialize, Principal};
+
+use super::migrations::v1;
+use super::{not_registered, BusinessState};
+use crate::env::TimestampMillis;
+
+const DISPLAY_NAME_MIN_LENGTH: usize = 3;
+const DISPLAY_NAME_MAX_LENGTH: usize = 32;
+const BIO_MAX_LENGTH: usize = 500;
+const AVATAR_URL_MAX_LENGTH: usize = 256;
+
+/// What other players can see about a player
+#[derive(CandidType, Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct Profile {
+    /// Unique within a colony, ignoring case
+    display_name: Option<String>,
+    bio: String,
+    avatar_url: Option<String>,
+    /// When the player joined their first colony. Travelling doesn't change it.
+    joined_at: TimestampMillis,
+}
+
+/// What `setProfile` takes. Every field replaces the current one, empty strings clear it.
+#[derive(CandidType, Deserialize, Debug, Default, Clone)]
+pub struct ProfileArgs {
+    pub display_name: String,
+    pub bio: String,
+    pub avatar_url: String,
+}
+
+impl Profile {
+    pub fn new(joined_at: TimestampMillis) -> Self {
+        Self {
+            joined_at,
+            ..Default::default()
+        }
+    }
+
+    pub fn display_name(&self) -> Option<&str> {
+        self.display_name.as_deref()
+    }
+
+    fn has_display_name(&self, name: &str) -> bool {
+        self.display_name.as_deref().map(str::to_lowercase) == Some(name.to_lowercase())
+    }
+}
+
+fn non_empty(value: &str) -> Option<String> {
+    let value = value.trim();
+    if value.is_empty() {
+        None
+    } else {
+        Some(value.to_string())
+    }
+}
+
+fn validate_display_name(name: &str) -> Result<(), String> {
+    let length = name.chars().count();
+    if !(DISPLAY_NAME_MIN_LENGTH..=DISPLAY_NAME_MAX_LENGTH).contains(&length) {
+        return Err(format!(
+            "The display name must be between {} and {} characters long",
+            DISPLAY_NAME_MIN_LENGTH, DISPLAY_NAME_MAX_LENGTH
+        ));
+    }
+
+    if !name
+        .chars()
+        .all(|c| c.is_alphanumeric() || c == ' ' || c == '_' || c == '-')
+    {
+        return Err(
+            "The display name can only contain letters, digits, spaces, '_' and '-'".to_string(),
+        );
+    }
+
+    Ok(())
+}
+
+fn validate_avatar_url(url: &str) -> Result<(), String> {
+    if url.chars().count() > AVATAR_URL_MAX_LENGTH {
+        return Err(format!(
+            "The avatar URL can't be longer than {} characters",
+            AVATAR_URL_MAX_LENGTH
+        ));
+    }
+
+    if !url.starts_with("https://") {
+        return Err("The avatar URL must start with https://".to_string());
+    }
+
+    Ok(())
+}
+
+impl BusinessState {
+    pub fn set_profile(&mut self, principal: Principal, args: ProfileArgs) -> Result<(), String> {
+        let display_name = non_empty(&args.display_name);
+        let bio = args.bio.trim().to_string();
+        let avatar_url = non_empty(&args.avatar_url);
+
+        if let Some(name) = &display_name {
+            validate_display_name(name)?;
+
+            if self.is_display_name_taken(name, &principal) {
+                return Err(format!("The display name {} is already taken", name));
+            }
+        }
+
+        if bio.chars().count() > BIO_MAX_LENGTH {
+            return Err(format!(
+                "The bio can't be longer than {} characters",
+                BIO_MAX_LENGTH
+            ));
+        }
+
+        if let Some(url) = &avatar_url {
+            validate_avatar_url(url)?;
+        }
+
+        let p = self.player.get_mut(&principal).ok_or_else(not_registered)?;
+
+        p.profile = Profile {
+            display_name,
+            bio,
+            avatar_url,
+            ..p.profile.clone()
+        };
+
+        Ok(())
+    }
+
+    pub fn profile(&self, principal: &Principal) -> Result<Profile, String> {
+        self.player
+            .get(principal)
+            .map(|p| p.profile.clone())
+            .ok_or_else(not_registered)
+    }
+
+    /// Whether a player other than `principal` already uses this display name in the colony
+    pub(super) fn is_display_name_taken(&self, name: &str, principal: &Principal) -> bool {
+        self.player
+            .iter()
+            .any(|(other, p)| other != principal && p.profile.has_display_name(name))
+    }
+
+    /// Keeps a travelling player's profile, except for a display name that is already taken in
+    /// this colony. The player has to pick a new one in that case.
+    pub(super) fn arriving_profile(&self, principal: &Principal, profile: Profile) -> Profile {
+        match &profile.display_name {
+            Some(name) if self.is_display_name_taken(name, principal) => Profile {
+                display_name: None,
+                ..profile
+            },
+            _ => profile,
+        }
+    }
+}
+
+impl From<v1::Profile> for Profile {
+    fn from(profile: v1::Profile) -> Self {
+        Self {
+            display_name: profile.display_name,
+            bio: profile.bio,
+            avatar_url: profile.avatar_url,
+            joined_at: profile.joined_at,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::business_logic::PlayerState;
+
+    fn args(display_name: &str) -> ProfileArgs {
+        ProfileArgs {
+            display_name: display_name.to_string(),
+            bio: "Chopping wood since generation 0".to_string(),
+            avatar_url: "https://example.com/avatar.png".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_set_and_get_profile() -> Result<(), String> {
+        let mut business_state = BusinessState::default();
+
+        let user1: Principal = Principal::from_slice(&[1]);
+        business_state.add_player(user1, 42)?;
+
+        business_state.set_profile(user1, args("  Lumberjack "))?;
+
+        assert_eq!(
+            business_state.profile(&user1)?,
+            Profile {
+                display_name: Some("Lumberjack".to_string()),
+                bio: "Chopping wood since generation 0".to_string(),
+                avatar_url: Some("https://example.com/avatar.png".to_string()),
+                joined_at: 42,
+            }
+        );
+
+        business_state.set_profile(user1, ProfileArgs::default())?;
+        assert_eq!(business_state.profile(&user1)?, Profile::new(42));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_display_names_are_unique() -> Result<(), String> {
+        let mut business_state = BusinessState::default();
+
+        let user1: Principal = Principal::from_slice(&[1]);
+        let user2: Principal = Principal::from_slice(&[2]);
+        business_state.add_player(user1, 0)?;
+        business_state.add_player(user2, 0)?;
+
+        business_state.set_profile(user1, args("Lumberjack"))?;
+        // Setting the same name again is fine
+        business_state.set_profile(user1, args("Lumberjack"))?;
+
+        assert_eq!(
+            business_state.set_profile(user2, args("lumberjack")),
+            Err("The display name lumberjack is already taken".to_string())
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_profile_validation() -> Result<(), String> {
+        let mut business_state = BusinessState::default();
+
+        let user1: Principal = Principal::from_slice(&[1]);
+        business_state.add_player(user1, 0)?;
+
+        assert_eq!(
+            business_state.set_profile(user1, args("Jo")),
+            Err("The display name must be between 3 and 32 characters long".to_string())
+        );
+        assert_eq!(
+            business_state.set_profile(user1, args("<script>")),
+            Err(
+                "The display name can only contain letters, digits, spaces, '_' and '-'"
+                    .to_string()
+            )
+        );
+        assert_eq!(
+            business_state.set_profile(
+                user1,
+                ProfileArgs {
+                    avatar_url: "http://example.com/avatar.png".to_string(),
+                    ..args("Lumberjack")
+                }
+            ),
+            Err("The avatar URL must start with https://".to_string())
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_profile_travels_with_the_player() -> Result<(), String> {
+        let mut origin = BusinessState::default();
+        let mut destination = BusinessState::default();
+
+        let user1: Principal = Principal::from_slice(&[1]);
+        let user2: Principal = Principal::from_slice(&[2]);
+        let user3: Principal = Principal::from_slice(&[3]);
+
+        origin.add_player(user1, 10)?;
+        origin.add_player(user2, 20)?;
+        origin.set_profile(user1, args("Lumberjack"))?;
+        origin.set_profile(user2, args("Miner"))?;
+
+        destination.add_player(user3, 30)?;
+        destination.set_profile(user3, args("Miner"))?;
+
+        let traveler: PlayerState = origin.player.remove(&user1).unwrap();
+        destination.add_traveler(user1, traveler)?;
+        assert_eq!(
+            destination.profile(&user1)?,
+            origin_profile(10, "Lumberjack")
+        );
+
+        // The name is already used at the destination, everything else is kept
+        let traveler: PlayerState = origin.player.remove(&user2).unwrap();
+        destination.add_traveler(user2, traveler)?;
+        assert_eq!(
+            destination.profile(&user2)?,
+            Profile {
+                display_name: None,
+                ..origin_profile(20, "Miner")
+            }
+        );
+
+        Ok(())
+    }
+
+    fn origin_profile(joined_at: TimestampMillis, display_name: &str) -> Profile {
+        Profile {
+            display_name: Some(display_name.to_string()),
+            bio: "Chopping wood since generation 0".to_string(),
+            avatar_url: Some("https://example.com/avatar.png".to_string()),
+            joined_at,
+        }
+    }
+}
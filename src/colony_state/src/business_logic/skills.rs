@@ -0,0 +1,237 @@
+use std::collections::HashMap;
+
+use candid::{CandidType, Deserialize, Principal};
+
+use super::migrations::v1;
+use super::{not_registered, BusinessState, PlayerState, Resources};
+
+/// A level can add at most the whole rewards_per_second of a resource to the yield
+const MAX_BONUS_PERCENT_PER_LEVEL: u64 = 100;
+
+/// How much experience it takes to reach each skill level, and what a level is worth.
+/// Experience is counted in seconds worked for a resource.
+#[derive(CandidType, Deserialize, Debug, Clone, PartialEq)]
+pub struct SkillCurve {
+    /// Total experience needed for each level, starting with level 1. Must be increasing.
+    level_thresholds: Vec<u64>,
+    /// Extra yield per level, as a percent of the colony's rewards_per_second
+    bonus_percent_per_level: u64,
+}
+
+impl Default for SkillCurve {
+    fn default() -> Self {
+        const HOUR: u64 = 60 * 60;
+
+        Self {
+            // 1h, 3h, 6h, 10h, ... each level takes one hour longer than the previous one
+            level_thresholds: (1..=10)
+                .map(|level| level * (level + 1) / 2 * HOUR)
+                .collect(),
+            bonus_percent_per_level: 5,
+        }
+    }
+}
+
+impl SkillCurve {
+    fn level(&self, experience: u64) -> u8 {
+        self.level_thresholds
+            .iter()
+            .take_while(|threshold| experience >= **threshold)
+            .count() as u8
+    }
+}
+
+#[derive(CandidType, Deserialize, Debug, Clone, PartialEq)]
+pub struct SkillLevel {
+    resource: Resources,
+    level: u8,
+    experience: u64,
+    /// Experience needed for the next level. Empty at the maximum level.
+    next_level_at: Option<u64>,
+    yield_bonus_percent: u64,
+}
+
+#[derive(CandidType, Deserialize, Debug, Clone, PartialEq)]
+pub struct PlayerProfile {
+    skills: Vec<SkillLevel>,
+}
+
+impl BusinessState {
+    pub fn player_profile(&self, principal: &Principal) -> Result<PlayerProfile, String> {
+        let p = self.player.get(principal).ok_or_else(not_registered)?;
+
+        let curve = &self.colony.skill_curve;
+
+        let mut skills: Vec<SkillLevel> = p
+            .experience
+            .iter()
+            .map(|(res, experience)| {
+                let level = curve.level(*experience);
+
+                SkillLevel {
+                    resource: *res,
+                    level,
+                    experience: *experience,
+                    next_level_at: curve.level_thresholds.get(level as usize).cloned(),
+                    yield_bonus_percent: level as u64 * curve.bonus_percent_per_level,
+                }
+            })
+            .collect();
+        skills.sort_by_key(|s| s.resource);
+
+        Ok(PlayerProfile { skills })
+    }
+
+    pub fn set_skill_curve(&mut self, curve: SkillCurve) -> Result<(), String> {
+        if curve.level_thresholds.windows(2).any(|w| w[0] >= w[1]) {
+            return Err("Level thresholds must be increasing".to_string());
+        }
+
+        if curve.level_thresholds.len() > u8::MAX as usize {
+            return Err(format!("There can't be more than {} levels", u8::MAX));
+        }
+
+        if curve.bonus_percent_per_level > MAX_BONUS_PERCENT_PER_LEVEL {
+            return Err(format!(
+                "The bonus per level can't be more than {}%",
+                MAX_BONUS_PERCENT_PER_LEVEL
+            ));
+        }
+
+        self.colony.skill_curve = curve;
+
+        Ok(())
+    }
+
+    /// Extra resources the player's skill levels add to the colony's rewards for the given time
+    /// worked.
+    pub(super) fn skill_bonus(
+        &self,
+        player: &PlayerState,
+        seconds_elapsed: u64,
+    ) -> HashMap<Resources, u64> {
+        let curve = &self.colony.skill_curve;
+
+        self.available_unclaimed(seconds_elapsed)
+            .into_iter()
+            .map(|(res, val)| {
+                let level = curve.level(*player.experience.get(&res).unwrap_or(&0));
+                (
+                    res,
+                    val.saturating_mul(level as u64)
+                        .saturating_mul(curve.bonus_percent_per_level)
+                        / 100,
+                )
+            })
+            .filter(|(_, val)| *val > 0)
+            .collect()
+    }
+
+    /// Credits experience for every resource the player was paid for.
+    pub(super) fn add_experience(
+        player: &mut PlayerState,
+        paid: &[(Resources, u64)],
+        seconds: u64,
+    ) {
+        for (res, val) in paid.iter() {
+            if *val > 0 {
+                *player.experience.entry(*res).or_insert(0) += seconds;
+            }
+        }
+    }
+}
+
+impl From<v1::SkillCurve> for SkillCurve {
+    fn from(curve: v1::SkillCurve) -> Self {
+        Self {
+            level_thresholds: curve.level_thresholds,
+            bonus_percent_per_level: curve.bonus_percent_per_level,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::env::MILLIS_TO_SECONDS;
+
+    #[test]
+    fn test_default_curve() {
+        let curve = SkillCurve::default();
+
+        assert_eq!(curve.level(0), 0);
+        assert_eq!(curve.level(3599), 0);
+        assert_eq!(curve.level(3600), 1);
+        assert_eq!(curve.level(3 * 3600), 2);
+        assert_eq!(curve.level(u64::MAX), 10);
+    }
+
+    #[test]
+    fn test_work_earns_experience_and_levels_raise_yield() -> Result<(), String> {
+        let mut business_state = BusinessState::default();
+        business_state.colony.upkeep_per_second.clear();
+        business_state.set_skill_curve(SkillCurve {
+            level_thresholds: vec![10, 20],
+            bonus_percent_per_level: 50,
+        })?;
+
+        let user1: Principal = Principal::from_slice(&[1]);
+        business_state.player.insert(user1, PlayerState::default());
+
+        business_state.work_set(user1, None, 0)?;
+        business_state.work_claim(user1, MILLIS_TO_SECONDS * 10)?;
+
+        let profile = business_state.player_profile(&user1)?;
+        assert_eq!(profile.skills.len(), 4);
+        assert_eq!(
+            profile.skills[0],
+            SkillLevel {
+                resource: Resources::Wood,
+                level: 1,
+                experience: 10,
+                next_level_at: Some(20),
+                yield_bonus_percent: 50,
+            }
+        );
+
+        // Level 1 pays 50% more
+        business_state.work_set(user1, None, 0)?;
+        business_state.work_claim(user1, MILLIS_TO_SECONDS * 10)?;
+
+        let p = business_state.player.get(&user1).unwrap();
+        assert_eq!(p.inventory.get(Resources::Wood), 100 + 150);
+
+        let profile = business_state.player_profile(&user1)?;
+        assert_eq!(profile.skills[0].level, 2);
+        assert_eq!(profile.skills[0].next_level_at, None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_set_skill_curve_validation() {
+        let mut business_state = BusinessState::default();
+
+        assert_eq!(
+            business_state.set_skill_curve(SkillCurve {
+                level_thresholds: vec![10, 10],
+                bonus_percent_per_level: 1,
+            }),
+            Err("Level thresholds must be increasing".to_string())
+        );
+        assert_eq!(
+            business_state.set_skill_curve(SkillCurve {
+                level_thresholds: vec![10, 20],
+                bonus_percent_per_level: u64::MAX,
+            }),
+            Err("The bonus per level can't be more than 100%".to_string())
+        );
+        assert_eq!(
+            business_state.set_skill_curve(SkillCurve {
+                level_thresholds: vec![10, 20],
+                bonus_percent_per_level: MAX_BONUS_PERCENT_PER_LEVEL,
+            }),
+            Ok(())
+        );
+    }
+}
@@ -0,0 +1,198 @@
+use std::collections::{BTreeMap, HashMap};
+
+use candid::Principal;
+use serde::Serialize;
+
+use super::{BusinessState, ExpeditionState, ExpeditionStep, Resources};
+
+/// A read-only picture of a colony, for looking at snapshots outside of the canister. Principals
+/// are in their text form and resources are keyed by name, so it reads well as a table or JSON.
+#[derive(Serialize, Debug)]
+pub struct SnapshotView {
+    pub colony: ColonyView,
+    pub players: Vec<PlayerView>,
+    pub expeditions: Vec<ExpeditionView>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ColonyView {
+    pub generation: u8,
+    pub taxes_percent: u8,
+    pub coffers: BTreeMap<String, u64>,
+    pub expeditions_count: u64,
+    pub remote_colonies: Vec<String>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct PlayerView {
+    pub principal: String,
+    pub display_name: Option<String>,
+    pub status: String,
+    pub inventory: BTreeMap<String, u64>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ExpeditionView {
+    pub id: u64,
+    /// Just the name of the step, e.g. `Proposed`
+    pub step: String,
+    /// When the expedition started starting, or the canister of the new colony
+    pub step_detail: Option<String>,
+    pub proposed_by: String,
+    pub members: Vec<String>,
+    pub resources_required: BTreeMap<String, u64>,
+    pub resources_pool: BTreeMap<String, u64>,
+    /// The wasm the new colony was installed from
+    pub wasm_hash: Option<String>,
+}
+
+fn by_name(resources: &HashMap<Resources, u64>) -> BTreeMap<String, u64> {
+    resources
+        .iter()
+        .map(|(res, val)| (format!("{:?}", res), *val))
+        .collect()
+}
+
+fn step_view(step: &ExpeditionStep) -> (String, Option<String>) {
+    match step {
+        ExpeditionStep::Proposed => ("Proposed".to_string(), None),
+        ExpeditionStep::Ready => ("Ready".to_string(), None),
+        ExpeditionStep::Starting(since) => ("Starting".to_string(), Some(since.to_string())),
+        ExpeditionStep::Started(canister_id) => {
+            ("Started".to_string(), Some(canister_id.to_text()))
+        }
+        ExpeditionStep::Done => ("Done".to_string(), None),
+    }
+}
+
+impl ExpeditionView {
+    fn new(expedition: &ExpeditionState) -> Self {
+        let (step, step_detail) = step_view(&expedition.step);
+
+        Self {
+            id: expedition.id,
+            step,
+            step_detail,
+            proposed_by: expedition.proposed_by.to_text(),
+            members: expedition.members.iter().map(Principal::to_text).collect(),
+            resources_required: by_name(&expedition.resources_required),
+            resources_pool: by_name(&expedition.resources_pool.contents),
+            wasm_hash: expedition.wasm_hash.clone(),
+        }
+    }
+}
+
+impl SnapshotView {
+    /// Only keeps `principal`, and the expeditions they are a member of
+    pub fn filter_player(&mut self, principal: &Principal) {
+        let principal = principal.to_text();
+
+        self.players.retain(|p| p.principal == principal);
+        self.expeditions
+            .retain(|expedition| expedition.members.contains(&principal));
+    }
+
+    /// Only keeps the expeditions in the step named `step`, ignoring case
+    pub fn filter_step(&mut self, step: &str) {
+        self.expeditions
+            .retain(|expedition| expedition.step.eq_ignore_ascii_case(step));
+    }
+}
+
+impl BusinessState {
+    pub fn snapshot_view(&self) -> SnapshotView {
+        let colony = ColonyView {
+            generation: self.colony.generation,
+            taxes_percent: self.colony.taxes_percent,
+            coffers: by_name(&self.colony.coffers.contents),
+            expeditions_count: self.expeditions_count,
+            remote_colonies: self
+                .remote_colonies
+                .iter()
+                .map(Principal::to_text)
+                .collect(),
+        };
+
+        let mut players: Vec<PlayerView> = self
+            .player
+            .iter()
+            .map(|(principal, p)| PlayerView {
+                principal: principal.to_text(),
+                display_name: p.profile.display_name().map(str::to_string),
+                status: format!("{:?}", p.status),
+                inventory: by_name(&p.inventory.contents),
+            })
+            .collect();
+        players.sort_by(|a, b| a.principal.cmp(&b.principal));
+
+        let mut expeditions: Vec<ExpeditionView> =
+            self.expeditions.values().map(ExpeditionView::new).collect();
+        expeditions.sort_by_key(|expedition| expedition.id);
+
+        SnapshotView {
+            colony,
+            players,
+            expeditions,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snapshot_view_filters() -> Result<(), String> {
+        let mut business_state = BusinessState::default();
+
+        let user1: Principal = Principal::from_slice(&[1]);
+        let user2: Principal = Principal::from_slice(&[2]);
+        business_state.add_player(user1, 0)?;
+        business_state.add_player(user2, 0)?;
+        business_state
+            .colony
+            .add_to_coffers(&HashMap::from([(Resources::Wood, 500)]));
+        business_state.expeditions.insert(
+            0,
+            ExpeditionState {
+                id: 0,
+                proposed_by: user1,
+                members: vec![user1],
+                ..Default::default()
+            },
+        );
+        business_state.expeditions.insert(
+            1,
+            ExpeditionState {
+                id: 1,
+                step: ExpeditionStep::Ready,
+                proposed_by: user2,
+                members: vec![user2],
+                ..Default::default()
+            },
+        );
+
+        let view = business_state.snapshot_view();
+        assert_eq!(view.players.len(), 2);
+        assert_eq!(view.expeditions.len(), 2);
+        assert_eq!(view.colony.coffers.get("Wood"), Some(&500));
+
+        let mut view = business_state.snapshot_view();
+        view.filter_player(&user1);
+        assert_eq!(view.players.len(), 1);
+        assert_eq!(view.players[0].principal, user1.to_text());
+        assert_eq!(
+            view.expeditions.iter().map(|e| e.id).collect::<Vec<_>>(),
+            vec![0]
+        );
+
+        let mut view = business_state.snapshot_view();
+        view.filter_step("ready");
+        assert_eq!(
+            view.expeditions.iter().map(|e| e.id).collect::<Vec<_>>(),
+            vec![1]
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,217 @@
+use std::collections::HashMap;
+
+use candid::{CandidType, Deserialize, Principal};
+
+use crate::env::TimestampMillis;
+use crate::utils::sha256_hex;
+
+/// Every wasm uploaded to the colony, keyed by its SHA-256. One of them is active and gets
+/// installed in the colonies started by expeditions.
+#[derive(CandidType, Deserialize, Debug, Default)]
+pub struct WasmRegistry {
+    modules: HashMap<String, StoredWasm>,
+    active: Option<String>,
+    /// The versions that were active before the current one, the most recent last
+    history: Vec<String>,
+}
+
+#[derive(CandidType, Deserialize, Debug)]
+struct StoredWasm {
+    label: String,
+    uploaded_at: TimestampMillis,
+    uploaded_by: Principal,
+    module: Vec<u8>,
+}
+
+#[derive(CandidType, Deserialize, Debug, Clone, PartialEq)]
+pub struct WasmVersion {
+    pub sha256: String,
+    pub label: String,
+    pub size: u64,
+    pub uploaded_at: TimestampMillis,
+    pub uploaded_by: Principal,
+    pub active: bool,
+}
+
+impl WasmRegistry {
+    /// Stores `module` and returns its hash. Uploading a module that is already stored only
+    /// updates its label, if a new one is given.
+    pub fn add(
+        &mut self,
+        module: Vec<u8>,
+        label: Option<String>,
+        uploaded_by: Principal,
+        now: TimestampMillis,
+    ) -> String {
+        let sha256 = sha256_hex(&module);
+
+        let stored = self
+            .modules
+            .entry(sha256.clone())
+            .or_insert_with(|| StoredWasm {
+                label: String::new(),
+                uploaded_at: now,
+                uploaded_by,
+                module,
+            });
+        if let Some(label) = label {
+            stored.label = label;
+        }
+
+        sha256
+    }
+
+    /// Makes the stored module `sha256` the one new colonies are installed with
+    pub fn activate(&mut self, sha256: &str) -> Result<(), String> {
+        if !self.modules.contains_key(sha256) {
+            return Err(format!("There is no wasm {}", sha256));
+        }
+
+        if self.active.as_deref() != Some(sha256) {
+            if let Some(previous) = self.active.replace(sha256.to_string()) {
+                self.history.push(previous);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Goes back to the version that was active before the current one, and returns its hash
+    pub fn rollback(&mut self) -> Result<String, String> {
+        let previous = self
+            .history
+            .pop()
+            .ok_or_else(|| "There is no earlier wasm to roll back to".to_string())?;
+        self.active = Some(previous.clone());
+
+        Ok(previous)
+    }
+
+    /// Deletes the stored module `sha256`, which can't be the active one or the one a rollback
+    /// would go back to
+    pub fn remove(&mut self, sha256: &str) -> Result<(), String> {
+        if !self.modules.contains_key(sha256) {
+            return Err(format!("There is no wasm {}", sha256));
+        }
+        if self.active.as_deref() == Some(sha256) {
+            return Err("The active wasm can't be removed".to_string());
+        }
+        if self.history.last().map(String::as_str) == Some(sha256) {
+            return Err("The wasm a rollback would go back to can't be removed".to_string());
+        }
+
+        self.modules.remove(sha256);
+        self.history.retain(|previous| previous != sha256);
+
+        Ok(())
+    }
+
+    /// The hash and module of the active version
+    pub fn active(&self) -> Option<(&str, &[u8])> {
+        let sha256 = self.active.as_deref()?;
+
+        Some((sha256, self.module(sha256)?))
+    }
+
+    pub fn module(&self, sha256: &str) -> Option<&[u8]> {
+        self.modules
+            .get(sha256)
+            .map(|stored| stored.module.as_slice())
+    }
+
+    /// Every stored version, oldest first
+    pub fn versions(&self) -> Vec<WasmVersion> {
+        let mut versions: Vec<WasmVersion> = self
+            .modules
+            .iter()
+            .map(|(sha256, stored)| WasmVersion {
+                sha256: sha256.clone(),
+                label: stored.label.clone(),
+                size: stored.module.len() as u64,
+                uploaded_at: stored.uploaded_at,
+                uploaded_by: stored.uploaded_by,
+                active: self.active.as_ref() == Some(sha256),
+            })
+            .collect();
+        versions.sort_by(|a, b| (a.uploaded_at, &a.sha256).cmp(&(b.uploaded_at, &b.sha256)));
+
+        versions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_activate_and_rollback() {
+        let admin = Principal::from_slice(&[1]);
+        let mut registry = WasmRegistry::default();
+        assert!(registry.active().is_none());
+
+        let v1 = registry.add(b"\0asm1".to_vec(), Some("v1".to_string()), admin, 10);
+        let v2 = registry.add(b"\0asm2".to_vec(), None, admin, 20);
+        assert_eq!(registry.add(b"\0asm1".to_vec(), None, admin, 30), v1);
+
+        registry.activate(&v1).unwrap();
+        registry.activate(&v2).unwrap();
+        assert_eq!(registry.active(), Some((v2.as_str(), &b"\0asm2"[..])));
+        assert_eq!(
+            registry.activate("beef"),
+            Err("There is no wasm beef".to_string())
+        );
+
+        let versions = registry.versions();
+        assert_eq!(versions.len(), 2);
+        assert_eq!(versions[0].label, "v1");
+        assert_eq!(versions[0].uploaded_at, 10);
+        assert!(!versions[0].active);
+        assert!(versions[1].active);
+
+        assert_eq!(registry.rollback(), Ok(v1.clone()));
+        assert_eq!(
+            registry.active().map(|(sha256, _)| sha256),
+            Some(v1.as_str())
+        );
+        assert_eq!(
+            registry.rollback(),
+            Err("There is no earlier wasm to roll back to".to_string())
+        );
+    }
+
+    #[test]
+    fn test_remove() {
+        let admin = Principal::from_slice(&[1]);
+        let mut registry = WasmRegistry::default();
+        let v1 = registry.add(b"\0asm1".to_vec(), None, admin, 10);
+        let v2 = registry.add(b"\0asm2".to_vec(), None, admin, 20);
+        let v3 = registry.add(b"\0asm3".to_vec(), None, admin, 30);
+        registry.activate(&v1).unwrap();
+        registry.activate(&v2).unwrap();
+        registry.activate(&v3).unwrap();
+
+        assert_eq!(
+            registry.remove(&v3),
+            Err("The active wasm can't be removed".to_string())
+        );
+        assert_eq!(
+            registry.remove(&v2),
+            Err("The wasm a rollback would go back to can't be removed".to_string())
+        );
+        assert_eq!(
+            registry.remove("beef"),
+            Err("There is no wasm beef".to_string())
+        );
+
+        registry.remove(&v1).unwrap();
+        assert!(registry.module(&v1).is_none());
+        assert_eq!(registry.versions().len(), 2);
+
+        // The removed version is skipped by later rollbacks
+        assert_eq!(registry.rollback(), Ok(v2));
+        assert_eq!(
+            registry.rollback(),
+            Err("There is no earlier wasm to roll back to".to_string())
+        );
+    }
+}
@@ -0,0 +1,2 @@
+pub const MILLIS_TO_SECONDS: u64 = 1_000_000_000;
+pub type TimestampMillis = u64;
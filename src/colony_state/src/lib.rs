@@ -0,0 +1,25 @@
+//! The state of a colony and the rules of the game, without the canister around them. The
+//! snapshot_inspector uses it to decode snapshots with the same types as the canister.
+
+pub mod business_logic;
+pub mod env;
+pub mod stable_state;
+pub mod utils;
+
+use candid::{CandidType, Deserialize};
+
+use crate::business_logic::{BusinessState, SystemSettings};
+
+pub use business_logic::{ColonyView, ExpeditionView, PlayerView, SnapshotView};
+
+/// Everything that is saved across upgrades
+#[derive(CandidType, Default, Deserialize)]
+pub struct Data {
+    pub business_state: BusinessState,
+    pub system_settings: SystemSettings,
+}
+
+/// Reads a snapshot saved by `startSnapshotExport` outside of the canister
+pub fn inspect_snapshot(bytes: &[u8]) -> Result<SnapshotView, String> {
+    Ok(stable_state::decode(bytes)?.business_state.snapshot_view())
+}
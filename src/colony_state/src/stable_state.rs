@@ -0,0 +1,362 @@
+//! How `Data` is kept in stable memory across upgrades. The state is saved together with its
+//! version, and post_upgrade runs it through one migration per version step until it matches the
+//! current `Data`.
+
+use candid::de::IDLDeserialize;
+use candid::utils::ArgumentDecoder;
+use candid::{CandidType, Deserialize, Reserved};
+
+use crate::business_logic::{migrations, SystemSettings};
+use crate::Data;
+
+/// Bump this, freeze the previous layout in `business_logic::migrations` and add a migration
+/// step whenever `Data` changes in a way candid can't decode on its own, e.g. a new field that
+/// isn't an `Option`.
+pub const STATE_VERSION: u32 = 2;
+
+mod v0 {
+    use super::*;
+
+    #[derive(CandidType, Deserialize, Default)]
+    pub struct Data {
+        pub business_state: migrations::v0::BusinessState,
+        pub system_settings: SystemSettings,
+    }
+
+    #[derive(CandidType, Deserialize, Default)]
+    pub struct SystemSettings {}
+}
+
+mod v1 {
+    use super::*;
+    use candid::Principal;
+
+    #[derive(CandidType, Deserialize, Default)]
+    pub struct Data {
+        pub business_state: migrations::v1::BusinessState,
+        pub system_settings: SystemSettings,
+    }
+
+    #[derive(CandidType, Deserialize, Default)]
+    pub struct SystemSettings {
+        pub admins: Vec<Principal>,
+    }
+}
+
+/// The state as it was found in stable memory, in any of the versions a canister might hold
+enum StableState {
+    V0(Box<v0::Data>),
+    V1(Box<v1::Data>),
+    V2(Box<Data>),
+}
+
+impl StableState {
+    /// Takes the state one version step forward
+    fn migrate(self) -> Self {
+        match self {
+            // There were no admins before. post_upgrade makes whoever upgrades the canister one.
+            StableState::V0(data) => StableState::V1(Box::new(v1::Data {
+                business_state: data.business_state.into(),
+                system_settings: Default::default(),
+            })),
+            StableState::V1(data) => StableState::V2(Box::new(Data {
+                business_state: data.business_state.into(),
+                system_settings: SystemSettings {
+                    admins: data.system_settings.admins,
+                },
+            })),
+            StableState::V2(data) => StableState::V2(data),
+        }
+    }
+
+    fn into_current(mut self) -> Data {
+        loop {
+            match self {
+                StableState::V2(data) => return *data,
+                older => self = older.migrate(),
+            }
+        }
+    }
+}
+
+/// Like `candid::decode_args`, but ignores whatever follows the arguments. Stable memory is
+/// read in whole pages, so there are always zeroes left at the end.
+fn decode_prefix<T: for<'de> ArgumentDecoder<'de>>(bytes: &[u8]) -> Result<T, String> {
+    let mut de = IDLDeserialize::new(bytes).map_err(|e| e.to_string())?;
+    ArgumentDecoder::decode(&mut de).map_err(|e| e.to_string())
+}
+
+pub fn encode(data: &Data) -> Result<Vec<u8>, String> {
+    candid::encode_args((STATE_VERSION, data)).map_err(|e| e.to_string())
+}
+
+/// Reads the `(STATE_VERSION, Data)` saved by pre_upgrade, or the state of a canister from
+/// before the state had a version, and migrates it to the current `Data`.
+pub fn decode(bytes: &[u8]) -> Result<Data, String> {
+    let state = match decode_prefix::<(u32, Reserved)>(bytes) {
+        // Unversioned state was saved as just `(Data,)`
+        Err(_) => StableState::V0(Box::new(decode_prefix::<(v0::Data,)>(bytes)?.0)),
+        Ok((1, _)) => StableState::V1(Box::new(decode_prefix::<(u32, v1::Data)>(bytes)?.1)),
+        Ok((2, _)) => StableState::V2(Box::new(decode_prefix::<(u32, Data)>(bytes)?.1)),
+        Ok((version, _)) => {
+            return Err(format!(
+                "Stable state version {} is newer than this canister's version {}",
+                version, STATE_VERSION
+            ))
+        }
+    };
+
+    Ok(state.into_current())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::business_logic::{
+        Building, ExpeditionStep, Item, OrderSide, PlayerStatus, Resources,
+    };
+    use candid::Principal;
+    use std::collections::HashMap;
+
+    fn v0_fixture() -> v0::Data {
+        let user1 = Principal::from_slice(&[1]);
+
+        v0::Data {
+            business_state: migrations::v0::BusinessState {
+                colony: migrations::v0::ColonyState {
+                    generation: 1,
+                    taxes_percent: 5,
+                    global_resources_multiplier: 2,
+                    rewards_per_second: HashMap::from([(Resources::Gold, 100)]),
+                    coffers: migrations::v0::Inventory {
+                        size: 0,
+                        contents: HashMap::from([(Resources::Wood, 500)]),
+                    },
+                },
+                player: HashMap::from([(
+                    user1,
+                    migrations::v0::PlayerState {
+                        status: PlayerStatus::WorkingAll(42),
+                        inventory: migrations::v0::Inventory {
+                            size: 0,
+                            contents: HashMap::from([(Resources::Stone, 70)]),
+                        },
+                    },
+                )]),
+                expeditions: HashMap::from([(
+                    0,
+                    migrations::v0::ExpeditionState {
+                        id: 0,
+                        step: ExpeditionStep::Ready,
+                        proposed_by: user1,
+                        proposed_at: 7,
+                        resources_required: HashMap::from([(Resources::Food, 60)]),
+                        resources_pool: Default::default(),
+                        members: vec![user1],
+                    },
+                )]),
+                expeditions_count: 1,
+                remote_colonies: vec![Principal::from_slice(&[9])],
+                wasm_store: vec![0, 97, 115, 109],
+            },
+            system_settings: v0::SystemSettings {},
+        }
+    }
+
+    #[test]
+    fn test_restore_v0() {
+        // What `stable_save((&data,))` wrote before the state had a version, followed by the
+        // rest of the stable memory page
+        let mut bytes = candid::encode_args((&v0_fixture(),)).unwrap();
+        bytes.resize(bytes.len() + 1024, 0);
+
+        let data = decode(&bytes).unwrap();
+        let business_state = &data.business_state;
+
+        assert_eq!(business_state.colony.generation, 1);
+        assert_eq!(business_state.colony.global_resources_multiplier, 2);
+        assert_eq!(business_state.colony.coffers.get(Resources::Wood), 500);
+        assert_eq!(business_state.colony.max_work_session_seconds, 8 * 60 * 60);
+
+        let p = &business_state.player[&Principal::from_slice(&[1])];
+        assert_eq!(p.get_status(), PlayerStatus::WorkingAll(42));
+        assert_eq!(p.get_inventory().get(Resources::Stone), 70);
+
+        let expedition = &business_state.expeditions[&0];
+        assert!(matches!(
+            expedition.clone().get_step(),
+            ExpeditionStep::Ready
+        ));
+        assert_eq!(expedition.outcome, None);
+
+        assert_eq!(business_state.expeditions_count, 1);
+        assert_eq!(
+            business_state.remote_colonies,
+            vec![Principal::from_slice(&[9])]
+        );
+        assert_eq!(
+            business_state.wasm_registry.active(),
+            Some((
+                "cd5d4935a48c0672cb06407bb443bc0087aff947c6b864bac886982c73b3027f",
+                &[0, 97, 115, 109][..]
+            ))
+        );
+        assert!(data.system_settings.admins.is_empty());
+    }
+
+    fn v1_fixture() -> v1::Data {
+        use migrations::v1::*;
+
+        let user1 = Principal::from_slice(&[1]);
+        let mut data = v1::Data::default();
+        let business_state = &mut data.business_state;
+
+        business_state.colony.max_work_session_seconds = 60;
+        business_state.player.insert(
+            user1,
+            PlayerState {
+                status: PlayerStatus::Idle,
+                inventory: Inventory {
+                    size: 0,
+                    contents: HashMap::from([(Resources::Wood, 80)]),
+                    escrow: HashMap::from([(Resources::Wood, 20)]),
+                },
+                lp_shares: HashMap::from([((Resources::Wood, Resources::Stone), 5)]),
+                items: vec![ItemState {
+                    item: Item::Axe,
+                    durability: 10,
+                }],
+                crafting: vec![],
+                experience: HashMap::from([(Resources::Wood, 3)]),
+                profile: Profile {
+                    display_name: Some("ada".to_string()),
+                    ..Default::default()
+                },
+            },
+        );
+        business_state.expeditions.insert(
+            0,
+            ExpeditionState {
+                id: 0,
+                step: ExpeditionStep::Proposed,
+                proposed_by: user1,
+                proposed_at: 7,
+                resources_required: Default::default(),
+                resources_pool: Default::default(),
+                members: vec![user1],
+                outcome: Some(ExpeditionOutcome {
+                    seed: 4,
+                    lost_percent: 12,
+                    found: vec![],
+                    rewards_per_second: Default::default(),
+                }),
+            },
+        );
+        business_state.expeditions_count = 2;
+        business_state.wasm_store = b"\0asm".to_vec();
+        business_state.market.orders.insert(
+            0,
+            Order {
+                id: 0,
+                owner: user1,
+                side: OrderSide::Ask,
+                base: Resources::Wood,
+                quote: Resources::Stone,
+                price: 2,
+                amount: 20,
+                remaining: 20,
+                placed_at: 7,
+            },
+        );
+        business_state.market.orders_count = 1;
+        business_state.amm.pools.insert(
+            (Resources::Wood, Resources::Stone),
+            Pool {
+                reserve_a: 100,
+                reserve_b: 100,
+                total_shares: 100,
+                colony_shares: 95,
+            },
+        );
+        business_state
+            .buildings
+            .insert(Building::Farm, BuildingState::default());
+        business_state.leaderboards.stats.insert(
+            user1,
+            PlayerStats {
+                seconds_worked: 3,
+                ..Default::default()
+            },
+        );
+        data.system_settings.admins.push(user1);
+
+        data
+    }
+
+    #[test]
+    fn test_restore_v1() {
+        let admin = Principal::from_slice(&[1]);
+        let mut bytes = candid::encode_args((1u32, &v1_fixture())).unwrap();
+        bytes.resize(bytes.len() + 1024, 0);
+
+        let restored = decode(&bytes).unwrap();
+        let business_state = &restored.business_state;
+        assert_eq!(business_state.colony.max_work_session_seconds, 60);
+        assert_eq!(business_state.expeditions_count, 2);
+        assert_eq!(restored.system_settings.admins, vec![admin]);
+
+        let p = &business_state.player[&admin];
+        assert_eq!(p.get_inventory().get(Resources::Wood), 80);
+        assert_eq!(
+            business_state.profile(&admin).unwrap().display_name(),
+            Some("ada")
+        );
+        assert_eq!(business_state.player_orders(admin).len(), 1);
+        assert_eq!(business_state.pools().len(), 1);
+        assert_eq!(business_state.recipes().len(), 4);
+        assert!(business_state.buildings.contains_key(&Building::Farm));
+        assert_ne!(business_state.player_stats(&admin), Default::default());
+
+        let expedition = &business_state.expeditions[&0];
+        assert_eq!(expedition.outcome.as_ref().unwrap().lost_percent, 12);
+        assert_eq!(expedition.wasm_hash, None);
+
+        let versions = restored.business_state.wasm_registry.versions();
+        assert_eq!(versions.len(), 1);
+        assert!(versions[0].active);
+        assert_eq!(versions[0].uploaded_by, Principal::anonymous());
+    }
+
+    #[test]
+    fn test_restore_current_version() {
+        let mut data = Data::default();
+        data.business_state.expeditions_count = 3;
+        data.system_settings
+            .admins
+            .push(Principal::from_slice(&[1]));
+
+        let mut bytes = encode(&data).unwrap();
+        bytes.resize(bytes.len() + 1024, 0);
+
+        let restored = decode(&bytes).unwrap();
+        assert_eq!(restored.business_state.expeditions_count, 3);
+        assert_eq!(
+            restored.system_settings.admins,
+            vec![Principal::from_slice(&[1])]
+        );
+    }
+
+    #[test]
+    fn test_refuse_newer_version() {
+        let bytes = candid::encode_args((STATE_VERSION + 1, Data::default())).unwrap();
+
+        assert_eq!(
+            decode(&bytes).err(),
+            Some(format!(
+                "Stable state version {} is newer than this canister's version {}",
+                STATE_VERSION + 1,
+                STATE_VERSION
+            ))
+        );
+    }
+}
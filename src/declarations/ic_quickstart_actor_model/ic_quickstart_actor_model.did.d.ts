@@ -1,4 +1,27 @@
 import type { Principal } from '@dfinity/principal';
+export type Building = { 'Quarry' : null } |
+  { 'Sawmill' : null } |
+  { 'Farm' : null } |
+  { 'Well' : null } |
+  { 'Warehouse' : null };
+export interface BuildingProgress {
+  'building' : Building,
+  'level' : number,
+  'required' : Array<[Resources, bigint]>,
+  'contributed' : Array<[Resources, bigint]>,
+}
+export interface ChildUpgrade {
+  'result' : Result,
+  'canister_id' : Principal,
+  'children' : Option,
+}
+export interface ChildrenUpgrade {
+  'cascade' : boolean,
+  'children' : Array<ChildUpgrade>,
+  'started_at' : bigint,
+  'wasm_hash' : string,
+  'finished_at' : [] | [bigint],
+}
 export interface ColonyInfo {
   'player_count' : bigint,
   'taxes_percent' : number,
@@ -7,15 +30,26 @@ export interface ColonyInfo {
   'coffers' : Inventory,
   'expeditions_count' : bigint,
   'rewards_per_second' : Array<[Resources, number]>,
+  'buildings' : Array<BuildingProgress>,
+}
+export interface CraftingJob { 'item' : Item, 'ready_at' : bigint }
+export interface ExpeditionOutcome {
+  'found' : Array<[Resources, bigint]>,
+  'lost_percent' : number,
+  'seed' : number,
+  'rewards_per_second' : Array<[Resources, number]>,
 }
 export interface ExpeditionState {
   'id' : bigint,
   'members' : Array<Principal>,
+  'colony_canister' : [] | [Principal],
   'step' : ExpeditionStep,
   'resources_required' : Array<[Resources, bigint]>,
+  'outcome' : [] | [ExpeditionOutcome],
   'proposed_at' : bigint,
   'proposed_by' : Principal,
   'resources_pool' : Inventory,
+  'wasm_hash' : [] | [string],
 }
 export type ExpeditionStep = { 'Started' : Principal } |
   { 'Starting' : bigint } |
@@ -25,6 +59,113 @@ export type ExpeditionStep = { 'Started' : Principal } |
 export interface Inventory {
   'contents' : Array<[Resources, bigint]>,
   'size' : number,
+  'escrow' : Array<[Resources, bigint]>,
+}
+export type Item = { 'Axe' : null } |
+  { 'Bucket' : null } |
+  { 'Pickaxe' : null } |
+  { 'Sickle' : null };
+export interface ItemState { 'item' : Item, 'durability' : bigint }
+export interface LeaderboardEntry {
+  'principal' : Principal,
+  'rank' : bigint,
+  'score' : bigint,
+}
+export type LeaderboardKind = { 'TaxesPaid' : null } |
+  { 'SecondsWorked' : null } |
+  { 'ResourcesEarned' : null } |
+  { 'ExpeditionsJoined' : null } |
+  { 'ExpeditionContributions' : null };
+export interface LeaderboardPage {
+  'total' : bigint,
+  'kind' : LeaderboardKind,
+  'entries' : Array<LeaderboardEntry>,
+}
+export interface NewOrder {
+  'base' : Resources,
+  'side' : OrderSide,
+  'quote' : Resources,
+  'price' : bigint,
+  'amount' : bigint,
+}
+export type Option = [] | [
+  {
+    'cascade' : boolean,
+    'children' : Array<
+      {
+        'result' : { 'Ok' : null } |
+          { 'Err' : string },
+        'canister_id' : Principal,
+        'children' : Option,
+      }
+    >,
+    'started_at' : bigint,
+    'wasm_hash' : string,
+    'finished_at' : [] | [bigint],
+  }
+];
+export interface Order {
+  'id' : bigint,
+  'placed_at' : bigint,
+  'owner' : Principal,
+  'base' : Resources,
+  'side' : OrderSide,
+  'quote' : Resources,
+  'remaining' : bigint,
+  'price' : bigint,
+  'amount' : bigint,
+}
+export interface OrderBookDepth {
+  'asks' : Array<[bigint, bigint]>,
+  'bids' : Array<[bigint, bigint]>,
+}
+export type OrderSide = { 'Ask' : null } |
+  { 'Bid' : null };
+export type PlayerError = { 'NotRegistered' : null } |
+  { 'Other' : string };
+export interface PlayerItems {
+  'crafting' : Array<CraftingJob>,
+  'items' : Array<ItemState>,
+}
+export interface PlayerProfile { 'skills' : Array<SkillLevel> }
+export interface PlayerStats {
+  'seconds_worked' : bigint,
+  'taxes_paid' : bigint,
+  'expedition_contributions' : bigint,
+  'expeditions_joined' : bigint,
+  'resources_earned' : bigint,
+}
+export interface Pool {
+  'total_shares' : bigint,
+  'colony_shares' : bigint,
+  'reserve_a' : bigint,
+  'reserve_b' : bigint,
+}
+export interface PoolDeposit {
+  'a' : Resources,
+  'b' : Resources,
+  'amount_a' : bigint,
+  'amount_b' : bigint,
+}
+export interface PoolInfo { 'a' : Resources, 'b' : Resources, 'pool' : Pool }
+export interface Profile {
+  'bio' : string,
+  'avatar_url' : [] | [string],
+  'display_name' : [] | [string],
+  'joined_at' : bigint,
+}
+export interface ProfileArgs {
+  'bio' : string,
+  'avatar_url' : string,
+  'display_name' : string,
+}
+export interface Recipe {
+  'item' : Item,
+  'boosts' : Array<Resources>,
+  'inputs' : Array<[Resources, bigint]>,
+  'crafting_seconds' : bigint,
+  'durability' : bigint,
+  'bonus_percent' : bigint,
 }
 export type Resources = { 'Stone' : null } |
   { 'Food' : null } |
@@ -33,22 +174,134 @@ export type Resources = { 'Stone' : null } |
   { 'Water' : null };
 export type Result = { 'Ok' : null } |
   { 'Err' : string };
-export type Result_1 = { 'Ok' : Array<[Resources, bigint]> } |
+export type Result_1 = { 'Ok' : bigint } |
+  { 'Err' : PlayerError };
+export type Result_10 = { 'Ok' : bigint } |
+  { 'Err' : string };
+export type Result_11 = { 'Ok' : Array<[Resources, bigint]> } |
+  { 'Err' : PlayerError };
+export type Result_12 = { 'Ok' : UpkeepEstimate } |
+  { 'Err' : PlayerError };
+export type Result_13 = { 'Ok' : string } |
+  { 'Err' : string };
+export type Result_14 = { 'Ok' : SnapshotInfo } |
+  { 'Err' : string };
+export type Result_15 = { 'Ok' : ChildrenUpgrade } |
+  { 'Err' : string };
+export type Result_2 = { 'Ok' : Array<Item> } |
+  { 'Err' : PlayerError };
+export type Result_3 = { 'Ok' : BuildingProgress } |
+  { 'Err' : PlayerError };
+export type Result_4 = { 'Ok' : null } |
+  { 'Err' : PlayerError };
+export type Result_5 = { 'Ok' : Inventory } |
+  { 'Err' : PlayerError };
+export type Result_6 = { 'Ok' : PlayerItems } |
+  { 'Err' : PlayerError };
+export type Result_7 = { 'Ok' : PlayerProfile } |
+  { 'Err' : PlayerError };
+export type Result_8 = { 'Ok' : Profile } |
+  { 'Err' : PlayerError };
+export type Result_9 = { 'Ok' : Array<number> } |
   { 'Err' : string };
+export interface SkillCurve {
+  'level_thresholds' : Array<bigint>,
+  'bonus_percent_per_level' : bigint,
+}
+export interface SkillLevel {
+  'resource' : Resources,
+  'level' : number,
+  'experience' : bigint,
+  'next_level_at' : [] | [bigint],
+  'yield_bonus_percent' : bigint,
+}
+export interface SnapshotInfo { 'sha256' : string, 'size' : bigint }
+export interface SwapArgs {
+  'to' : Resources,
+  'min_amount_out' : bigint,
+  'from' : Resources,
+  'amount_in' : bigint,
+}
+export interface Trade {
+  'id' : bigint,
+  'executed_at' : bigint,
+  'base' : Resources,
+  'maker_order_id' : bigint,
+  'quote' : Resources,
+  'seller' : Principal,
+  'taker_order_id' : bigint,
+  'buyer' : Principal,
+  'price' : bigint,
+  'amount' : bigint,
+}
+export interface UpkeepEstimate {
+  'seconds_worked' : bigint,
+  'consumed' : Array<[Resources, bigint]>,
+  'supplied_seconds' : [] | [bigint],
+  'upkeep_per_second' : Array<[Resources, bigint]>,
+  'seconds_paid' : bigint,
+}
+export interface WasmVersion {
+  'active' : boolean,
+  'sha256' : string,
+  'size' : bigint,
+  'label' : string,
+  'uploaded_at' : bigint,
+  'uploaded_by' : Principal,
+}
 export interface _SERVICE {
+  'activateWasm' : (arg_0: string) => Promise<Result>,
+  'addAdmin' : (arg_0: Principal) => Promise<Result>,
+  'addLiquidity' : (arg_0: PoolDeposit) => Promise<Result_1>,
   'addPlayerToWorld' : () => Promise<Result>,
+  'cancelOrder' : (arg_0: bigint) => Promise<Result>,
+  'collectCrafted' : () => Promise<Result_2>,
+  'contributeToBuilding' : (arg_0: Building, arg_1: Array<[Resources, bigint]>) => Promise<Result_3>,
+  'craft' : (arg_0: Item) => Promise<Result_4>,
   'demoAddResourcesToExpedition' : () => Promise<Result>,
   'expeditionNext' : (arg_0: bigint) => Promise<Result>,
+  'finishSnapshotImport' : () => Promise<Result>,
+  'getChildrenUpgrade' : () => Promise<[] | [ChildrenUpgrade]>,
   'getColonyInfo' : () => Promise<ColonyInfo>,
   'getExpeditions' : () => Promise<Array<[bigint, ExpeditionState]>>,
-  'getPlayerInventory' : () => Promise<Inventory>,
+  'getLeaderboard' : (arg_0: LeaderboardKind, arg_1: bigint, arg_2: bigint) => Promise<LeaderboardPage>,
+  'getMyOrders' : () => Promise<Array<Order>>,
+  'getOrderBook' : (arg_0: Resources, arg_1: Resources) => Promise<OrderBookDepth>,
+  'getPlayerInventory' : () => Promise<Result_5>,
+  'getPlayerItems' : () => Promise<Result_6>,
+  'getPlayerProfile' : () => Promise<Result_7>,
+  'getPlayerStats' : (arg_0: Principal) => Promise<PlayerStats>,
+  'getPools' : () => Promise<Array<PoolInfo>>,
+  'getProfile' : (arg_0: Principal) => Promise<Result_8>,
+  'getRecentTrades' : (arg_0: Resources, arg_1: Resources) => Promise<Array<Trade>>,
+  'getRecipes' : () => Promise<Array<Recipe>>,
   'getRemoteColonies' : () => Promise<Array<Principal>>,
-  'getUnclaimedWork' : () => Promise<Result_1>,
+  'getSnapshotChunk' : (arg_0: bigint) => Promise<Result_9>,
+  'getSwapQuote' : (arg_0: Resources, arg_1: Resources, arg_2: bigint) => Promise<Result_10>,
+  'getUnclaimedWork' : () => Promise<Result_11>,
+  'getUpkeepEstimate' : () => Promise<Result_12>,
+  'getWasmVersions' : () => Promise<Array<WasmVersion>>,
   'greet' : (arg_0: string) => Promise<string>,
+  'harvest' : () => Promise<Result_11>,
   'isPlayerHere' : () => Promise<boolean>,
-  'joinExpedition' : (arg_0: bigint) => Promise<Result>,
-  'startExpedition' : () => Promise<Result>,
-  'startWork' : () => Promise<Result>,
-  'stopWork' : () => Promise<Result>,
+  'joinExpedition' : (arg_0: bigint) => Promise<Result_4>,
+  'placeOrder' : (arg_0: NewOrder) => Promise<Result_1>,
+  'removeLiquidity' : (arg_0: Resources, arg_1: Resources, arg_2: bigint) => Promise<Result_11>,
+  'removeWasm' : (arg_0: string) => Promise<Result>,
+  'rollbackWasm' : () => Promise<Result_13>,
+  'seedPool' : (arg_0: PoolDeposit) => Promise<Result_10>,
+  'setMaxWorkSession' : (arg_0: bigint) => Promise<Result>,
+  'setProfile' : (arg_0: ProfileArgs) => Promise<Result_4>,
+  'setSkillCurve' : (arg_0: SkillCurve) => Promise<Result>,
+  'setSwapFee' : (arg_0: number) => Promise<Result>,
+  'setUpkeepRates' : (arg_0: Array<[Resources, number]>) => Promise<Result>,
+  'startExpedition' : () => Promise<Result_4>,
+  'startSnapshotExport' : () => Promise<Result_14>,
+  'startSnapshotImport' : (arg_0: SnapshotInfo) => Promise<Result>,
+  'startWork' : () => Promise<Result_4>,
+  'stopWork' : () => Promise<Result_4>,
+  'swap' : (arg_0: SwapArgs) => Promise<Result_1>,
+  'upgradeChildren' : (arg_0: string, arg_1: [] | [boolean]) => Promise<Result_15>,
+  'uploadSnapshotChunk' : (arg_0: Array<number>) => Promise<Result>,
   'wasm_sha256' : () => Promise<string>,
 }
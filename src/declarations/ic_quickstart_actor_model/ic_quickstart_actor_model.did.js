@@ -1,4 +1,5 @@
 export const idlFactory = ({ IDL }) => {
+  const Option = IDL.Rec();
   const Result = IDL.Variant({ 'Ok' : IDL.Null, 'Err' : IDL.Text });
   const Resources = IDL.Variant({
     'Stone' : IDL.Null,
@@ -7,9 +8,75 @@ export const idlFactory = ({ IDL }) => {
     'Wood' : IDL.Null,
     'Water' : IDL.Null,
   });
+  const PoolDeposit = IDL.Record({
+    'a' : Resources,
+    'b' : Resources,
+    'amount_a' : IDL.Nat64,
+    'amount_b' : IDL.Nat64,
+  });
+  const PlayerError = IDL.Variant({
+    'NotRegistered' : IDL.Null,
+    'Other' : IDL.Text,
+  });
+  const Result_1 = IDL.Variant({ 'Ok' : IDL.Nat64, 'Err' : PlayerError });
+  const Item = IDL.Variant({
+    'Axe' : IDL.Null,
+    'Bucket' : IDL.Null,
+    'Pickaxe' : IDL.Null,
+    'Sickle' : IDL.Null,
+  });
+  const Result_2 = IDL.Variant({ 'Ok' : IDL.Vec(Item), 'Err' : PlayerError });
+  const Building = IDL.Variant({
+    'Quarry' : IDL.Null,
+    'Sawmill' : IDL.Null,
+    'Farm' : IDL.Null,
+    'Well' : IDL.Null,
+    'Warehouse' : IDL.Null,
+  });
+  const BuildingProgress = IDL.Record({
+    'building' : Building,
+    'level' : IDL.Nat8,
+    'required' : IDL.Vec(IDL.Tuple(Resources, IDL.Nat64)),
+    'contributed' : IDL.Vec(IDL.Tuple(Resources, IDL.Nat64)),
+  });
+  const Result_3 = IDL.Variant({
+    'Ok' : BuildingProgress,
+    'Err' : PlayerError,
+  });
+  const Result_4 = IDL.Variant({ 'Ok' : IDL.Null, 'Err' : PlayerError });
+  Option.fill(
+    IDL.Opt(
+      IDL.Record({
+        'cascade' : IDL.Bool,
+        'children' : IDL.Vec(
+          IDL.Record({
+            'result' : IDL.Variant({ 'Ok' : IDL.Null, 'Err' : IDL.Text }),
+            'canister_id' : IDL.Principal,
+            'children' : Option,
+          })
+        ),
+        'started_at' : IDL.Nat64,
+        'wasm_hash' : IDL.Text,
+        'finished_at' : IDL.Opt(IDL.Nat64),
+      })
+    )
+  );
+  const ChildUpgrade = IDL.Record({
+    'result' : Result,
+    'canister_id' : IDL.Principal,
+    'children' : Option,
+  });
+  const ChildrenUpgrade = IDL.Record({
+    'cascade' : IDL.Bool,
+    'children' : IDL.Vec(ChildUpgrade),
+    'started_at' : IDL.Nat64,
+    'wasm_hash' : IDL.Text,
+    'finished_at' : IDL.Opt(IDL.Nat64),
+  });
   const Inventory = IDL.Record({
     'contents' : IDL.Vec(IDL.Tuple(Resources, IDL.Nat64)),
     'size' : IDL.Nat32,
+    'escrow' : IDL.Vec(IDL.Tuple(Resources, IDL.Nat64)),
   });
   const ColonyInfo = IDL.Record({
     'player_count' : IDL.Nat64,
@@ -19,6 +86,7 @@ export const idlFactory = ({ IDL }) => {
     'coffers' : Inventory,
     'expeditions_count' : IDL.Nat64,
     'rewards_per_second' : IDL.Vec(IDL.Tuple(Resources, IDL.Nat8)),
+    'buildings' : IDL.Vec(BuildingProgress),
   });
   const ExpeditionStep = IDL.Variant({
     'Started' : IDL.Principal,
@@ -27,38 +95,257 @@ export const idlFactory = ({ IDL }) => {
     'Ready' : IDL.Null,
     'Proposed' : IDL.Null,
   });
+  const ExpeditionOutcome = IDL.Record({
+    'found' : IDL.Vec(IDL.Tuple(Resources, IDL.Nat64)),
+    'lost_percent' : IDL.Nat8,
+    'seed' : IDL.Nat32,
+    'rewards_per_second' : IDL.Vec(IDL.Tuple(Resources, IDL.Nat8)),
+  });
   const ExpeditionState = IDL.Record({
     'id' : IDL.Nat64,
     'members' : IDL.Vec(IDL.Principal),
+    'colony_canister' : IDL.Opt(IDL.Principal),
     'step' : ExpeditionStep,
     'resources_required' : IDL.Vec(IDL.Tuple(Resources, IDL.Nat64)),
+    'outcome' : IDL.Opt(ExpeditionOutcome),
     'proposed_at' : IDL.Nat64,
     'proposed_by' : IDL.Principal,
     'resources_pool' : Inventory,
+    'wasm_hash' : IDL.Opt(IDL.Text),
+  });
+  const LeaderboardKind = IDL.Variant({
+    'TaxesPaid' : IDL.Null,
+    'SecondsWorked' : IDL.Null,
+    'ResourcesEarned' : IDL.Null,
+    'ExpeditionsJoined' : IDL.Null,
+    'ExpeditionContributions' : IDL.Null,
+  });
+  const LeaderboardEntry = IDL.Record({
+    'principal' : IDL.Principal,
+    'rank' : IDL.Nat64,
+    'score' : IDL.Nat64,
+  });
+  const LeaderboardPage = IDL.Record({
+    'total' : IDL.Nat64,
+    'kind' : LeaderboardKind,
+    'entries' : IDL.Vec(LeaderboardEntry),
+  });
+  const OrderSide = IDL.Variant({ 'Ask' : IDL.Null, 'Bid' : IDL.Null });
+  const Order = IDL.Record({
+    'id' : IDL.Nat64,
+    'placed_at' : IDL.Nat64,
+    'owner' : IDL.Principal,
+    'base' : Resources,
+    'side' : OrderSide,
+    'quote' : Resources,
+    'remaining' : IDL.Nat64,
+    'price' : IDL.Nat64,
+    'amount' : IDL.Nat64,
+  });
+  const OrderBookDepth = IDL.Record({
+    'asks' : IDL.Vec(IDL.Tuple(IDL.Nat64, IDL.Nat64)),
+    'bids' : IDL.Vec(IDL.Tuple(IDL.Nat64, IDL.Nat64)),
+  });
+  const Result_5 = IDL.Variant({ 'Ok' : Inventory, 'Err' : PlayerError });
+  const CraftingJob = IDL.Record({ 'item' : Item, 'ready_at' : IDL.Nat64 });
+  const ItemState = IDL.Record({ 'item' : Item, 'durability' : IDL.Nat64 });
+  const PlayerItems = IDL.Record({
+    'crafting' : IDL.Vec(CraftingJob),
+    'items' : IDL.Vec(ItemState),
+  });
+  const Result_6 = IDL.Variant({ 'Ok' : PlayerItems, 'Err' : PlayerError });
+  const SkillLevel = IDL.Record({
+    'resource' : Resources,
+    'level' : IDL.Nat8,
+    'experience' : IDL.Nat64,
+    'next_level_at' : IDL.Opt(IDL.Nat64),
+    'yield_bonus_percent' : IDL.Nat64,
+  });
+  const PlayerProfile = IDL.Record({ 'skills' : IDL.Vec(SkillLevel) });
+  const Result_7 = IDL.Variant({ 'Ok' : PlayerProfile, 'Err' : PlayerError });
+  const PlayerStats = IDL.Record({
+    'seconds_worked' : IDL.Nat64,
+    'taxes_paid' : IDL.Nat64,
+    'expedition_contributions' : IDL.Nat64,
+    'expeditions_joined' : IDL.Nat64,
+    'resources_earned' : IDL.Nat64,
+  });
+  const Pool = IDL.Record({
+    'total_shares' : IDL.Nat64,
+    'colony_shares' : IDL.Nat64,
+    'reserve_a' : IDL.Nat64,
+    'reserve_b' : IDL.Nat64,
+  });
+  const PoolInfo = IDL.Record({
+    'a' : Resources,
+    'b' : Resources,
+    'pool' : Pool,
+  });
+  const Profile = IDL.Record({
+    'bio' : IDL.Text,
+    'avatar_url' : IDL.Opt(IDL.Text),
+    'display_name' : IDL.Opt(IDL.Text),
+    'joined_at' : IDL.Nat64,
+  });
+  const Result_8 = IDL.Variant({ 'Ok' : Profile, 'Err' : PlayerError });
+  const Trade = IDL.Record({
+    'id' : IDL.Nat64,
+    'executed_at' : IDL.Nat64,
+    'base' : Resources,
+    'maker_order_id' : IDL.Nat64,
+    'quote' : Resources,
+    'seller' : IDL.Principal,
+    'taker_order_id' : IDL.Nat64,
+    'buyer' : IDL.Principal,
+    'price' : IDL.Nat64,
+    'amount' : IDL.Nat64,
+  });
+  const Recipe = IDL.Record({
+    'item' : Item,
+    'boosts' : IDL.Vec(Resources),
+    'inputs' : IDL.Vec(IDL.Tuple(Resources, IDL.Nat64)),
+    'crafting_seconds' : IDL.Nat64,
+    'durability' : IDL.Nat64,
+    'bonus_percent' : IDL.Nat64,
   });
-  const Result_1 = IDL.Variant({
+  const Result_9 = IDL.Variant({ 'Ok' : IDL.Vec(IDL.Nat8), 'Err' : IDL.Text });
+  const Result_10 = IDL.Variant({ 'Ok' : IDL.Nat64, 'Err' : IDL.Text });
+  const Result_11 = IDL.Variant({
     'Ok' : IDL.Vec(IDL.Tuple(Resources, IDL.Nat64)),
-    'Err' : IDL.Text,
+    'Err' : PlayerError,
+  });
+  const UpkeepEstimate = IDL.Record({
+    'seconds_worked' : IDL.Nat64,
+    'consumed' : IDL.Vec(IDL.Tuple(Resources, IDL.Nat64)),
+    'supplied_seconds' : IDL.Opt(IDL.Nat64),
+    'upkeep_per_second' : IDL.Vec(IDL.Tuple(Resources, IDL.Nat64)),
+    'seconds_paid' : IDL.Nat64,
+  });
+  const Result_12 = IDL.Variant({ 'Ok' : UpkeepEstimate, 'Err' : PlayerError });
+  const WasmVersion = IDL.Record({
+    'active' : IDL.Bool,
+    'sha256' : IDL.Text,
+    'size' : IDL.Nat64,
+    'label' : IDL.Text,
+    'uploaded_at' : IDL.Nat64,
+    'uploaded_by' : IDL.Principal,
+  });
+  const NewOrder = IDL.Record({
+    'base' : Resources,
+    'side' : OrderSide,
+    'quote' : Resources,
+    'price' : IDL.Nat64,
+    'amount' : IDL.Nat64,
+  });
+  const Result_13 = IDL.Variant({ 'Ok' : IDL.Text, 'Err' : IDL.Text });
+  const ProfileArgs = IDL.Record({
+    'bio' : IDL.Text,
+    'avatar_url' : IDL.Text,
+    'display_name' : IDL.Text,
   });
+  const SkillCurve = IDL.Record({
+    'level_thresholds' : IDL.Vec(IDL.Nat64),
+    'bonus_percent_per_level' : IDL.Nat64,
+  });
+  const SnapshotInfo = IDL.Record({ 'sha256' : IDL.Text, 'size' : IDL.Nat64 });
+  const Result_14 = IDL.Variant({ 'Ok' : SnapshotInfo, 'Err' : IDL.Text });
+  const SwapArgs = IDL.Record({
+    'to' : Resources,
+    'min_amount_out' : IDL.Nat64,
+    'from' : Resources,
+    'amount_in' : IDL.Nat64,
+  });
+  const Result_15 = IDL.Variant({ 'Ok' : ChildrenUpgrade, 'Err' : IDL.Text });
   return IDL.Service({
+    'activateWasm' : IDL.Func([IDL.Text], [Result], []),
+    'addAdmin' : IDL.Func([IDL.Principal], [Result], []),
+    'addLiquidity' : IDL.Func([PoolDeposit], [Result_1], []),
     'addPlayerToWorld' : IDL.Func([], [Result], []),
+    'cancelOrder' : IDL.Func([IDL.Nat64], [Result], []),
+    'collectCrafted' : IDL.Func([], [Result_2], []),
+    'contributeToBuilding' : IDL.Func(
+        [Building, IDL.Vec(IDL.Tuple(Resources, IDL.Nat64))],
+        [Result_3],
+        [],
+      ),
+    'craft' : IDL.Func([Item], [Result_4], []),
     'demoAddResourcesToExpedition' : IDL.Func([], [Result], []),
     'expeditionNext' : IDL.Func([IDL.Nat64], [Result], []),
+    'finishSnapshotImport' : IDL.Func([], [Result], []),
+    'getChildrenUpgrade' : IDL.Func([], [IDL.Opt(ChildrenUpgrade)], ['query']),
     'getColonyInfo' : IDL.Func([], [ColonyInfo], ['query']),
     'getExpeditions' : IDL.Func(
         [],
         [IDL.Vec(IDL.Tuple(IDL.Nat64, ExpeditionState))],
         ['query'],
       ),
-    'getPlayerInventory' : IDL.Func([], [Inventory], ['query']),
+    'getLeaderboard' : IDL.Func(
+        [LeaderboardKind, IDL.Nat64, IDL.Nat64],
+        [LeaderboardPage],
+        ['query'],
+      ),
+    'getMyOrders' : IDL.Func([], [IDL.Vec(Order)], ['query']),
+    'getOrderBook' : IDL.Func(
+        [Resources, Resources],
+        [OrderBookDepth],
+        ['query'],
+      ),
+    'getPlayerInventory' : IDL.Func([], [Result_5], ['query']),
+    'getPlayerItems' : IDL.Func([], [Result_6], ['query']),
+    'getPlayerProfile' : IDL.Func([], [Result_7], ['query']),
+    'getPlayerStats' : IDL.Func([IDL.Principal], [PlayerStats], ['query']),
+    'getPools' : IDL.Func([], [IDL.Vec(PoolInfo)], ['query']),
+    'getProfile' : IDL.Func([IDL.Principal], [Result_8], ['query']),
+    'getRecentTrades' : IDL.Func(
+        [Resources, Resources],
+        [IDL.Vec(Trade)],
+        ['query'],
+      ),
+    'getRecipes' : IDL.Func([], [IDL.Vec(Recipe)], ['query']),
     'getRemoteColonies' : IDL.Func([], [IDL.Vec(IDL.Principal)], ['query']),
-    'getUnclaimedWork' : IDL.Func([], [Result_1], ['query']),
+    'getSnapshotChunk' : IDL.Func([IDL.Nat64], [Result_9], ['query']),
+    'getSwapQuote' : IDL.Func(
+        [Resources, Resources, IDL.Nat64],
+        [Result_10],
+        ['query'],
+      ),
+    'getUnclaimedWork' : IDL.Func([], [Result_11], ['query']),
+    'getUpkeepEstimate' : IDL.Func([], [Result_12], ['query']),
+    'getWasmVersions' : IDL.Func([], [IDL.Vec(WasmVersion)], ['query']),
     'greet' : IDL.Func([IDL.Text], [IDL.Text], ['query']),
+    'harvest' : IDL.Func([], [Result_11], []),
     'isPlayerHere' : IDL.Func([], [IDL.Bool], ['query']),
-    'joinExpedition' : IDL.Func([IDL.Nat64], [Result], []),
-    'startExpedition' : IDL.Func([], [Result], []),
-    'startWork' : IDL.Func([], [Result], []),
-    'stopWork' : IDL.Func([], [Result], []),
+    'joinExpedition' : IDL.Func([IDL.Nat64], [Result_4], []),
+    'placeOrder' : IDL.Func([NewOrder], [Result_1], []),
+    'removeLiquidity' : IDL.Func(
+        [Resources, Resources, IDL.Nat64],
+        [Result_11],
+        [],
+      ),
+    'removeWasm' : IDL.Func([IDL.Text], [Result], []),
+    'rollbackWasm' : IDL.Func([], [Result_13], []),
+    'seedPool' : IDL.Func([PoolDeposit], [Result_10], []),
+    'setMaxWorkSession' : IDL.Func([IDL.Nat64], [Result], []),
+    'setProfile' : IDL.Func([ProfileArgs], [Result_4], []),
+    'setSkillCurve' : IDL.Func([SkillCurve], [Result], []),
+    'setSwapFee' : IDL.Func([IDL.Nat16], [Result], []),
+    'setUpkeepRates' : IDL.Func(
+        [IDL.Vec(IDL.Tuple(Resources, IDL.Nat8))],
+        [Result],
+        [],
+      ),
+    'startExpedition' : IDL.Func([], [Result_4], []),
+    'startSnapshotExport' : IDL.Func([], [Result_14], []),
+    'startSnapshotImport' : IDL.Func([SnapshotInfo], [Result], []),
+    'startWork' : IDL.Func([], [Result_4], []),
+    'stopWork' : IDL.Func([], [Result_4], []),
+    'swap' : IDL.Func([SwapArgs], [Result_1], []),
+    'upgradeChildren' : IDL.Func(
+        [IDL.Text, IDL.Opt(IDL.Bool)],
+        [Result_15],
+        [],
+      ),
+    'uploadSnapshotChunk' : IDL.Func([IDL.Vec(IDL.Nat8)], [Result], []),
     'wasm_sha256' : IDL.Func([], [IDL.Text], ['query']),
   });
 };
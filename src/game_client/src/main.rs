@@ -0,0 +1,264 @@
+use std::path::{Path, PathBuf};
+
+use candid::parser::types::FuncMode;
+use candid::parser::typing::{check_prog, TypeEnv};
+use candid::types::{Function, Label, Type};
+use candid::{IDLArgs, IDLProg};
+
+use ic_agent::agent::http_transport::ReqwestHttpReplicaV2Transport;
+use ic_agent::identity::{AnonymousIdentity, BasicIdentity, Secp256k1Identity};
+use ic_agent::{Agent, Identity};
+use ic_types::Principal;
+
+use clap::{Parser, Subcommand};
+use url::Url;
+
+/// Every call is checked against the colony's interface, so the client always knows how to
+/// encode the arguments and decode the reply of any endpoint
+const COLONY_DID: &str =
+    include_str!("../../ic_quickstart_actor_model/ic_quickstart_actor_model.did");
+
+/// Plays against a colony canister. Run it once per player with a different --identity to script
+/// multi-player sessions.
+#[derive(Parser)]
+struct Opts {
+    url: String,
+    canister_id: Principal,
+    /// PEM file of the identity to play as, e.g. one created by `dfx identity new`. Plays as the
+    /// anonymous principal without it.
+    #[clap(long)]
+    identity: Option<PathBuf>,
+    /// Trust the root key the replica hands out. Only for replicas you run yourself; it's always
+    /// fetched from localhost and 127.0.0.1.
+    #[clap(long)]
+    fetch_root_key: bool,
+    #[clap(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Prints the principal of the identity
+    Whoami,
+    /// Lists the colony's endpoints with their arguments
+    Methods,
+    /// Calls any endpoint, with its arguments in candid text format, e.g. `call joinExpedition '(0)'`
+    Call {
+        method: String,
+        args: Option<String>,
+    },
+    AddPlayer,
+    ColonyInfo,
+    Inventory,
+    StartWork,
+    StopWork,
+    UnclaimedWork,
+    Harvest,
+    Expeditions,
+    StartExpedition,
+    JoinExpedition {
+        expedition_id: u64,
+    },
+    ExpeditionNext {
+        expedition_id: u64,
+    },
+}
+
+impl Command {
+    /// The endpoint behind the command and its arguments
+    fn endpoint(self) -> (String, String) {
+        let (method, args) = match self {
+            Command::Whoami | Command::Methods => unreachable!(),
+            Command::Call { method, args } => {
+                return (method, args.unwrap_or_else(|| "()".to_string()))
+            }
+            Command::AddPlayer => ("addPlayerToWorld", "()".to_string()),
+            Command::ColonyInfo => ("getColonyInfo", "()".to_string()),
+            Command::Inventory => ("getPlayerInventory", "()".to_string()),
+            Command::StartWork => ("startWork", "()".to_string()),
+            Command::StopWork => ("stopWork", "()".to_string()),
+            Command::UnclaimedWork => ("getUnclaimedWork", "()".to_string()),
+            Command::Harvest => ("harvest", "()".to_string()),
+            Command::Expeditions => ("getExpeditions", "()".to_string()),
+            Command::StartExpedition => ("startExpedition", "()".to_string()),
+            Command::JoinExpedition { expedition_id } => {
+                ("joinExpedition", format!("({})", expedition_id))
+            }
+            Command::ExpeditionNext { expedition_id } => {
+                ("expeditionNext", format!("({})", expedition_id))
+            }
+        };
+
+        (method.to_string(), args)
+    }
+}
+
+/// The colony's interface, parsed from its .did
+struct Colony {
+    env: TypeEnv,
+    service: Type,
+}
+
+impl Colony {
+    fn new() -> Self {
+        let prog: IDLProg = COLONY_DID
+            .parse()
+            .expect("Failed to parse the colony's .did");
+        let mut env = TypeEnv::new();
+        let service = check_prog(&mut env, &prog)
+            .expect("Failed to check the colony's .did")
+            .expect("The colony's .did has no service");
+
+        Self { env, service }
+    }
+
+    fn methods(&self) -> Vec<(String, Type)> {
+        self.env
+            .as_service(&self.service)
+            .expect("The colony's .did has no service")
+            .to_vec()
+    }
+
+    /// Checks the arguments against the method's signature and encodes them
+    fn encode_args(&self, method: &str, args: &str) -> Result<(Function, Vec<u8>), String> {
+        let func = self
+            .env
+            .get_method(&self.service, method)
+            .map_err(|_| format!("The colony has no method {}", method))?;
+
+        let args: IDLArgs = args
+            .parse()
+            .map_err(|e| format!("Failed to parse the arguments: {}", e))?;
+        let arg = args
+            .to_bytes_with_types(&self.env, &func.args)
+            .map_err(|e| format!("The arguments don't match {}: {}", func, e))?;
+
+        Ok((func.clone(), arg))
+    }
+
+    async fn call(
+        &self,
+        agent: &Agent,
+        canister_id: &Principal,
+        method: &str,
+        func: &Function,
+        arg: Vec<u8>,
+    ) -> Result<IDLArgs, String> {
+        let response = if func.modes.contains(&FuncMode::Query) {
+            agent.query(canister_id, method).with_arg(arg).call().await
+        } else {
+            agent
+                .update(canister_id, method)
+                .with_arg(arg)
+                .call_and_wait(waiter())
+                .await
+        }
+        .map_err(|e| e.to_string())?;
+
+        IDLArgs::from_bytes_with_types(&response, &self.env, &func.rets)
+            .map_err(|e| format!("Failed to decode the reply: {}", e))
+    }
+}
+
+/// Anything `dfx identity` creates: older versions use Ed25519 keys, newer ones secp256k1
+fn load_identity(pem: &Path) -> Result<Box<dyn Identity>, String> {
+    if let Ok(identity) = Secp256k1Identity::from_pem_file(pem) {
+        return Ok(Box::new(identity));
+    }
+
+    BasicIdentity::from_pem_file(pem)
+        .map(|identity| Box::new(identity) as Box<dyn Identity>)
+        .map_err(|e| format!("Failed to load the identity from {:?}: {}", pem, e))
+}
+
+fn is_local(url: &str) -> bool {
+    let host = Url::parse(url)
+        .ok()
+        .and_then(|url| url.host_str().map(str::to_string));
+
+    matches!(host.as_deref(), Some("localhost" | "127.0.0.1"))
+}
+
+fn waiter() -> garcon::Delay {
+    garcon::Delay::builder()
+        .throttle(std::time::Duration::from_millis(500))
+        .timeout(std::time::Duration::from_secs(60 * 5))
+        .build()
+}
+
+/// Whether the reply is a single `variant { Err = ... }`, like the ones of most updates
+fn is_err(reply: &IDLArgs) -> bool {
+    match reply.args.as_slice() {
+        [candid::parser::value::IDLValue::Variant(variant)] => {
+            variant.0.id == Label::Named("Err".to_string())
+        }
+        _ => false,
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    let opts = Opts::parse();
+    let colony = Colony::new();
+
+    let identity = match &opts.identity {
+        Some(pem) => load_identity(pem).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }),
+        None => Box::new(AnonymousIdentity),
+    };
+
+    match opts.command {
+        Command::Whoami => {
+            println!("{}", identity.sender().unwrap());
+            return;
+        }
+        Command::Methods => {
+            for (method, func) in colony.methods() {
+                println!("{} : {}", method, func);
+            }
+            return;
+        }
+        _ => {}
+    }
+
+    let (method, args) = opts.command.endpoint();
+    let (func, arg) = colony.encode_args(&method, &args).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    });
+
+    let transport = ReqwestHttpReplicaV2Transport::create(opts.url.clone())
+        .expect("Failed to create Reqwest transport");
+    let timeout = std::time::Duration::from_secs(60 * 5);
+
+    let agent = Agent::builder()
+        .with_transport(transport)
+        .with_boxed_identity(identity)
+        .with_ingress_expiry(Some(timeout))
+        .build()
+        .expect("Failed to build agent");
+
+    if opts.fetch_root_key || is_local(&opts.url) {
+        agent
+            .fetch_root_key()
+            .await
+            .expect("Couldn't fetch root key");
+    }
+
+    match colony
+        .call(&agent, &opts.canister_id, &method, &func, arg)
+        .await
+    {
+        Ok(reply) if is_err(&reply) => {
+            eprintln!("{}", reply);
+            std::process::exit(1);
+        }
+        Ok(reply) => println!("{}", reply),
+        Err(error) => {
+            eprintln!("{}", error);
+            std::process::exit(1);
+        }
+    }
+}
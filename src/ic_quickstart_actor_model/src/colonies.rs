@@ -0,0 +1,100 @@
+use candid::Principal;
+
+use crate::business_logic::ChildrenUpgrade;
+
+/// Calls to other colonies. A colony is an admin of the colonies it started, so it can hand them
+/// a wasm and have them upgrade their own children.
+pub trait Colonies {
+    /// Adds the wasm to the colony's registry and makes it the active one there
+    async fn load_wasm(
+        &self,
+        colony: Principal,
+        wasm: Vec<u8>,
+        label: Option<String>,
+    ) -> Result<String, String>;
+
+    async fn upgrade_children(
+        &self,
+        colony: Principal,
+        wasm_hash: String,
+        cascade: bool,
+    ) -> Result<ChildrenUpgrade, String>;
+}
+
+pub struct IcColonies;
+
+impl Colonies for IcColonies {
+    async fn load_wasm(
+        &self,
+        colony: Principal,
+        wasm: Vec<u8>,
+        label: Option<String>,
+    ) -> Result<String, String> {
+        let (result,): (Result<String, String>,) =
+            ic_cdk::api::call::call(colony, "load_wasm", (wasm, label))
+                .await
+                .map_err(|(code, msg)| {
+                    format!("An error happened during the call: {}: {}", code as u8, msg)
+                })?;
+
+        result
+    }
+
+    async fn upgrade_children(
+        &self,
+        colony: Principal,
+        wasm_hash: String,
+        cascade: bool,
+    ) -> Result<ChildrenUpgrade, String> {
+        let (result,): (Result<ChildrenUpgrade, String>,) =
+            ic_cdk::api::call::call(colony, "upgradeChildren", (wasm_hash, Some(cascade)))
+                .await
+                .map_err(|(code, msg)| {
+                    format!("An error happened during the call: {}: {}", code as u8, msg)
+                })?;
+
+        result
+    }
+}
+
+/// Colonies that accept every wasm and have no children of their own
+#[cfg(test)]
+#[derive(Default)]
+pub struct FakeColonies {
+    /// The colonies that were handed a wasm, and its hash
+    pub loaded: std::cell::RefCell<Vec<(Principal, String)>>,
+}
+
+#[cfg(test)]
+impl Colonies for FakeColonies {
+    async fn load_wasm(
+        &self,
+        colony: Principal,
+        wasm: Vec<u8>,
+        _label: Option<String>,
+    ) -> Result<String, String> {
+        let sha256 = crate::utils::sha256_hex(&wasm);
+        self.loaded.borrow_mut().push((colony, sha256.clone()));
+
+        Ok(sha256)
+    }
+
+    async fn upgrade_children(
+        &self,
+        colony: Principal,
+        wasm_hash: String,
+        cascade: bool,
+    ) -> Result<ChildrenUpgrade, String> {
+        if !self.loaded.borrow().contains(&(colony, wasm_hash.clone())) {
+            return Err(format!("There is no wasm {}", wasm_hash));
+        }
+
+        Ok(ChildrenUpgrade {
+            wasm_hash,
+            cascade,
+            started_at: 0,
+            finished_at: Some(0),
+            children: vec![],
+        })
+    }
+}
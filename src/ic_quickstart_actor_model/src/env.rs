@@ -1,30 +1,50 @@
 use candid::Principal;
 use rand::rngs::StdRng;
 use rand::{RngCore, SeedableRng};
+use sha2::{Digest, Sha256};
+#[cfg(test)]
+use std::{
+    cell::{Cell, RefCell},
+    collections::VecDeque,
+    rc::Rc,
+};
+
+pub use colony_state::env::{TimestampMillis, MILLIS_TO_SECONDS};
 
-pub const MILLIS_TO_SECONDS: u64 = 1_000_000_000;
-pub type TimestampMillis = u64;
 type Cycles = u128;
 
 pub trait Environment {
     fn now(&self) -> TimestampMillis;
     fn caller(&self) -> Principal;
     fn canister_id(&self) -> Principal;
-    fn random_u32(&mut self) -> u32;
+    /// Fails until the RNG has been seeded with secure entropy, see `add_entropy`
+    fn random_u32(&mut self) -> Result<u32, String>;
+    /// Mixes entropy from the management canister's `raw_rand` into the RNG
+    fn add_entropy(&mut self, entropy: &[u8]);
+    fn is_securely_seeded(&self) -> bool;
     fn cycles_balance(&self) -> Cycles;
     fn memory_used(&self) -> u64;
 }
 
+pub const NOT_SECURELY_SEEDED: &str =
+    "The canister is still waiting for secure randomness, please try again in a few seconds";
+
 pub struct CanisterEnv {
     rng: StdRng,
+    /// The time based seed is predictable, so the RNG isn't used until entropy from `raw_rand`
+    /// has been mixed in
+    securely_seeded: bool,
 }
 
 impl CanisterEnv {
     pub fn new() -> Self {
+        Self::seeded_from_time(ic_cdk::api::time())
+    }
+
+    fn seeded_from_time(now_millis: TimestampMillis) -> Self {
         CanisterEnv {
             // Seed the PRNG with the current time.
             rng: {
-                let now_millis = ic_cdk::api::time();
                 let mut seed = [0u8; 32];
                 seed[..8].copy_from_slice(&now_millis.to_be_bytes());
                 seed[8..16].copy_from_slice(&now_millis.to_be_bytes());
@@ -32,6 +52,7 @@ impl CanisterEnv {
                 seed[24..32].copy_from_slice(&now_millis.to_be_bytes());
                 StdRng::from_seed(seed)
             },
+            securely_seeded: false,
         }
     }
 }
@@ -55,8 +76,30 @@ impl Environment for CanisterEnv {
         ic_cdk::id()
     }
 
-    fn random_u32(&mut self) -> u32 {
-        self.rng.next_u32()
+    fn random_u32(&mut self) -> Result<u32, String> {
+        if !self.securely_seeded {
+            return Err(NOT_SECURELY_SEEDED.to_string());
+        }
+
+        Ok(self.rng.next_u32())
+    }
+
+    fn add_entropy(&mut self, entropy: &[u8]) {
+        // Hash the new entropy together with output of the current RNG, so entropy that was
+        // mixed in before isn't lost
+        let mut current = [0u8; 32];
+        self.rng.fill_bytes(&mut current);
+
+        let mut hasher = Sha256::new();
+        hasher.update(current);
+        hasher.update(entropy);
+
+        self.rng = StdRng::from_seed(hasher.finalize().into());
+        self.securely_seeded = true;
+    }
+
+    fn is_securely_seeded(&self) -> bool {
+        self.securely_seeded
     }
 
     fn cycles_balance(&self) -> Cycles {
@@ -78,18 +121,122 @@ impl Environment for CanisterEnv {
     }
 }
 
+/// A clock for tests. It is shared by all its clones, so a test can keep one to move the time
+/// forward after the environment has been handed to the `RuntimeState`.
+#[cfg(test)]
+#[derive(Clone, Default)]
+pub struct TestClock {
+    now: Rc<Cell<TimestampMillis>>,
+    /// Added to the time every time it is read, so no two reads see the same time
+    step: Rc<Cell<u64>>,
+}
+
+#[cfg(test)]
+impl TestClock {
+    pub fn new(now: TimestampMillis) -> Self {
+        let clock = Self::default();
+        clock.set(now);
+        clock
+    }
+
+    /// The current time, without advancing the clock
+    pub fn peek(&self) -> TimestampMillis {
+        self.now.get()
+    }
+
+    pub fn set(&self, now: TimestampMillis) {
+        self.now.set(now);
+    }
+
+    pub fn advance(&self, nanos: u64) {
+        self.now.set(self.now.get() + nanos);
+    }
+
+    pub fn advance_seconds(&self, seconds: u64) {
+        self.advance(seconds * MILLIS_TO_SECONDS);
+    }
+
+    /// Makes every read of the time move the clock forward by `nanos`. 0 stops the clock.
+    pub fn auto_advance(&self, nanos: u64) {
+        self.step.set(nanos);
+    }
+
+    fn read(&self) -> TimestampMillis {
+        let now = self.now.get();
+        self.advance(self.step.get());
+        now
+    }
+}
+
+/// Randomness for tests. Scripted values are handed out first, in order, and after that the
+/// values come from a PRNG with a fixed seed, so every run of a test sees the same numbers.
+/// Clones share their state, like `TestClock`.
+#[cfg(test)]
+#[derive(Clone)]
+pub struct TestRandom {
+    scripted: Rc<RefCell<VecDeque<u32>>>,
+    rng: Rc<RefCell<StdRng>>,
+}
+
+#[cfg(test)]
+impl TestRandom {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            scripted: Default::default(),
+            rng: Rc::new(RefCell::new(StdRng::seed_from_u64(seed))),
+        }
+    }
+
+    /// Queues values to be returned by the next calls to `random_u32`
+    pub fn script(&self, values: &[u32]) {
+        self.scripted.borrow_mut().extend(values);
+    }
+
+    fn next_u32(&self) -> u32 {
+        let scripted = self.scripted.borrow_mut().pop_front();
+        scripted.unwrap_or_else(|| self.rng.borrow_mut().next_u32())
+    }
+
+    /// Restarts the PRNG from the entropy, so tests that reseed stay reproducible
+    fn reseed(&self, entropy: &[u8]) {
+        let mut seed = [0u8; 32];
+        seed.copy_from_slice(&Sha256::digest(entropy));
+        *self.rng.borrow_mut() = StdRng::from_seed(seed);
+    }
+}
+
+#[cfg(test)]
 pub struct TestEnv {
-    pub now: u64,
+    pub clock: TestClock,
     pub caller: Principal,
     pub canister_id: Principal,
-    pub random_u32: u32,
+    pub random: TestRandom,
+    /// Starts out true, so only tests about seeding have to care about it
+    pub securely_seeded: bool,
     pub cycles_balance: Cycles,
     pub memory_used: u64,
 }
 
+#[cfg(test)]
+impl TestEnv {
+    /// Anonymous caller and canister at time 0, with randomness seeded from `seed`
+    pub fn new(seed: u64) -> Self {
+        Self {
+            clock: TestClock::new(0),
+            caller: Principal::anonymous(),
+            canister_id: Principal::anonymous(),
+            random: TestRandom::new(seed),
+            securely_seeded: true,
+            cycles_balance: 0,
+            memory_used: 0,
+        }
+    }
+}
+
+#[cfg(test)]
 impl Environment for TestEnv {
-    fn now(&self) -> u64 {
-        self.now
+    fn now(&self) -> TimestampMillis {
+        self.clock.read()
     }
 
     fn caller(&self) -> Principal {
@@ -100,8 +247,21 @@ impl Environment for TestEnv {
         self.canister_id
     }
 
-    fn random_u32(&mut self) -> u32 {
-        self.random_u32
+    fn random_u32(&mut self) -> Result<u32, String> {
+        if !self.securely_seeded {
+            return Err(NOT_SECURELY_SEEDED.to_string());
+        }
+
+        Ok(self.random.next_u32())
+    }
+
+    fn add_entropy(&mut self, entropy: &[u8]) {
+        self.random.reseed(entropy);
+        self.securely_seeded = true;
+    }
+
+    fn is_securely_seeded(&self) -> bool {
+        self.securely_seeded
     }
 
     fn cycles_balance(&self) -> Cycles {
@@ -128,8 +288,14 @@ impl Environment for EmptyEnv {
         Principal::anonymous()
     }
 
-    fn random_u32(&mut self) -> u32 {
-        0
+    fn random_u32(&mut self) -> Result<u32, String> {
+        Err(NOT_SECURELY_SEEDED.to_string())
+    }
+
+    fn add_entropy(&mut self, _entropy: &[u8]) {}
+
+    fn is_securely_seeded(&self) -> bool {
+        false
     }
 
     fn cycles_balance(&self) -> Cycles {
@@ -140,3 +306,55 @@ impl Environment for EmptyEnv {
         0
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clock_advances() {
+        let env = TestEnv::new(0);
+        let clock = env.clock.clone();
+
+        clock.advance_seconds(2);
+        assert_eq!(env.now(), 2 * MILLIS_TO_SECONDS);
+        assert_eq!(env.now(), 2 * MILLIS_TO_SECONDS);
+
+        clock.auto_advance(10);
+        assert_eq!(env.now(), 2 * MILLIS_TO_SECONDS);
+        assert_eq!(env.now(), 2 * MILLIS_TO_SECONDS + 10);
+        assert_eq!(clock.peek(), 2 * MILLIS_TO_SECONDS + 20);
+    }
+
+    #[test]
+    fn test_randomness_is_scripted_then_seeded() {
+        let mut env = TestEnv::new(42);
+        let mut same_seed = TestEnv::new(42);
+
+        env.random.script(&[7, 8]);
+        assert_eq!(env.random_u32(), Ok(7));
+        assert_eq!(env.random_u32(), Ok(8));
+
+        let values: Vec<u32> = (0..5).map(|_| env.random_u32().unwrap()).collect();
+        let expected: Vec<u32> = (0..5).map(|_| same_seed.random_u32().unwrap()).collect();
+        assert_eq!(values, expected);
+    }
+
+    #[test]
+    fn test_canister_env_waits_for_entropy() {
+        let mut env = CanisterEnv::seeded_from_time(1_000);
+        let mut same_time = CanisterEnv::seeded_from_time(1_000);
+
+        assert!(!env.is_securely_seeded());
+        assert_eq!(env.random_u32(), Err(NOT_SECURELY_SEEDED.to_string()));
+
+        env.add_entropy(&[1; 32]);
+        same_time.add_entropy(&[2; 32]);
+        assert!(env.is_securely_seeded());
+
+        // Knowing the time the canister started isn't enough to predict the numbers anymore
+        let values: Vec<u32> = (0..5).map(|_| env.random_u32().unwrap()).collect();
+        let other: Vec<u32> = (0..5).map(|_| same_time.random_u32().unwrap()).collect();
+        assert_ne!(values, other);
+    }
+}
@@ -1,15 +1,25 @@
-mod business_logic;
+mod colonies;
 mod env;
 mod lifetime;
+mod management;
+mod snapshots;
 
+use crate::colonies::{Colonies, IcColonies};
 use crate::env::{CanisterEnv, EmptyEnv, Environment};
+use crate::lifetime::ReseedSchedule;
+use crate::management::{IcManagementCanister, InstallMode, ManagementCanister};
+use crate::snapshots::{SnapshotInfo, Snapshots};
 use business_logic::{
-    BusinessState, ColonyState, ExpeditionState, ExpeditionStep, Inventory, PlayerStatus,
-    Resources, SystemSettings,
+    Building, BuildingProgress, ChildUpgrade, ChildrenUpgrade, ColonyState, ExpeditionState,
+    ExpeditionStep, Inventory, Item, LeaderboardKind, LeaderboardPage, NewOrder, Order,
+    OrderBookDepth, PlayerError, PlayerItems, PlayerProfile, PlayerStats, PlayerStatus,
+    PoolDeposit, PoolInfo, Profile, ProfileArgs, Recipe, Resources, SkillCurve, SwapArgs, Trade,
+    UpkeepEstimate, WasmVersion,
 };
-use candid::{candid_method, CandidType, Encode, Nat, Principal};
+use candid::{candid_method, CandidType, Encode, Principal};
+use colony_state::{business_logic, stable_state, utils, Data};
+use futures_util::future::join_all;
 
-use env::MILLIS_TO_SECONDS;
 use ic_cdk_macros::*;
 use serde::Deserialize;
 
@@ -18,8 +28,6 @@ use std::{
     collections::HashMap,
 };
 
-use sha2::{Digest, Sha256};
-
 thread_local! {
     static RUNTIME_STATE: RefCell<RuntimeState> = RefCell::default();
 }
@@ -27,6 +35,8 @@ thread_local! {
 struct RuntimeState {
     pub env: Box<dyn Environment>,
     pub data: Data,
+    pub reseed: ReseedSchedule,
+    pub snapshots: Snapshots,
 }
 
 impl Default for RuntimeState {
@@ -34,16 +44,12 @@ impl Default for RuntimeState {
         RuntimeState {
             env: Box::new(EmptyEnv {}),
             data: Data::default(),
+            reseed: Default::default(),
+            snapshots: Default::default(),
         }
     }
 }
 
-#[derive(CandidType, Default, Deserialize)]
-struct Data {
-    business_state: BusinessState,
-    system_settings: SystemSettings,
-}
-
 #[derive(CandidType, Deserialize)]
 struct ColonyInfo {
     canister_id: Principal,
@@ -53,6 +59,7 @@ struct ColonyInfo {
     coffers: Inventory,
     player_count: usize,
     expeditions_count: u64,
+    buildings: Vec<BuildingProgress>,
 }
 
 #[candid_method(query, rename = "getColonyInfo")]
@@ -75,40 +82,42 @@ fn get_colony_info_impl(runtime_state: Ref<RuntimeState>) -> ColonyInfo {
         coffers: runtime_state.data.business_state.colony.coffers.clone(),
         player_count: runtime_state.data.business_state.player.len(),
         expeditions_count: runtime_state.data.business_state.expeditions_count,
+        buildings: runtime_state.data.business_state.all_building_progress(),
     }
 }
 
 #[candid_method(query, rename = "getPlayerInventory")]
 #[query(name = "getPlayerInventory")]
-fn get_player_inventory() -> Inventory {
+fn get_player_inventory() -> Result<Inventory, PlayerError> {
     RUNTIME_STATE.with(|state| get_player_inventory_impl(state.borrow()))
 }
 
-fn get_player_inventory_impl(runtime_state: Ref<RuntimeState>) -> Inventory {
+fn get_player_inventory_impl(runtime_state: Ref<RuntimeState>) -> Result<Inventory, PlayerError> {
     runtime_state
         .data
         .business_state
         .player
         .get(&runtime_state.env.caller())
-        .unwrap()
-        .get_inventory()
+        .map(|p| p.get_inventory())
+        .ok_or(PlayerError::NotRegistered)
 }
 
 #[candid_method(update, rename = "startWork")]
 #[update(name = "startWork")]
-fn start_work() -> Result<(), String> {
+fn start_work() -> Result<(), PlayerError> {
     RUNTIME_STATE.with(|state| start_work_impl(&mut state.borrow_mut()))
 }
 
-fn start_work_impl(runtime_state: &mut RuntimeState) -> Result<(), String> {
-    match runtime_state
+fn start_work_impl(runtime_state: &mut RuntimeState) -> Result<(), PlayerError> {
+    let status = runtime_state
         .data
         .business_state
         .player
         .get(&runtime_state.env.caller())
-        .expect("Player not found in this world")
-        .get_status()
-    {
+        .ok_or(PlayerError::NotRegistered)?
+        .get_status();
+
+    match status {
         PlayerStatus::Idle => runtime_state.data.business_state.work_set(
             runtime_state.env.caller(),
             None,
@@ -120,23 +129,25 @@ fn start_work_impl(runtime_state: &mut RuntimeState) -> Result<(), String> {
 
         PlayerStatus::Traveling => Err("Cannot start work when traveling".to_string()),
     }
+    .map_err(PlayerError::Other)
 }
 
 #[candid_method(update, rename = "stopWork")]
 #[update(name = "stopWork")]
-fn stop_work() -> Result<(), String> {
+fn stop_work() -> Result<(), PlayerError> {
     RUNTIME_STATE.with(|state| stop_work_impl(&mut state.borrow_mut()))
 }
 
-fn stop_work_impl(runtime_state: &mut RuntimeState) -> Result<(), String> {
-    match runtime_state
+fn stop_work_impl(runtime_state: &mut RuntimeState) -> Result<(), PlayerError> {
+    let status = runtime_state
         .data
         .business_state
         .player
         .get(&runtime_state.env.caller())
-        .expect("Player not found in this world")
-        .get_status()
-    {
+        .ok_or(PlayerError::NotRegistered)?
+        .get_status();
+
+    match status {
         PlayerStatus::WorkingAll(_) | PlayerStatus::WorkingFocused(_, _) => runtime_state
             .data
             .business_state
@@ -146,38 +157,167 @@ fn stop_work_impl(runtime_state: &mut RuntimeState) -> Result<(), String> {
             Err("Player is currently not working".to_string())
         }
     }
+    .map_err(PlayerError::Other)
 }
 
 #[candid_method(query, rename = "getUnclaimedWork")]
 #[query(name = "getUnclaimedWork")]
-fn get_unclaimed_work() -> Result<Vec<(Resources, u64)>, String> {
+fn get_unclaimed_work() -> Result<Vec<(Resources, u64)>, PlayerError> {
     RUNTIME_STATE.with(|state| get_unclaimed_work_impl(state.borrow()))
 }
 
 fn get_unclaimed_work_impl(
     runtime_state: Ref<RuntimeState>,
-) -> Result<Vec<(Resources, u64)>, String> {
-    let seconds_elapsed;
+) -> Result<Vec<(Resources, u64)>, PlayerError> {
+    let business_state = &runtime_state.data.business_state;
+    let caller = runtime_state.env.caller();
+    business_state.check_registered(&caller)?;
+
+    let seconds_elapsed = business_state.seconds_worked(&caller, runtime_state.env.now())?;
+    let seconds_paid = business_state.upkeep_paid_seconds(&caller, seconds_elapsed)?;
+    business_state
+        .player_unclaimed(&caller, seconds_paid)
+        .map_err(PlayerError::Other)
+}
+
+#[candid_method(update, rename = "harvest")]
+#[update(name = "harvest")]
+fn harvest() -> Result<Vec<(Resources, u64)>, PlayerError> {
+    RUNTIME_STATE.with(|state| harvest_impl(&mut state.borrow_mut()))
+}
 
-    match runtime_state
+fn harvest_impl(runtime_state: &mut RuntimeState) -> Result<Vec<(Resources, u64)>, PlayerError> {
+    runtime_state
         .data
         .business_state
-        .player
-        .get(&runtime_state.env.caller())
-        .expect("Player not found in this world")
-        .get_status()
-    {
-        PlayerStatus::WorkingAll(working_since)
-        | PlayerStatus::WorkingFocused(working_since, _) => {
-            seconds_elapsed = (runtime_state.env.now() - working_since) / MILLIS_TO_SECONDS
-        }
-        _ => return Err("The player is not currently working".to_string()),
-    };
+        .check_registered(&runtime_state.env.caller())?;
+
+    runtime_state
+        .data
+        .business_state
+        .harvest(runtime_state.env.caller(), runtime_state.env.now())
+        .map_err(PlayerError::Other)
+}
+
+#[candid_method(update, rename = "setMaxWorkSession")]
+#[update(name = "setMaxWorkSession")]
+fn set_max_work_session(seconds: u64) -> Result<(), String> {
+    RUNTIME_STATE.with(|state| set_max_work_session_impl(&mut state.borrow_mut(), seconds))
+}
+
+fn set_max_work_session_impl(runtime_state: &mut RuntimeState, seconds: u64) -> Result<(), String> {
+    runtime_state
+        .data
+        .system_settings
+        .check_admin(&runtime_state.env.caller())?;
+
+    runtime_state
+        .data
+        .business_state
+        .set_max_work_session(seconds)
+}
+
+#[candid_method(query, rename = "getUpkeepEstimate")]
+#[query(name = "getUpkeepEstimate")]
+fn get_upkeep_estimate() -> Result<UpkeepEstimate, PlayerError> {
+    RUNTIME_STATE.with(|state| get_upkeep_estimate_impl(state.borrow()))
+}
+
+fn get_upkeep_estimate_impl(
+    runtime_state: Ref<RuntimeState>,
+) -> Result<UpkeepEstimate, PlayerError> {
+    runtime_state
+        .data
+        .business_state
+        .check_registered(&runtime_state.env.caller())?;
+
+    runtime_state
+        .data
+        .business_state
+        .upkeep_estimate(&runtime_state.env.caller(), runtime_state.env.now())
+        .map_err(PlayerError::Other)
+}
+
+#[candid_method(update, rename = "setUpkeepRates")]
+#[update(name = "setUpkeepRates")]
+fn set_upkeep_rates(rates: HashMap<Resources, u8>) -> Result<(), String> {
+    RUNTIME_STATE.with(|state| set_upkeep_rates_impl(&mut state.borrow_mut(), rates))
+}
+
+fn set_upkeep_rates_impl(
+    runtime_state: &mut RuntimeState,
+    rates: HashMap<Resources, u8>,
+) -> Result<(), String> {
+    runtime_state
+        .data
+        .system_settings
+        .check_admin(&runtime_state.env.caller())?;
+
+    runtime_state.data.business_state.set_upkeep_rates(rates);
+
+    Ok(())
+}
+
+#[candid_method(query, rename = "getPlayerProfile")]
+#[query(name = "getPlayerProfile")]
+fn get_player_profile() -> Result<PlayerProfile, PlayerError> {
+    RUNTIME_STATE.with(|state| get_player_profile_impl(state.borrow()))
+}
+
+fn get_player_profile_impl(runtime_state: Ref<RuntimeState>) -> Result<PlayerProfile, PlayerError> {
+    runtime_state
+        .data
+        .business_state
+        .check_registered(&runtime_state.env.caller())?;
+
+    runtime_state
+        .data
+        .business_state
+        .player_profile(&runtime_state.env.caller())
+        .map_err(PlayerError::Other)
+}
+
+#[candid_method(update, rename = "setSkillCurve")]
+#[update(name = "setSkillCurve")]
+fn set_skill_curve(curve: SkillCurve) -> Result<(), String> {
+    RUNTIME_STATE.with(|state| set_skill_curve_impl(&mut state.borrow_mut(), curve))
+}
 
-    Ok(runtime_state
+fn set_skill_curve_impl(runtime_state: &mut RuntimeState, curve: SkillCurve) -> Result<(), String> {
+    runtime_state
+        .data
+        .system_settings
+        .check_admin(&runtime_state.env.caller())?;
+
+    runtime_state.data.business_state.set_skill_curve(curve)
+}
+
+#[candid_method(query, rename = "getLeaderboard")]
+#[query(name = "getLeaderboard")]
+fn get_leaderboard(kind: LeaderboardKind, offset: u64, limit: u64) -> LeaderboardPage {
+    RUNTIME_STATE.with(|state| get_leaderboard_impl(state.borrow(), kind, offset, limit))
+}
+
+fn get_leaderboard_impl(
+    runtime_state: Ref<RuntimeState>,
+    kind: LeaderboardKind,
+    offset: u64,
+    limit: u64,
+) -> LeaderboardPage {
+    runtime_state
         .data
         .business_state
-        .available_unclaimed(seconds_elapsed))
+        .leaderboard(kind, offset, limit)
+}
+
+#[candid_method(query, rename = "getPlayerStats")]
+#[query(name = "getPlayerStats")]
+fn get_player_stats(principal: Principal) -> PlayerStats {
+    RUNTIME_STATE.with(|state| get_player_stats_impl(state.borrow(), principal))
+}
+
+fn get_player_stats_impl(runtime_state: Ref<RuntimeState>, principal: Principal) -> PlayerStats {
+    runtime_state.data.business_state.player_stats(&principal)
 }
 
 #[candid_method(query, rename = "getExpeditions")]
@@ -192,31 +332,43 @@ fn get_expeditions_impl(runtime_state: Ref<RuntimeState>) -> HashMap<u64, Expedi
 
 #[candid_method(update, rename = "startExpedition")]
 #[update(name = "startExpedition")]
-fn start_expedition() -> Result<(), String> {
+fn start_expedition() -> Result<(), PlayerError> {
     RUNTIME_STATE.with(|state| start_expedition_impl(&mut state.borrow_mut()))
 }
 
-fn start_expedition_impl(runtime_state: &mut RuntimeState) -> Result<(), String> {
+fn start_expedition_impl(runtime_state: &mut RuntimeState) -> Result<(), PlayerError> {
+    runtime_state
+        .data
+        .business_state
+        .check_registered(&runtime_state.env.caller())?;
+
     runtime_state
         .data
         .business_state
         .propose_expedition(runtime_state.env.caller(), runtime_state.env.now())
+        .map_err(PlayerError::Other)
 }
 
 #[candid_method(update, rename = "joinExpedition")]
 #[update(name = "joinExpedition")]
-fn join_expedition(expedition_id: u64) -> Result<(), String> {
+fn join_expedition(expedition_id: u64) -> Result<(), PlayerError> {
     RUNTIME_STATE.with(|state| join_expedition_impl(&mut state.borrow_mut(), expedition_id))
 }
 
 fn join_expedition_impl(
     runtime_state: &mut RuntimeState,
     expedition_id: u64,
-) -> Result<(), String> {
+) -> Result<(), PlayerError> {
+    runtime_state
+        .data
+        .business_state
+        .check_registered(&runtime_state.env.caller())?;
+
     runtime_state
         .data
         .business_state
         .join_expedition(&runtime_state.env.caller(), expedition_id)
+        .map_err(PlayerError::Other)
 }
 
 #[candid_method(update, rename = "demoAddResourcesToExpedition")]
@@ -237,330 +389,676 @@ fn demo_add_res_impl(runtime_state: &mut RuntimeState) -> Result<(), String> {
     Ok(())
 }
 
-/// We can send arguments to the newly installed canister
-#[derive(CandidType, Deserialize, Debug)]
-struct CanisterInstallSendArgs {
-    colony_state: ColonyState,
+#[candid_method(update, rename = "placeOrder")]
+#[update(name = "placeOrder")]
+fn place_order(new_order: NewOrder) -> Result<u64, PlayerError> {
+    RUNTIME_STATE.with(|state| place_order_impl(&mut state.borrow_mut(), new_order))
 }
 
-#[candid_method(update, rename = "expeditionNext")]
-#[update(name = "expeditionNext")]
-async fn expedition_next(expedition_id: u64) -> Result<(), String> {
-    // RUNTIME_STATE.with(|state| expedition_next_impl(&mut state.borrow_mut(), expedition_id))
-
-    let now = RUNTIME_STATE.with(|state| state.borrow().env.now());
-    let self_canister_id = RUNTIME_STATE.with(|state| state.borrow().env.canister_id());
-
-    let current_step = RUNTIME_STATE.with(|state| {
-        state
-            .borrow()
-            .data
-            .business_state
-            .expeditions
-            .get(&expedition_id)
-            .expect("Can't find expedition")
-            .clone()
-            .get_step()
-    });
-
-    match current_step {
-        ExpeditionStep::Proposed => {
-            let success = RUNTIME_STATE.with(|state| {
-                let mut s = state.borrow_mut();
-                if s.data
-                    .business_state
-                    .expeditions
-                    .get(&expedition_id)
-                    .expect("Can't find expedition")
-                    .has_enough_resources()
-                {
-                    s.data
-                        .business_state
-                        .expeditions
-                        .get_mut(&expedition_id)
-                        .expect("Can't find expedition")
-                        .set_step(ExpeditionStep::Ready)
-                        .unwrap();
-                    true
-                } else {
-                    false
-                }
-            });
-
-            if success {
-                return Ok(());
-            } else {
-                return Err("Not enough resources to start the expedition".to_string());
-            }
-        }
-        ExpeditionStep::Ready => {
-            // First we set the step to starting, so we don't try to start the same expedition two times
-
-            RUNTIME_STATE.with(|state| {
-                state
-                    .borrow_mut()
-                    .data
-                    .business_state
-                    .expeditions
-                    .get_mut(&expedition_id)
-                    .expect("Can't find expedition")
-                    .set_step(ExpeditionStep::Starting(now))
-                    .unwrap();
-            });
-
-            // Async try to start the expedition
-            let canister_id = call_canister_create(self_canister_id).await;
-
-            ic_cdk::print(format!("Created canister {}", canister_id.to_string()));
-
-            let canister_wasm =
-                RUNTIME_STATE.with(|state| state.borrow().data.business_state.wasm_store.clone());
+fn place_order_impl(
+    runtime_state: &mut RuntimeState,
+    new_order: NewOrder,
+) -> Result<u64, PlayerError> {
+    runtime_state
+        .data
+        .business_state
+        .check_registered(&runtime_state.env.caller())?;
 
-            let coffers = RUNTIME_STATE.with(|state| {
-                state
-                    .borrow()
-                    .data
-                    .business_state
-                    .expeditions
-                    .get(&expedition_id)
-                    .expect("Can't find expedition")
-                    .resources_pool
-                    .clone()
-            });
+    runtime_state
+        .data
+        .business_state
+        .place_order(
+            runtime_state.env.caller(),
+            new_order,
+            runtime_state.env.now(),
+        )
+        .map_err(PlayerError::Other)
+}
 
-            let canister_install_args = Encode!(&CanisterInstallSendArgs {
-                colony_state: ColonyState {
-                    generation: 1,
-                    taxes_percent: 5,
-                    global_resources_multiplier: 1,
-                    rewards_per_second: HashMap::from([
-                        (Resources::Wood, 100),
-                        (Resources::Stone, 100),
-                        (Resources::Food, 100),
-                        (Resources::Water, 100),
-                        (Resources::Gold, 100),
-                    ]),
-                    coffers,
-                },
-            })
-            .unwrap();
+#[candid_method(update, rename = "cancelOrder")]
+#[update(name = "cancelOrder")]
+fn cancel_order(order_id: u64) -> Result<(), String> {
+    RUNTIME_STATE.with(|state| cancel_order_impl(&mut state.borrow_mut(), order_id))
+}
 
-            let result =
-                call_canister_install(&canister_id, canister_install_args, canister_wasm).await;
+fn cancel_order_impl(runtime_state: &mut RuntimeState, order_id: u64) -> Result<(), String> {
+    runtime_state
+        .data
+        .business_state
+        .cancel_order(runtime_state.env.caller(), order_id)
+}
 
-            if result {
-                // If successful, we update the step again
-                RUNTIME_STATE.with(|state| {
-                    state
-                        .borrow_mut()
-                        .data
-                        .business_state
-                        .expeditions
-                        .get_mut(&expedition_id)
-                        .expect("Can't find expedition")
-                        .set_step(ExpeditionStep::Started(canister_id))
-                        .unwrap();
-                });
-            }
+#[candid_method(query, rename = "getOrderBook")]
+#[query(name = "getOrderBook")]
+fn get_order_book(base: Resources, quote: Resources) -> OrderBookDepth {
+    RUNTIME_STATE.with(|state| get_order_book_impl(state.borrow(), base, quote))
+}
 
-            return Ok(());
+fn get_order_book_impl(
+    runtime_state: Ref<RuntimeState>,
+    base: Resources,
+    quote: Resources,
+) -> OrderBookDepth {
+    runtime_state
+        .data
+        .business_state
+        .order_book_depth(base, quote)
+}
 
-            // If unsuccessful, we update the step to "::Ready" so we can try again.
-        }
-        ExpeditionStep::Starting(_timestamp) => {
-            // We can use the timestamp to implement some kind of timeout retry logic
+#[candid_method(query, rename = "getRecentTrades")]
+#[query(name = "getRecentTrades")]
+fn get_recent_trades(base: Resources, quote: Resources) -> Vec<Trade> {
+    RUNTIME_STATE.with(|state| get_recent_trades_impl(state.borrow(), base, quote))
+}
 
-            return Err("Retry logic not implemented".to_string());
-        }
-        ExpeditionStep::Started(canister_id) => {
-            // A new colony has been started
-            RUNTIME_STATE.with(|state| {
-                state
-                    .borrow_mut()
-                    .data
-                    .business_state
-                    .remote_colonies
-                    .push(canister_id)
-            });
+fn get_recent_trades_impl(
+    runtime_state: Ref<RuntimeState>,
+    base: Resources,
+    quote: Resources,
+) -> Vec<Trade> {
+    runtime_state.data.business_state.recent_trades(base, quote)
+}
 
-            RUNTIME_STATE.with(|state| {
-                state
-                    .borrow_mut()
-                    .data
-                    .business_state
-                    .expeditions
-                    .get_mut(&expedition_id)
-                    .expect("Can't find expedition")
-                    .set_step(ExpeditionStep::Done)
-                    .unwrap();
-            });
-            return Ok(());
-        }
-        ExpeditionStep::Done => {
-            return Err("This expedition cannot be changed anymore".to_string());
-        }
-    }
+#[candid_method(query, rename = "getMyOrders")]
+#[query(name = "getMyOrders")]
+fn get_my_orders() -> Vec<Order> {
+    RUNTIME_STATE.with(|state| get_my_orders_impl(state.borrow()))
+}
 
-    // Err("[expedition_next_impl] This should be unreachable".to_string())
+fn get_my_orders_impl(runtime_state: Ref<RuntimeState>) -> Vec<Order> {
+    runtime_state
+        .data
+        .business_state
+        .player_orders(runtime_state.env.caller())
 }
 
-async fn call_canister_create(self_canister_id: Principal) -> Principal {
-    ic_cdk::print("creating new colony...");
+#[candid_method(update, rename = "seedPool")]
+#[update(name = "seedPool")]
+fn seed_pool(deposit: PoolDeposit) -> Result<u64, String> {
+    RUNTIME_STATE.with(|state| seed_pool_impl(&mut state.borrow_mut(), deposit))
+}
 
-    #[derive(CandidType, Debug, Clone, Deserialize)]
-    pub struct CreateCanisterSettings {
-        pub controllers: Option<Vec<Principal>>,
-        pub compute_allocation: Option<Nat>,
-        pub memory_allocation: Option<Nat>,
-        pub freezing_threshold: Option<Nat>,
-    }
+fn seed_pool_impl(runtime_state: &mut RuntimeState, deposit: PoolDeposit) -> Result<u64, String> {
+    runtime_state
+        .data
+        .system_settings
+        .check_admin(&runtime_state.env.caller())?;
 
-    #[derive(CandidType, Clone, Deserialize)]
-    pub struct CreateCanisterArgs {
-        pub cycles: u64,
-        pub settings: CreateCanisterSettings,
-    }
+    runtime_state.data.business_state.seed_pool(deposit)
+}
 
-    #[derive(CandidType, Clone, Deserialize, Debug)]
-    pub struct CanisterIdRecord {
-        pub canister_id: Principal,
-    }
+#[candid_method(update, rename = "setSwapFee")]
+#[update(name = "setSwapFee")]
+fn set_swap_fee(basis_points: u16) -> Result<(), String> {
+    RUNTIME_STATE.with(|state| set_swap_fee_impl(&mut state.borrow_mut(), basis_points))
+}
 
-    // Add your own principal as a controller, in case manual control is needed
-    let create_args = CreateCanisterArgs {
-        cycles: 1_000_000_000_000,
-        settings: CreateCanisterSettings {
-            controllers: Some(vec![self_canister_id]),
-            compute_allocation: None,
-            memory_allocation: None,
-            freezing_threshold: None,
-        },
-    };
+fn set_swap_fee_impl(runtime_state: &mut RuntimeState, basis_points: u16) -> Result<(), String> {
+    runtime_state
+        .data
+        .system_settings
+        .check_admin(&runtime_state.env.caller())?;
 
-    #[derive(CandidType)]
-    struct In {
-        settings: Option<CreateCanisterSettings>,
-    }
+    runtime_state.data.business_state.set_swap_fee(basis_points)
+}
 
-    let in_arg = In {
-        settings: Some(create_args.settings),
-    };
+#[candid_method(update, rename = "swap")]
+#[update(name = "swap")]
+fn swap(args: SwapArgs) -> Result<u64, PlayerError> {
+    RUNTIME_STATE.with(|state| swap_impl(&mut state.borrow_mut(), args))
+}
 
-    let (create_result,): (CanisterIdRecord,) = match ic_cdk::api::call::call_with_payment(
-        Principal::management_canister(),
-        "create_canister",
-        (in_arg,),
-        create_args.cycles,
-    )
-    .await
-    {
-        Ok(x) => x,
-        Err((code, msg)) => {
-            ic_cdk::print(format!(
-                "An error happened during the call: {}: {}",
-                code as u8, msg
-            ));
-
-            (CanisterIdRecord {
-                canister_id: Principal::anonymous(),
-            },)
-        }
-    };
+fn swap_impl(runtime_state: &mut RuntimeState, args: SwapArgs) -> Result<u64, PlayerError> {
+    runtime_state
+        .data
+        .business_state
+        .check_registered(&runtime_state.env.caller())?;
 
-    ic_cdk::print(format!("{}", create_result.canister_id.to_text()));
-
-    create_result.canister_id
-}
-
-async fn call_canister_install(
-    canister_id: &Principal,
-    canister_install_args: Vec<u8>,
-    canister_wasm: Vec<u8>,
-) -> bool {
-    #[derive(CandidType, Deserialize)]
-    enum InstallMode {
-        #[serde(rename = "install")]
-        Install,
-        #[serde(rename = "reinstall")]
-        Reinstall,
-        #[serde(rename = "upgrade")]
-        Upgrade,
-    }
-
-    #[derive(CandidType, Deserialize)]
-    struct CanisterInstall {
-        mode: InstallMode,
-        canister_id: Principal,
-        #[serde(with = "serde_bytes")]
-        wasm_module: Vec<u8>,
-        #[serde(with = "serde_bytes")]
-        arg: Vec<u8>,
-    }
-
-    let install_config: CanisterInstall = CanisterInstall {
-        mode: InstallMode::Install,
-        canister_id: canister_id.clone(),
-        wasm_module: canister_wasm,
-        arg: canister_install_args,
-    };
+    runtime_state
+        .data
+        .business_state
+        .swap(runtime_state.env.caller(), args)
+        .map_err(PlayerError::Other)
+}
 
-    match ic_cdk::api::call::call(
-        Principal::management_canister(),
-        "install_code",
-        (install_config,),
-    )
-    .await
-    {
-        Ok(x) => x,
-        Err((code, msg)) => {
-            ic_cdk::print(format!(
-                "An error happened during the call: {}: {}",
-                code as u8, msg
-            ));
-            return false;
-        }
-    };
+#[candid_method(query, rename = "getSwapQuote")]
+#[query(name = "getSwapQuote")]
+fn get_swap_quote(from: Resources, to: Resources, amount_in: u64) -> Result<u64, String> {
+    RUNTIME_STATE.with(|state| get_swap_quote_impl(state.borrow(), from, to, amount_in))
+}
 
-    true
+fn get_swap_quote_impl(
+    runtime_state: Ref<RuntimeState>,
+    from: Resources,
+    to: Resources,
+    amount_in: u64,
+) -> Result<u64, String> {
+    runtime_state
+        .data
+        .business_state
+        .swap_quote(from, to, amount_in)
 }
 
-#[candid_method(query, rename = "isPlayerHere")]
-#[query(name = "isPlayerHere")]
-fn is_player_here() -> bool {
-    RUNTIME_STATE.with(|state| is_player_here_impl(state.borrow()))
+#[candid_method(update, rename = "addLiquidity")]
+#[update(name = "addLiquidity")]
+fn add_liquidity(deposit: PoolDeposit) -> Result<u64, PlayerError> {
+    RUNTIME_STATE.with(|state| add_liquidity_impl(&mut state.borrow_mut(), deposit))
 }
 
-fn is_player_here_impl(runtime_state: Ref<RuntimeState>) -> bool {
+fn add_liquidity_impl(
+    runtime_state: &mut RuntimeState,
+    deposit: PoolDeposit,
+) -> Result<u64, PlayerError> {
     runtime_state
         .data
         .business_state
-        .is_player_in_world(runtime_state.env.caller())
+        .check_registered(&runtime_state.env.caller())?;
+
+    runtime_state
+        .data
+        .business_state
+        .add_liquidity(runtime_state.env.caller(), deposit)
+        .map_err(PlayerError::Other)
 }
 
-#[candid_method(update, rename = "addPlayerToWorld")]
-#[update(name = "addPlayerToWorld")]
-fn add_player_to_world() -> Result<(), String> {
-    RUNTIME_STATE.with(|state| add_player_to_world_impl(&mut state.borrow_mut()))
+#[candid_method(update, rename = "removeLiquidity")]
+#[update(name = "removeLiquidity")]
+fn remove_liquidity(
+    a: Resources,
+    b: Resources,
+    shares: u64,
+) -> Result<Vec<(Resources, u64)>, PlayerError> {
+    RUNTIME_STATE.with(|state| remove_liquidity_impl(&mut state.borrow_mut(), a, b, shares))
 }
 
-fn add_player_to_world_impl(runtime_state: &mut RuntimeState) -> Result<(), String> {
+fn remove_liquidity_impl(
+    runtime_state: &mut RuntimeState,
+    a: Resources,
+    b: Resources,
+    shares: u64,
+) -> Result<Vec<(Resources, u64)>, PlayerError> {
+    runtime_state
+        .data
+        .business_state
+        .check_registered(&runtime_state.env.caller())?;
+
     runtime_state
         .data
         .business_state
-        .add_player(runtime_state.env.caller())
+        .remove_liquidity(runtime_state.env.caller(), a, b, shares)
+        .map_err(PlayerError::Other)
 }
 
-#[candid_method(query, rename = "getRemoteColonies")]
-#[query(name = "getRemoteColonies")]
-fn get_remote_colonies() -> Vec<Principal> {
-    RUNTIME_STATE.with(|state| get_remote_colonies_impl(state.borrow()))
+#[candid_method(query, rename = "getPools")]
+#[query(name = "getPools")]
+fn get_pools() -> Vec<PoolInfo> {
+    RUNTIME_STATE.with(|state| get_pools_impl(state.borrow()))
 }
 
-fn get_remote_colonies_impl(runtime_state: Ref<RuntimeState>) -> Vec<Principal> {
-    runtime_state.data.business_state.remote_colonies.clone()
+fn get_pools_impl(runtime_state: Ref<RuntimeState>) -> Vec<PoolInfo> {
+    runtime_state.data.business_state.pools()
 }
 
-#[candid_method(query)]
+#[candid_method(query, rename = "getRecipes")]
+#[query(name = "getRecipes")]
+fn get_recipes() -> Vec<Recipe> {
+    RUNTIME_STATE.with(|state| get_recipes_impl(state.borrow()))
+}
+
+fn get_recipes_impl(runtime_state: Ref<RuntimeState>) -> Vec<Recipe> {
+    runtime_state.data.business_state.recipes()
+}
+
+#[candid_method(update, rename = "craft")]
+#[update(name = "craft")]
+fn craft(item: Item) -> Result<(), PlayerError> {
+    RUNTIME_STATE.with(|state| craft_impl(&mut state.borrow_mut(), item))
+}
+
+fn craft_impl(runtime_state: &mut RuntimeState, item: Item) -> Result<(), PlayerError> {
+    runtime_state
+        .data
+        .business_state
+        .check_registered(&runtime_state.env.caller())?;
+
+    runtime_state
+        .data
+        .business_state
+        .craft(runtime_state.env.caller(), item, runtime_state.env.now())
+        .map_err(PlayerError::Other)
+}
+
+#[candid_method(update, rename = "collectCrafted")]
+#[update(name = "collectCrafted")]
+fn collect_crafted() -> Result<Vec<Item>, PlayerError> {
+    RUNTIME_STATE.with(|state| collect_crafted_impl(&mut state.borrow_mut()))
+}
+
+fn collect_crafted_impl(runtime_state: &mut RuntimeState) -> Result<Vec<Item>, PlayerError> {
+    runtime_state
+        .data
+        .business_state
+        .check_registered(&runtime_state.env.caller())?;
+
+    runtime_state
+        .data
+        .business_state
+        .collect_crafted(runtime_state.env.caller(), runtime_state.env.now())
+        .map_err(PlayerError::Other)
+}
+
+#[candid_method(query, rename = "getPlayerItems")]
+#[query(name = "getPlayerItems")]
+fn get_player_items() -> Result<PlayerItems, PlayerError> {
+    RUNTIME_STATE.with(|state| get_player_items_impl(state.borrow()))
+}
+
+fn get_player_items_impl(runtime_state: Ref<RuntimeState>) -> Result<PlayerItems, PlayerError> {
+    runtime_state
+        .data
+        .business_state
+        .check_registered(&runtime_state.env.caller())?;
+
+    runtime_state
+        .data
+        .business_state
+        .player_items(runtime_state.env.caller())
+        .map_err(PlayerError::Other)
+}
+
+#[candid_method(update, rename = "contributeToBuilding")]
+#[update(name = "contributeToBuilding")]
+fn contribute_to_building(
+    building: Building,
+    resources: HashMap<Resources, u64>,
+) -> Result<BuildingProgress, PlayerError> {
+    RUNTIME_STATE
+        .with(|state| contribute_to_building_impl(&mut state.borrow_mut(), building, resources))
+}
+
+fn contribute_to_building_impl(
+    runtime_state: &mut RuntimeState,
+    building: Building,
+    resources: HashMap<Resources, u64>,
+) -> Result<BuildingProgress, PlayerError> {
+    runtime_state
+        .data
+        .business_state
+        .check_registered(&runtime_state.env.caller())?;
+
+    runtime_state
+        .data
+        .business_state
+        .contribute_to_building(runtime_state.env.caller(), building, resources)
+        .map_err(PlayerError::Other)
+}
+
+#[candid_method(update, rename = "addAdmin")]
+#[update(name = "addAdmin")]
+fn add_admin(principal: Principal) -> Result<(), String> {
+    RUNTIME_STATE.with(|state| add_admin_impl(&mut state.borrow_mut(), principal))
+}
+
+fn add_admin_impl(runtime_state: &mut RuntimeState, principal: Principal) -> Result<(), String> {
+    let system_settings = &mut runtime_state.data.system_settings;
+    system_settings.check_admin(&runtime_state.env.caller())?;
+
+    if !system_settings.admins.contains(&principal) {
+        system_settings.admins.push(principal);
+    }
+
+    Ok(())
+}
+
+#[candid_method(update, rename = "startSnapshotExport")]
+#[update(name = "startSnapshotExport")]
+fn start_snapshot_export() -> Result<SnapshotInfo, String> {
+    RUNTIME_STATE.with(|state| start_snapshot_export_impl(&mut state.borrow_mut()))
+}
+
+fn start_snapshot_export_impl(runtime_state: &mut RuntimeState) -> Result<SnapshotInfo, String> {
+    runtime_state
+        .data
+        .system_settings
+        .check_admin(&runtime_state.env.caller())?;
+
+    runtime_state.snapshots.start_export(&runtime_state.data)
+}
+
+#[candid_method(query, rename = "getSnapshotChunk")]
+#[query(name = "getSnapshotChunk")]
+fn get_snapshot_chunk(offset: u64) -> Result<Vec<u8>, String> {
+    RUNTIME_STATE.with(|state| get_snapshot_chunk_impl(state.borrow(), offset))
+}
+
+fn get_snapshot_chunk_impl(
+    runtime_state: Ref<RuntimeState>,
+    offset: u64,
+) -> Result<Vec<u8>, String> {
+    runtime_state
+        .data
+        .system_settings
+        .check_admin(&runtime_state.env.caller())?;
+
+    runtime_state.snapshots.export_chunk(offset)
+}
+
+#[candid_method(update, rename = "startSnapshotImport")]
+#[update(name = "startSnapshotImport")]
+fn start_snapshot_import(expected: SnapshotInfo) -> Result<(), String> {
+    RUNTIME_STATE.with(|state| start_snapshot_import_impl(&mut state.borrow_mut(), expected))
+}
+
+fn start_snapshot_import_impl(
+    runtime_state: &mut RuntimeState,
+    expected: SnapshotInfo,
+) -> Result<(), String> {
+    runtime_state
+        .data
+        .system_settings
+        .check_admin(&runtime_state.env.caller())?;
+
+    runtime_state.snapshots.start_import(expected);
+
+    Ok(())
+}
+
+#[candid_method(update, rename = "uploadSnapshotChunk")]
+#[update(name = "uploadSnapshotChunk")]
+fn upload_snapshot_chunk(chunk: Vec<u8>) -> Result<(), String> {
+    RUNTIME_STATE.with(|state| upload_snapshot_chunk_impl(&mut state.borrow_mut(), chunk))
+}
+
+fn upload_snapshot_chunk_impl(
+    runtime_state: &mut RuntimeState,
+    chunk: Vec<u8>,
+) -> Result<(), String> {
+    runtime_state
+        .data
+        .system_settings
+        .check_admin(&runtime_state.env.caller())?;
+
+    runtime_state.snapshots.add_import_chunk(chunk)
+}
+
+/// Replaces the whole state with the uploaded snapshot. The caller stays an admin, even if the
+/// snapshot comes from a colony they didn't administer.
+#[candid_method(update, rename = "finishSnapshotImport")]
+#[update(name = "finishSnapshotImport")]
+fn finish_snapshot_import() -> Result<(), String> {
+    RUNTIME_STATE.with(|state| finish_snapshot_import_impl(&mut state.borrow_mut()))
+}
+
+fn finish_snapshot_import_impl(runtime_state: &mut RuntimeState) -> Result<(), String> {
+    let caller = runtime_state.env.caller();
+    runtime_state.data.system_settings.check_admin(&caller)?;
+
+    let mut data = runtime_state.snapshots.finish_import()?;
+    if !data.system_settings.admins.contains(&caller) {
+        data.system_settings.admins.push(caller);
+    }
+    runtime_state.data = data;
+
+    Ok(())
+}
+
+/// We can send arguments to the newly installed canister
+#[derive(CandidType, Deserialize, Debug)]
+struct CanisterInstallSendArgs {
+    colony_state: ColonyState,
+}
+
+#[candid_method(update, rename = "expeditionNext")]
+#[update(name = "expeditionNext")]
+async fn expedition_next(expedition_id: u64) -> Result<(), String> {
+    expedition_next_impl(&IcManagementCanister, expedition_id).await
+}
+
+fn set_expedition_step(expedition_id: u64, step: ExpeditionStep) -> Result<(), String> {
+    RUNTIME_STATE.with(|state| {
+        state
+            .borrow_mut()
+            .data
+            .business_state
+            .expeditions
+            .get_mut(&expedition_id)
+            .ok_or_else(|| "Can't find expedition".to_string())?
+            .set_step(step)
+    })
+}
+
+fn set_colony_canister(expedition_id: u64, canister_id: Principal) -> Result<(), String> {
+    RUNTIME_STATE.with(|state| {
+        state
+            .borrow_mut()
+            .data
+            .business_state
+            .expeditions
+            .get_mut(&expedition_id)
+            .ok_or_else(|| "Can't find expedition".to_string())?
+            .colony_canister = Some(canister_id);
+
+        Ok(())
+    })
+}
+
+async fn expedition_next_impl<M: ManagementCanister>(
+    management: &M,
+    expedition_id: u64,
+) -> Result<(), String> {
+    let now = RUNTIME_STATE.with(|state| state.borrow().env.now());
+    let self_canister_id = RUNTIME_STATE.with(|state| state.borrow().env.canister_id());
+
+    let expedition = RUNTIME_STATE.with(|state| {
+        state
+            .borrow()
+            .data
+            .business_state
+            .expeditions
+            .get(&expedition_id)
+            .cloned()
+            .ok_or_else(|| "Can't find expedition".to_string())
+    })?;
+
+    match expedition.clone().get_step() {
+        ExpeditionStep::Proposed => {
+            if expedition.has_enough_resources() {
+                set_expedition_step(expedition_id, ExpeditionStep::Ready)
+            } else {
+                Err("Not enough resources to start the expedition".to_string())
+            }
+        }
+        ExpeditionStep::Ready => {
+            let (wasm_hash, canister_wasm) = RUNTIME_STATE.with(|state| {
+                let state = state.borrow();
+                let registry = &state.data.business_state.wasm_registry;
+
+                registry
+                    .active()
+                    .map(|(sha256, module)| (sha256.to_string(), module.to_vec()))
+                    .ok_or_else(|| "There is no wasm to install the new colony with".to_string())
+            })?;
+
+            // Roll what happens on the way before anything else changes, so the expedition stays
+            // Ready if the canister hasn't been seeded with secure randomness yet. A retry keeps
+            // the outcome of the earlier try and doesn't need any randomness.
+            let outcome = match expedition.outcome.clone() {
+                Some(outcome) => outcome,
+                None => RUNTIME_STATE.with(|state| {
+                    let mut runtime_state = state.borrow_mut();
+                    let seed = runtime_state.env.random_u32()?;
+                    runtime_state
+                        .data
+                        .business_state
+                        .roll_expedition_outcome(expedition_id, seed)
+                })?,
+            };
+
+            // First we set the step to starting, so we don't try to start the same expedition two times
+            set_expedition_step(expedition_id, ExpeditionStep::Starting(now))?;
+
+            // Async try to start the expedition, in the canister of an earlier try if there is one
+            let canister_id = match expedition.colony_canister {
+                Some(canister_id) => canister_id,
+                None => match management.create_canister(self_canister_id).await {
+                    Ok(canister_id) => {
+                        set_colony_canister(expedition_id, canister_id)?;
+                        canister_id
+                    }
+                    Err(error) => {
+                        // Nothing was created, so the expedition can simply try again
+                        set_expedition_step(expedition_id, ExpeditionStep::Ready)?;
+                        return Err(format!("Could not create the new colony: {}", error));
+                    }
+                },
+            };
+
+            let canister_install_args = Encode!(&CanisterInstallSendArgs {
+                colony_state: ColonyState {
+                    generation: 1,
+                    taxes_percent: 5,
+                    global_resources_multiplier: 1,
+                    rewards_per_second: outcome.rewards_per_second.clone(),
+                    coffers: outcome.arrival_coffers(&expedition.resources_pool),
+                    ..Default::default()
+                },
+            })
+            .unwrap();
+
+            match management
+                .install_code(
+                    InstallMode::Install,
+                    canister_id,
+                    canister_wasm,
+                    canister_install_args,
+                )
+                .await
+            {
+                // If successful, we remember what the colony runs and update the step again
+                Ok(()) => {
+                    RUNTIME_STATE.with(|state| {
+                        if let Some(expedition) = state
+                            .borrow_mut()
+                            .data
+                            .business_state
+                            .expeditions
+                            .get_mut(&expedition_id)
+                        {
+                            expedition.wasm_hash = Some(wasm_hash);
+                        }
+                    });
+
+                    set_expedition_step(expedition_id, ExpeditionStep::Started(canister_id))
+                }
+                // If unsuccessful, we update the step to "::Ready" so we can try again. The
+                // created canister is kept for that.
+                Err(error) => {
+                    set_expedition_step(expedition_id, ExpeditionStep::Ready)?;
+                    Err(format!("Could not install the new colony: {}", error))
+                }
+            }
+        }
+        ExpeditionStep::Starting(_timestamp) => {
+            // We can use the timestamp to implement some kind of timeout retry logic
+
+            Err("Retry logic not implemented".to_string())
+        }
+        ExpeditionStep::Started(canister_id) => {
+            // A new colony has been started
+            RUNTIME_STATE.with(|state| {
+                state
+                    .borrow_mut()
+                    .data
+                    .business_state
+                    .remote_colonies
+                    .push(canister_id)
+            });
+
+            set_expedition_step(expedition_id, ExpeditionStep::Done)
+        }
+        ExpeditionStep::Done => Err("This expedition cannot be changed anymore".to_string()),
+    }
+}
+
+#[candid_method(query, rename = "isPlayerHere")]
+#[query(name = "isPlayerHere")]
+fn is_player_here() -> bool {
+    RUNTIME_STATE.with(|state| is_player_here_impl(state.borrow()))
+}
+
+fn is_player_here_impl(runtime_state: Ref<RuntimeState>) -> bool {
+    runtime_state
+        .data
+        .business_state
+        .is_player_in_world(runtime_state.env.caller())
+}
+
+#[candid_method(update, rename = "addPlayerToWorld")]
+#[update(name = "addPlayerToWorld")]
+fn add_player_to_world() -> Result<(), String> {
+    RUNTIME_STATE.with(|state| add_player_to_world_impl(&mut state.borrow_mut()))
+}
+
+fn add_player_to_world_impl(runtime_state: &mut RuntimeState) -> Result<(), String> {
+    let now = runtime_state.env.now();
+
+    runtime_state
+        .data
+        .business_state
+        .add_player(runtime_state.env.caller(), now)
+}
+
+#[candid_method(update, rename = "setProfile")]
+#[update(name = "setProfile")]
+fn set_profile(args: ProfileArgs) -> Result<(), PlayerError> {
+    RUNTIME_STATE.with(|state| set_profile_impl(&mut state.borrow_mut(), args))
+}
+
+fn set_profile_impl(
+    runtime_state: &mut RuntimeState,
+    args: ProfileArgs,
+) -> Result<(), PlayerError> {
+    runtime_state
+        .data
+        .business_state
+        .check_registered(&runtime_state.env.caller())?;
+
+    runtime_state
+        .data
+        .business_state
+        .set_profile(runtime_state.env.caller(), args)
+        .map_err(PlayerError::Other)
+}
+
+#[candid_method(query, rename = "getProfile")]
+#[query(name = "getProfile")]
+fn get_profile(principal: Principal) -> Result<Profile, PlayerError> {
+    RUNTIME_STATE.with(|state| get_profile_impl(state.borrow(), principal))
+}
+
+fn get_profile_impl(
+    runtime_state: Ref<RuntimeState>,
+    principal: Principal,
+) -> Result<Profile, PlayerError> {
+    let business_state = &runtime_state.data.business_state;
+    business_state.check_registered(&principal)?;
+
+    business_state
+        .profile(&principal)
+        .map_err(PlayerError::Other)
+}
+
+#[candid_method(query, rename = "getRemoteColonies")]
+#[query(name = "getRemoteColonies")]
+fn get_remote_colonies() -> Vec<Principal> {
+    RUNTIME_STATE.with(|state| get_remote_colonies_impl(state.borrow()))
+}
+
+fn get_remote_colonies_impl(runtime_state: Ref<RuntimeState>) -> Vec<Principal> {
+    runtime_state.data.business_state.remote_colonies.clone()
+}
+
+#[candid_method(query)]
 #[query]
 fn greet(name: String) -> String {
     let now = RUNTIME_STATE.with(|state| state.borrow().env.now());
@@ -571,27 +1069,248 @@ fn greet(name: String) -> String {
     )
 }
 
-#[update(name = "load_wasm")]
-fn load_wasm(wasm: Vec<u8>) -> bool {
-    ic_cdk::print(format!("Loaded wasm with length: {}", &wasm.len()));
+/// Every wasm module starts with these bytes
+const WASM_MAGIC: &[u8] = b"\0asm";
+/// Gzipped modules start with these, the IC unpacks them when installing
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+
+/// Adds a wasm to the registry and makes it the one new colonies are installed with
+#[update(name = "load_wasm")]
+fn load_wasm(wasm: Vec<u8>, label: Option<String>) -> Result<String, String> {
+    ic_cdk::print(format!("Loaded wasm with length: {}", &wasm.len()));
+
+    RUNTIME_STATE.with(|state| load_wasm_impl(&mut state.borrow_mut(), wasm, label))
+}
+
+fn load_wasm_impl(
+    runtime_state: &mut RuntimeState,
+    wasm: Vec<u8>,
+    label: Option<String>,
+) -> Result<String, String> {
+    let caller = runtime_state.env.caller();
+    runtime_state.data.system_settings.check_admin(&caller)?;
+
+    if !wasm.starts_with(WASM_MAGIC) && !wasm.starts_with(GZIP_MAGIC) {
+        return Err("This is neither a wasm module nor a gzipped one".to_string());
+    }
+
+    let registry = &mut runtime_state.data.business_state.wasm_registry;
+    let sha256 = registry.add(wasm, label, caller, runtime_state.env.now());
+    registry.activate(&sha256)?;
+
+    Ok(sha256)
+}
+
+/// The checksum of the wasm new colonies are installed with, empty if there is none
+#[candid_method(query, rename = "wasm_sha256")]
+#[query(name = "wasm_sha256")]
+fn wasm_sha256() -> String {
+    RUNTIME_STATE.with(|state| {
+        let state = state.borrow();
+        let active = state.data.business_state.wasm_registry.active();
+
+        active
+            .map(|(sha256, _)| sha256.to_string())
+            .unwrap_or_default()
+    })
+}
+
+#[candid_method(query, rename = "getWasmVersions")]
+#[query(name = "getWasmVersions")]
+fn get_wasm_versions() -> Vec<WasmVersion> {
+    RUNTIME_STATE.with(|state| get_wasm_versions_impl(state.borrow()))
+}
+
+fn get_wasm_versions_impl(runtime_state: Ref<RuntimeState>) -> Vec<WasmVersion> {
+    runtime_state.data.business_state.wasm_registry.versions()
+}
+
+#[candid_method(update, rename = "activateWasm")]
+#[update(name = "activateWasm")]
+fn activate_wasm(sha256: String) -> Result<(), String> {
+    RUNTIME_STATE.with(|state| activate_wasm_impl(&mut state.borrow_mut(), sha256))
+}
+
+fn activate_wasm_impl(runtime_state: &mut RuntimeState, sha256: String) -> Result<(), String> {
+    runtime_state
+        .data
+        .system_settings
+        .check_admin(&runtime_state.env.caller())?;
+
+    runtime_state
+        .data
+        .business_state
+        .wasm_registry
+        .activate(&sha256)
+}
+
+/// Makes the previously active wasm active again and returns its checksum
+#[candid_method(update, rename = "rollbackWasm")]
+#[update(name = "rollbackWasm")]
+fn rollback_wasm() -> Result<String, String> {
+    RUNTIME_STATE.with(|state| rollback_wasm_impl(&mut state.borrow_mut()))
+}
+
+fn rollback_wasm_impl(runtime_state: &mut RuntimeState) -> Result<String, String> {
+    runtime_state
+        .data
+        .system_settings
+        .check_admin(&runtime_state.env.caller())?;
 
-    RUNTIME_STATE.with(|state| state.borrow_mut().data.business_state.wasm_store = wasm);
+    runtime_state.data.business_state.wasm_registry.rollback()
+}
 
-    true
+/// Deletes a stored wasm that is neither active nor the one a rollback would go back to
+#[candid_method(update, rename = "removeWasm")]
+#[update(name = "removeWasm")]
+fn remove_wasm(sha256: String) -> Result<(), String> {
+    RUNTIME_STATE.with(|state| remove_wasm_impl(&mut state.borrow_mut(), sha256))
 }
 
-#[candid_method(query, rename = "wasm_sha256")]
-#[query(name = "wasm_sha256")]
-fn wasm_sha256() -> String {
+fn remove_wasm_impl(runtime_state: &mut RuntimeState, sha256: String) -> Result<(), String> {
+    runtime_state
+        .data
+        .system_settings
+        .check_admin(&runtime_state.env.caller())?;
+
+    runtime_state
+        .data
+        .business_state
+        .wasm_registry
+        .remove(&sha256)
+}
+
+/// How many children are upgraded at the same time
+const UPGRADE_BATCH_SIZE: usize = 10;
+
+/// Upgrades every colony this one started to the stored wasm `wasm_hash`, a batch at a time. With
+/// `cascade` each child also gets the wasm and upgrades its own children the same way. Only one
+/// upgrade runs at a time, unless the running one has gone unfinished for an hour.
+#[candid_method(update, rename = "upgradeChildren")]
+#[update(name = "upgradeChildren")]
+async fn upgrade_children(
+    wasm_hash: String,
+    cascade: Option<bool>,
+) -> Result<ChildrenUpgrade, String> {
+    upgrade_children_impl(
+        &IcManagementCanister,
+        &IcColonies,
+        wasm_hash,
+        cascade.unwrap_or(false),
+    )
+    .await
+}
+
+async fn upgrade_children_impl<M: ManagementCanister, C: Colonies>(
+    management: &M,
+    colonies: &C,
+    wasm_hash: String,
+    cascade: bool,
+) -> Result<ChildrenUpgrade, String> {
+    let (wasm, children, started_at) = RUNTIME_STATE.with(|state| {
+        let mut runtime_state = state.borrow_mut();
+        let caller = runtime_state.env.caller();
+        runtime_state.data.system_settings.check_admin(&caller)?;
+
+        let now = runtime_state.env.now();
+        let business_state = &mut runtime_state.data.business_state;
+        let wasm = business_state.start_children_upgrade(wasm_hash.clone(), cascade, now)?;
+
+        Ok::<_, String>((wasm, business_state.remote_colonies.clone(), now))
+    })?;
+
+    for batch in children.chunks(UPGRADE_BATCH_SIZE) {
+        let upgrades = batch
+            .iter()
+            .map(|child| upgrade_child(management, colonies, *child, &wasm, &wasm_hash, cascade));
+
+        // Record each batch as it's done, so getChildrenUpgrade shows how far along it is
+        for upgrade in join_all(upgrades).await {
+            RUNTIME_STATE.with(|state| {
+                state
+                    .borrow_mut()
+                    .data
+                    .business_state
+                    .record_child_upgrade(started_at, upgrade)
+            });
+        }
+    }
+
     RUNTIME_STATE.with(|state| {
-        let mut hasher = Sha256::new();
-        hasher.update(&state.borrow_mut().data.business_state.wasm_store);
-        let result = hasher.finalize();
+        let mut runtime_state = state.borrow_mut();
+        let now = runtime_state.env.now();
 
-        format!("{:x}", result)
+        runtime_state
+            .data
+            .business_state
+            .finish_children_upgrade(started_at, now)
+            .ok_or_else(|| "The upgrade of the children was replaced by a newer one".to_string())
     })
 }
 
+async fn upgrade_child<M: ManagementCanister, C: Colonies>(
+    management: &M,
+    colonies: &C,
+    child: Principal,
+    wasm: &[u8],
+    wasm_hash: &str,
+    cascade: bool,
+) -> ChildUpgrade {
+    let upgraded = management
+        .install_code(
+            InstallMode::Upgrade,
+            child,
+            wasm.to_vec(),
+            Encode!().unwrap(),
+        )
+        .await
+        .map_err(|error| format!("Could not upgrade the colony: {}", error));
+
+    if upgraded.is_err() || !cascade {
+        return ChildUpgrade {
+            canister_id: child,
+            result: upgraded,
+            children: None,
+        };
+    }
+
+    // The child can only upgrade its children to a wasm it has itself
+    let grandchildren = match colonies.load_wasm(child, wasm.to_vec(), None).await {
+        Ok(_) => {
+            colonies
+                .upgrade_children(child, wasm_hash.to_string(), true)
+                .await
+        }
+        Err(error) => Err(error),
+    };
+
+    match grandchildren {
+        Ok(grandchildren) => ChildUpgrade {
+            canister_id: child,
+            result: Ok(()),
+            children: Some(grandchildren),
+        },
+        Err(error) => ChildUpgrade {
+            canister_id: child,
+            result: Err(format!(
+                "The colony was upgraded, but not its children: {}",
+                error
+            )),
+            children: None,
+        },
+    }
+}
+
+#[candid_method(query, rename = "getChildrenUpgrade")]
+#[query(name = "getChildrenUpgrade")]
+fn get_children_upgrade() -> Option<ChildrenUpgrade> {
+    RUNTIME_STATE.with(|state| get_children_upgrade_impl(state.borrow()))
+}
+
+fn get_children_upgrade_impl(runtime_state: Ref<RuntimeState>) -> Option<ChildrenUpgrade> {
+    runtime_state.data.business_state.children_upgrade.clone()
+}
+
 // Auto export the candid interface
 candid::export_service!();
 
@@ -604,6 +1323,138 @@ fn __export_did_tmp_() -> String {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::business_logic::OrderSide;
+    use crate::colonies::FakeColonies;
+    use crate::env::{TestClock, TestEnv, TimestampMillis, MILLIS_TO_SECONDS, NOT_SECURELY_SEEDED};
+    use crate::lifetime::{reseed_rng, start_reseed_if_due};
+    use crate::management::{FakeManagementCanister, FakeOutcome};
+    use crate::utils::sha256_hex;
+    use candid::Decode;
+    use std::future::Future;
+    use std::task::{Context, Poll, Waker};
+
+    fn user(id: u8) -> Principal {
+        Principal::from_slice(&[id])
+    }
+
+    fn canister_id() -> Principal {
+        Principal::from_slice(&[0xca, 0x11])
+    }
+
+    fn seconds(seconds: u64) -> TimestampMillis {
+        seconds * MILLIS_TO_SECONDS
+    }
+
+    /// A fresh colony, called by `caller` at `now`. Queries take `state.borrow()` and updates
+    /// `&mut state.borrow_mut()`, the same way the canister entry points call the `_impl`s.
+    fn new_state(caller: Principal, now: TimestampMillis) -> RefCell<RuntimeState> {
+        let state = RefCell::new(RuntimeState::default());
+        set_env(&state, caller, now);
+        state
+    }
+
+    /// Makes the following calls come from `caller` at `now`
+    fn set_env(state: &RefCell<RuntimeState>, caller: Principal, now: TimestampMillis) {
+        set_caller(state, caller, TestClock::new(now));
+    }
+
+    /// Makes the following calls come from `caller`, with the time kept by `clock`
+    fn set_caller(state: &RefCell<RuntimeState>, caller: Principal, clock: TestClock) {
+        state.borrow_mut().env = Box::new(TestEnv {
+            clock,
+            caller,
+            canister_id: canister_id(),
+            ..TestEnv::new(0)
+        });
+    }
+
+    /// Registers each user in the colony at time 0
+    fn with_players(users: &[Principal]) -> RefCell<RuntimeState> {
+        let state = new_state(users[0], 0);
+        for user in users {
+            set_env(&state, *user, 0);
+            add_player_to_world_impl(&mut state.borrow_mut()).unwrap();
+        }
+        state
+    }
+
+    fn assert_not_registered<T: std::fmt::Debug>(result: Result<T, PlayerError>) {
+        assert_eq!(result.unwrap_err(), PlayerError::NotRegistered);
+    }
+
+    #[test]
+    fn test_queries_from_unregistered_caller() {
+        let state = new_state(user(1), 0);
+        let caller = user(1);
+
+        assert_not_registered(get_player_inventory_impl(state.borrow()));
+        assert_not_registered(get_unclaimed_work_impl(state.borrow()));
+        assert_not_registered(get_upkeep_estimate_impl(state.borrow()));
+        assert_not_registered(get_player_profile_impl(state.borrow()));
+        assert_not_registered(get_player_items_impl(state.borrow()));
+        assert_not_registered(get_profile_impl(state.borrow(), caller));
+
+        assert!(!is_player_here_impl(state.borrow()));
+        assert!(get_my_orders_impl(state.borrow()).is_empty());
+    }
+
+    #[test]
+    fn test_updates_from_unregistered_caller() {
+        let state = with_players(&[user(2)]);
+        set_env(&state, user(1), 0);
+        let state = &mut state.borrow_mut();
+
+        assert_not_registered(start_work_impl(state));
+        assert_not_registered(stop_work_impl(state));
+        assert_not_registered(harvest_impl(state));
+        assert_not_registered(start_expedition_impl(state));
+        assert_not_registered(join_expedition_impl(state, 0));
+        assert_not_registered(craft_impl(state, Item::Axe));
+        assert_not_registered(collect_crafted_impl(state));
+        assert_not_registered(set_profile_impl(state, ProfileArgs::default()));
+        assert_not_registered(contribute_to_building_impl(
+            state,
+            Building::Sawmill,
+            HashMap::from([(Resources::Wood, 1)]),
+        ));
+        assert_not_registered(swap_impl(
+            state,
+            SwapArgs {
+                from: Resources::Wood,
+                to: Resources::Stone,
+                amount_in: 1,
+                min_amount_out: 0,
+            },
+        ));
+        assert_not_registered(add_liquidity_impl(
+            state,
+            PoolDeposit {
+                a: Resources::Wood,
+                b: Resources::Stone,
+                amount_a: 1,
+                amount_b: 1,
+            },
+        ));
+        assert_not_registered(place_order_impl(
+            state,
+            NewOrder {
+                side: OrderSide::Ask,
+                base: Resources::Wood,
+                quote: Resources::Stone,
+                price: 1,
+                amount: 1,
+            },
+        ));
+        assert_not_registered(remove_liquidity_impl(
+            state,
+            Resources::Wood,
+            Resources::Stone,
+            1,
+        ));
+
+        // Nothing was added on the caller's behalf along the way
+        assert_eq!(state.data.business_state.player.len(), 1);
+    }
 
     #[test]
     fn check_candid() {
@@ -621,4 +1472,661 @@ mod tests {
             "Generated candid definition does not match expected did file"
         );
     }
+
+    #[test]
+    fn test_player_registration() {
+        let state = new_state(user(1), seconds(5));
+
+        assert!(!is_player_here_impl(state.borrow()));
+        assert_eq!(add_player_to_world_impl(&mut state.borrow_mut()), Ok(()));
+        assert!(is_player_here_impl(state.borrow()));
+
+        assert_eq!(
+            add_player_to_world_impl(&mut state.borrow_mut()),
+            Err("The player already exists in this world".to_string())
+        );
+
+        // Registering doesn't register anybody else
+        set_env(&state, user(2), seconds(5));
+        assert!(!is_player_here_impl(state.borrow()));
+
+        let profile = get_profile_impl(state.borrow(), user(1)).unwrap();
+        assert_eq!(profile, Profile::new(seconds(5)));
+    }
+
+    #[test]
+    fn test_colony_info() {
+        let state = with_players(&[user(1), user(2)]);
+
+        let info = get_colony_info_impl(state.borrow());
+        assert_eq!(info.canister_id, canister_id());
+        assert_eq!(info.generation, 0);
+        assert_eq!(info.taxes_percent, 10);
+        assert_eq!(info.player_count, 2);
+        assert_eq!(info.expeditions_count, 0);
+        assert_eq!(info.rewards_per_second.get(&Resources::Wood), Some(&10));
+        assert_eq!(info.buildings.len(), 5);
+    }
+
+    #[test]
+    fn test_work_across_time() {
+        let state = with_players(&[user(1)]);
+
+        assert_eq!(
+            stop_work_impl(&mut state.borrow_mut()),
+            Err(PlayerError::Other(
+                "Player is currently not working".to_string()
+            ))
+        );
+        assert_eq!(start_work_impl(&mut state.borrow_mut()), Ok(()));
+        assert_eq!(
+            start_work_impl(&mut state.borrow_mut()),
+            Err(PlayerError::Other("Player is already working.".to_string()))
+        );
+
+        // Nothing is earned until a full second has passed
+        set_env(&state, user(1), seconds(1) - 1);
+        let unclaimed: HashMap<Resources, u64> = get_unclaimed_work_impl(state.borrow())
+            .unwrap()
+            .into_iter()
+            .collect();
+        assert!(unclaimed.values().all(|val| *val == 0));
+
+        set_env(&state, user(1), seconds(10));
+        let unclaimed: HashMap<Resources, u64> = get_unclaimed_work_impl(state.borrow())
+            .unwrap()
+            .into_iter()
+            .collect();
+        assert_eq!(unclaimed.get(&Resources::Wood), Some(&100));
+
+        assert_eq!(stop_work_impl(&mut state.borrow_mut()), Ok(()));
+        assert_eq!(
+            get_unclaimed_work_impl(state.borrow()),
+            Err(PlayerError::Other(
+                "The player is not currently working".to_string()
+            ))
+        );
+
+        // 10 per second for 10 seconds, minus 1 Food and 1 Water per second of upkeep
+        let inventory = get_player_inventory_impl(state.borrow()).unwrap();
+        assert_eq!(inventory.get(Resources::Wood), 100);
+        assert_eq!(inventory.get(Resources::Stone), 100);
+        assert_eq!(inventory.get(Resources::Food), 90);
+        assert_eq!(inventory.get(Resources::Water), 90);
+
+        // A later session starts from where the player is now
+        set_env(&state, user(1), seconds(100));
+        start_work_impl(&mut state.borrow_mut()).unwrap();
+        set_env(&state, user(1), seconds(105));
+        stop_work_impl(&mut state.borrow_mut()).unwrap();
+
+        let inventory = get_player_inventory_impl(state.borrow()).unwrap();
+        assert_eq!(inventory.get(Resources::Wood), 150);
+    }
+
+    #[test]
+    fn test_harvest_across_time() {
+        let state = with_players(&[user(1)]);
+        let clock = TestClock::new(0);
+        set_caller(&state, user(1), clock.clone());
+
+        start_work_impl(&mut state.borrow_mut()).unwrap();
+
+        clock.advance_seconds(10);
+        clock.advance(MILLIS_TO_SECONDS / 2);
+        let harvested: HashMap<Resources, u64> = harvest_impl(&mut state.borrow_mut())
+            .unwrap()
+            .into_iter()
+            .collect();
+        assert_eq!(harvested.get(&Resources::Wood), Some(&100));
+
+        // The half second that wasn't paid for yet counts towards the next harvest
+        clock.advance_seconds(9);
+        clock.advance(MILLIS_TO_SECONDS / 2);
+        let harvested: HashMap<Resources, u64> = harvest_impl(&mut state.borrow_mut())
+            .unwrap()
+            .into_iter()
+            .collect();
+        assert_eq!(harvested.get(&Resources::Wood), Some(&100));
+
+        assert_eq!(clock.peek(), seconds(20));
+
+        stop_work_impl(&mut state.borrow_mut()).unwrap();
+        let inventory = get_player_inventory_impl(state.borrow()).unwrap();
+        assert_eq!(inventory.get(Resources::Wood), 200);
+    }
+
+    #[test]
+    fn test_clock_running_between_calls() {
+        let state = with_players(&[user(1)]);
+        let clock = TestClock::new(0);
+        set_caller(&state, user(1), clock.clone());
+
+        // Every call sees the time one second after the previous one
+        clock.auto_advance(seconds(1));
+
+        start_work_impl(&mut state.borrow_mut()).unwrap();
+        for _ in 0..8 {
+            get_unclaimed_work_impl(state.borrow()).unwrap();
+        }
+        stop_work_impl(&mut state.borrow_mut()).unwrap();
+
+        let inventory = get_player_inventory_impl(state.borrow()).unwrap();
+        assert_eq!(inventory.get(Resources::Wood), 90);
+    }
+
+    #[test]
+    fn test_expedition_propose_and_join() {
+        let state = with_players(&[user(1), user(2)]);
+
+        assert_eq!(
+            start_expedition_impl(&mut state.borrow_mut()),
+            Err(PlayerError::Other(
+                "The player doesn't have enough resources to propose an expedition".to_string()
+            ))
+        );
+
+        // Both players earn enough for an expedition
+        for id in [1, 2] {
+            set_env(&state, user(id), 0);
+            start_work_impl(&mut state.borrow_mut()).unwrap();
+            set_env(&state, user(id), seconds(10));
+            stop_work_impl(&mut state.borrow_mut()).unwrap();
+        }
+
+        set_env(&state, user(1), seconds(20));
+        assert_eq!(start_expedition_impl(&mut state.borrow_mut()), Ok(()));
+
+        set_env(&state, user(2), seconds(30));
+        assert_eq!(
+            join_expedition_impl(&mut state.borrow_mut(), 1),
+            Err(PlayerError::Other("Can't find expedition".to_string()))
+        );
+        assert_eq!(join_expedition_impl(&mut state.borrow_mut(), 0), Ok(()));
+        assert_eq!(
+            join_expedition_impl(&mut state.borrow_mut(), 0),
+            Err(PlayerError::Other(
+                "The player is already a member of this expedition".to_string()
+            ))
+        );
+
+        let expeditions = get_expeditions_impl(state.borrow());
+        assert_eq!(expeditions.len(), 1);
+
+        let expedition = expeditions.get(&0).unwrap();
+        assert_eq!(expedition.members, vec![user(1), user(2)]);
+        assert_eq!(expedition.resources_pool.get(Resources::Wood), 120);
+        assert!(matches!(
+            expedition.clone().get_step(),
+            ExpeditionStep::Proposed
+        ));
+
+        let inventory = get_player_inventory_impl(state.borrow()).unwrap();
+        assert_eq!(inventory.get(Resources::Wood), 40);
+
+        assert_eq!(get_colony_info_impl(state.borrow()).expeditions_count, 1);
+    }
+
+    /// Polls a future once. The fake management canister never waits on anything, so a call
+    /// either finishes on the first poll or hangs forever.
+    fn poll_once<F: Future>(future: F) -> Poll<F::Output> {
+        let mut future = Box::pin(future);
+        future
+            .as_mut()
+            .poll(&mut Context::from_waker(Waker::noop()))
+    }
+
+    fn run<F: Future>(future: F) -> F::Output {
+        match poll_once(future) {
+            Poll::Ready(output) => output,
+            Poll::Pending => panic!("The call didn't finish"),
+        }
+    }
+
+    fn expedition_step(expedition_id: u64) -> ExpeditionStep {
+        RUNTIME_STATE.with(|state| {
+            state.borrow().data.business_state.expeditions[&expedition_id]
+                .clone()
+                .get_step()
+        })
+    }
+
+    /// Installs a canister state with one proposed expedition as the thread's RUNTIME_STATE.
+    /// With `funded` the expedition's pool holds enough to get ready.
+    fn setup_expedition(funded: bool) {
+        let state = with_players(&[user(1)]);
+
+        start_work_impl(&mut state.borrow_mut()).unwrap();
+        set_env(&state, user(1), seconds(10));
+        stop_work_impl(&mut state.borrow_mut()).unwrap();
+        start_expedition_impl(&mut state.borrow_mut()).unwrap();
+
+        if funded {
+            demo_add_res_impl(&mut state.borrow_mut()).unwrap();
+        }
+
+        {
+            let registry = &mut state.borrow_mut().data.business_state.wasm_registry;
+            let sha256 = registry.add(b"\0asm".to_vec(), None, user(1), 0);
+            registry.activate(&sha256).unwrap();
+        }
+
+        RUNTIME_STATE.with(|runtime_state| *runtime_state.borrow_mut() = state.into_inner());
+    }
+
+    #[test]
+    fn test_expedition_starts_a_new_colony() {
+        setup_expedition(true);
+        let management = FakeManagementCanister::new(FakeOutcome::Succeed, FakeOutcome::Succeed);
+
+        assert_eq!(run(expedition_next_impl(&management, 0)), Ok(()));
+        assert!(matches!(expedition_step(0), ExpeditionStep::Ready));
+
+        assert_eq!(run(expedition_next_impl(&management, 0)), Ok(()));
+
+        let created = management.created.borrow()[0];
+        assert!(matches!(expedition_step(0), ExpeditionStep::Started(id) if id == created));
+
+        let installed = management.installed.borrow();
+        assert_eq!(installed.len(), 1);
+        assert_eq!(installed[0].canister_id, created);
+        assert_eq!(installed[0].wasm_module, b"\0asm".to_vec());
+        let wasm_hash = RUNTIME_STATE.with(|state| {
+            state.borrow().data.business_state.expeditions[&0]
+                .wasm_hash
+                .clone()
+        });
+        assert_eq!(wasm_hash, Some(sha256_hex(b"\0asm")));
+
+        // The new colony is installed with a generation 1 colony state funded by the expedition,
+        // minus whatever was lost on the way
+        let outcome = RUNTIME_STATE.with(|state| {
+            state.borrow().data.business_state.expeditions[&0]
+                .outcome
+                .clone()
+                .unwrap()
+        });
+        let args = Decode!(&installed[0].arg, CanisterInstallSendArgs).unwrap();
+        assert_eq!(args.colony_state.generation, 1);
+        assert_eq!(
+            args.colony_state.coffers.get(Resources::Wood),
+            1060 - 1060 * outcome.lost_percent as u64 / 100
+        );
+        assert_eq!(
+            args.colony_state.rewards_per_second,
+            outcome.rewards_per_second
+        );
+
+        assert_eq!(run(expedition_next_impl(&management, 0)), Ok(()));
+        assert!(matches!(expedition_step(0), ExpeditionStep::Done));
+        assert_eq!(
+            RUNTIME_STATE.with(|state| state.borrow().data.business_state.remote_colonies.clone()),
+            vec![created]
+        );
+
+        assert_eq!(
+            run(expedition_next_impl(&management, 0)),
+            Err("This expedition cannot be changed anymore".to_string())
+        );
+    }
+
+    #[test]
+    fn test_expedition_needs_resources() {
+        setup_expedition(false);
+        let management = FakeManagementCanister::new(FakeOutcome::Succeed, FakeOutcome::Succeed);
+
+        assert_eq!(
+            run(expedition_next_impl(&management, 0)),
+            Err("Not enough resources to start the expedition".to_string())
+        );
+        assert!(matches!(expedition_step(0), ExpeditionStep::Proposed));
+
+        assert_eq!(
+            run(expedition_next_impl(&management, 1)),
+            Err("Can't find expedition".to_string())
+        );
+    }
+
+    #[test]
+    fn test_expedition_retries_after_create_fails() {
+        setup_expedition(true);
+        let management = FakeManagementCanister::new(FakeOutcome::Fail, FakeOutcome::Succeed);
+
+        run(expedition_next_impl(&management, 0)).unwrap();
+        assert_eq!(
+            run(expedition_next_impl(&management, 0)),
+            Err("Could not create the new colony: create_canister failed".to_string())
+        );
+        assert!(matches!(expedition_step(0), ExpeditionStep::Ready));
+        assert!(management.installed.borrow().is_empty());
+
+        let management = FakeManagementCanister::new(FakeOutcome::Succeed, FakeOutcome::Succeed);
+        assert_eq!(run(expedition_next_impl(&management, 0)), Ok(()));
+        assert!(matches!(expedition_step(0), ExpeditionStep::Started(_)));
+    }
+
+    #[test]
+    fn test_expedition_retries_after_install_fails() {
+        setup_expedition(true);
+        let mut management = FakeManagementCanister::new(FakeOutcome::Succeed, FakeOutcome::Fail);
+
+        run(expedition_next_impl(&management, 0)).unwrap();
+        assert_eq!(
+            run(expedition_next_impl(&management, 0)),
+            Err("Could not install the new colony: install_code failed".to_string())
+        );
+        assert!(matches!(expedition_step(0), ExpeditionStep::Ready));
+        assert_eq!(management.created.borrow().len(), 1);
+        assert!(management.installed.borrow().is_empty());
+
+        // The retry installs into the canister that was already created
+        management.install = FakeOutcome::Succeed;
+        assert_eq!(run(expedition_next_impl(&management, 0)), Ok(()));
+
+        let created = management.created.borrow()[0];
+        assert_eq!(management.created.borrow().len(), 1);
+        assert_eq!(management.installed.borrow()[0].canister_id, created);
+        assert!(matches!(expedition_step(0), ExpeditionStep::Started(id) if id == created));
+    }
+
+    #[test]
+    fn test_expedition_waiting_for_the_management_canister() {
+        setup_expedition(true);
+        let management = FakeManagementCanister::new(FakeOutcome::Hang, FakeOutcome::Succeed);
+
+        run(expedition_next_impl(&management, 0)).unwrap();
+        assert!(poll_once(expedition_next_impl(&management, 0)).is_pending());
+
+        // The expedition stays in Starting so it can't be started a second time meanwhile
+        assert!(matches!(expedition_step(0), ExpeditionStep::Starting(now) if now == seconds(10)));
+        assert_eq!(
+            run(expedition_next_impl(&management, 0)),
+            Err("Retry logic not implemented".to_string())
+        );
+    }
+
+    #[test]
+    fn test_rng_is_reseeded_from_raw_rand() {
+        let state = new_state(user(1), 0);
+        let clock = TestClock::new(0);
+        state.borrow_mut().env = Box::new(TestEnv {
+            clock: clock.clone(),
+            securely_seeded: false,
+            ..TestEnv::new(0)
+        });
+        RUNTIME_STATE.with(|runtime_state| *runtime_state.borrow_mut() = state.into_inner());
+
+        let random_u32 = || RUNTIME_STATE.with(|state| state.borrow_mut().env.random_u32());
+        let due = || RUNTIME_STATE.with(|state| start_reseed_if_due(&mut state.borrow_mut()));
+
+        assert_eq!(random_u32(), Err(NOT_SECURELY_SEEDED.to_string()));
+
+        // The first heartbeat starts a reseed, the next ones wait for it to finish
+        assert!(due());
+        assert!(!due());
+
+        let mut management =
+            FakeManagementCanister::new(FakeOutcome::Succeed, FakeOutcome::Succeed);
+        management.raw_rand = FakeOutcome::Fail;
+        assert_eq!(
+            run(reseed_rng(&management)),
+            Err("raw_rand failed".to_string())
+        );
+        assert_eq!(random_u32(), Err(NOT_SECURELY_SEEDED.to_string()));
+
+        // A failed reseed is retried by the next heartbeat
+        assert!(due());
+        management.raw_rand = FakeOutcome::Succeed;
+        assert_eq!(run(reseed_rng(&management)), Ok(()));
+        assert!(random_u32().is_ok());
+
+        clock.advance_seconds(59 * 60);
+        assert!(!due());
+        clock.advance_seconds(60);
+        assert!(due());
+    }
+
+    #[test]
+    fn test_expedition_outcome_is_rolled_on_start() {
+        setup_expedition(true);
+        let management = FakeManagementCanister::new(FakeOutcome::Fail, FakeOutcome::Succeed);
+
+        let env = TestEnv {
+            clock: TestClock::new(seconds(10)),
+            ..TestEnv::new(0)
+        };
+        env.random.script(&[1234, 5678]);
+        RUNTIME_STATE.with(|state| state.borrow_mut().env = Box::new(env));
+
+        run(expedition_next_impl(&management, 0)).unwrap();
+        run(expedition_next_impl(&management, 0)).unwrap_err();
+
+        // The roll is kept when starting the colony has to be retried, which doesn't wait for
+        // secure randomness again
+        let env = TestEnv {
+            clock: TestClock::new(seconds(20)),
+            securely_seeded: false,
+            ..TestEnv::new(0)
+        };
+        RUNTIME_STATE.with(|state| state.borrow_mut().env = Box::new(env));
+        assert_eq!(
+            run(expedition_next_impl(&management, 0)),
+            Err("Could not create the new colony: create_canister failed".to_string())
+        );
+        let outcome = RUNTIME_STATE.with(|state| {
+            state.borrow().data.business_state.expeditions[&0]
+                .outcome
+                .clone()
+        });
+        assert_eq!(outcome.map(|outcome| outcome.seed), Some(1234));
+    }
+
+    #[test]
+    fn test_expedition_waits_for_secure_randomness() {
+        setup_expedition(true);
+        let management = FakeManagementCanister::new(FakeOutcome::Succeed, FakeOutcome::Succeed);
+
+        RUNTIME_STATE.with(|state| {
+            state.borrow_mut().env = Box::new(TestEnv {
+                securely_seeded: false,
+                ..TestEnv::new(0)
+            })
+        });
+
+        run(expedition_next_impl(&management, 0)).unwrap();
+        assert_eq!(
+            run(expedition_next_impl(&management, 0)),
+            Err(NOT_SECURELY_SEEDED.to_string())
+        );
+        assert!(matches!(expedition_step(0), ExpeditionStep::Ready));
+        assert!(management.created.borrow().is_empty());
+    }
+
+    #[test]
+    fn test_copy_colony_with_snapshot() -> Result<(), String> {
+        let production = with_players(&[user(1), user(2)]);
+        production
+            .borrow_mut()
+            .data
+            .system_settings
+            .admins
+            .push(user(1));
+
+        set_env(&production, user(2), 0);
+        assert_eq!(
+            start_snapshot_export_impl(&mut production.borrow_mut()),
+            Err("Only admins can do this".to_string())
+        );
+
+        set_env(&production, user(1), 0);
+        let info = start_snapshot_export_impl(&mut production.borrow_mut())?;
+        let mut bytes = vec![];
+        while (bytes.len() as u64) < info.size {
+            bytes.extend(get_snapshot_chunk_impl(
+                production.borrow(),
+                bytes.len() as u64,
+            )?);
+        }
+
+        let local = new_state(user(3), 0);
+        local.borrow_mut().data.system_settings.admins.push(user(3));
+        start_snapshot_import_impl(&mut local.borrow_mut(), info)?;
+        upload_snapshot_chunk_impl(&mut local.borrow_mut(), bytes)?;
+        finish_snapshot_import_impl(&mut local.borrow_mut())?;
+
+        let local = local.borrow();
+        assert!(local.data.business_state.is_player_in_world(user(2)));
+        assert_eq!(local.data.system_settings.admins, vec![user(1), user(3)]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_wasm_checks_the_header() {
+        let state = new_state(user(1), 0);
+        state.borrow_mut().data.system_settings.admins.push(user(1));
+
+        assert_eq!(
+            load_wasm_impl(&mut state.borrow_mut(), b"<html>".to_vec(), None),
+            Err("This is neither a wasm module nor a gzipped one".to_string())
+        );
+        let gzipped = load_wasm_impl(&mut state.borrow_mut(), vec![0x1f, 0x8b, 8, 0], None);
+        assert_eq!(gzipped, Ok(sha256_hex(&[0x1f, 0x8b, 8, 0])));
+        assert!(load_wasm_impl(&mut state.borrow_mut(), b"\0asm\x01\0\0\0".to_vec(), None).is_ok());
+    }
+
+    #[test]
+    fn test_wasm_versions() -> Result<(), String> {
+        let state = new_state(user(2), 0);
+        state.borrow_mut().data.system_settings.admins.push(user(1));
+
+        assert_eq!(
+            load_wasm_impl(&mut state.borrow_mut(), b"\0asm1".to_vec(), None),
+            Err("Only admins can do this".to_string())
+        );
+
+        set_env(&state, user(1), 10);
+        let v1 = load_wasm_impl(
+            &mut state.borrow_mut(),
+            b"\0asm1".to_vec(),
+            Some("v1".to_string()),
+        )?;
+        set_env(&state, user(1), 20);
+        let v2 = load_wasm_impl(&mut state.borrow_mut(), b"\0asm2".to_vec(), None)?;
+
+        let versions = get_wasm_versions_impl(state.borrow());
+        assert_eq!(versions.len(), 2);
+        assert_eq!(
+            versions[0],
+            WasmVersion {
+                sha256: v1.clone(),
+                label: "v1".to_string(),
+                size: 5,
+                uploaded_at: 10,
+                uploaded_by: user(1),
+                active: false,
+            }
+        );
+        assert!(versions[1].active);
+
+        assert_eq!(rollback_wasm_impl(&mut state.borrow_mut()), Ok(v1.clone()));
+        activate_wasm_impl(&mut state.borrow_mut(), v2.clone())?;
+        let active = state
+            .borrow()
+            .data
+            .business_state
+            .wasm_registry
+            .active()
+            .map(|(sha256, _)| sha256.to_string());
+        assert_eq!(active, Some(v2.clone()));
+        assert_eq!(
+            remove_wasm_impl(&mut state.borrow_mut(), v2),
+            Err("The active wasm can't be removed".to_string())
+        );
+
+        set_env(&state, user(2), 30);
+        assert_eq!(
+            rollback_wasm_impl(&mut state.borrow_mut()),
+            Err("Only admins can do this".to_string())
+        );
+        assert_eq!(
+            remove_wasm_impl(&mut state.borrow_mut(), v1),
+            Err("Only admins can do this".to_string())
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_upgrade_children() -> Result<(), String> {
+        let management = FakeManagementCanister::new(FakeOutcome::Succeed, FakeOutcome::Succeed);
+        let colonies = FakeColonies::default();
+        let child = run(management.create_canister(user(9)))?;
+        let missing_child = Principal::from_slice(&[0xc0, 0xff]);
+
+        let state = new_state(user(2), 10);
+        state.borrow_mut().data.system_settings.admins.push(user(1));
+        state.borrow_mut().data.business_state.remote_colonies = vec![child, missing_child];
+        RUNTIME_STATE.with(|runtime_state| *runtime_state.borrow_mut() = state.into_inner());
+
+        let sha256 = RUNTIME_STATE.with(|state| {
+            let registry = &mut state.borrow_mut().data.business_state.wasm_registry;
+            registry.add(b"\0asm2".to_vec(), None, user(1), 0)
+        });
+
+        assert_eq!(
+            run(upgrade_children_impl(
+                &management,
+                &colonies,
+                sha256.clone(),
+                true
+            )),
+            Err("Only admins can do this".to_string())
+        );
+
+        RUNTIME_STATE.with(|state| set_env(state, user(1), 20));
+        let upgrade = run(upgrade_children_impl(
+            &management,
+            &colonies,
+            sha256.clone(),
+            true,
+        ))?;
+
+        assert_eq!(upgrade.finished_at, Some(20));
+        assert_eq!(
+            upgrade.children,
+            vec![
+                ChildUpgrade {
+                    canister_id: child,
+                    result: Ok(()),
+                    children: Some(ChildrenUpgrade {
+                        wasm_hash: sha256.clone(),
+                        cascade: true,
+                        started_at: 0,
+                        finished_at: Some(0),
+                        children: vec![],
+                    }),
+                },
+                ChildUpgrade {
+                    canister_id: missing_child,
+                    result: Err(format!(
+                        "Could not upgrade the colony: Canister {} doesn't exist",
+                        missing_child
+                    )),
+                    children: None,
+                },
+            ]
+        );
+
+        let installed = management.installed.borrow();
+        assert_eq!(installed.len(), 1);
+        assert_eq!(installed[0].mode, InstallMode::Upgrade);
+        assert_eq!(installed[0].wasm_module, b"\0asm2".to_vec());
+        assert_eq!(colonies.loaded.borrow().clone(), vec![(child, sha256)]);
+
+        let recorded = RUNTIME_STATE.with(|state| get_children_upgrade_impl(state.borrow()));
+        assert_eq!(recorded, Some(upgrade));
+
+        Ok(())
+    }
 }
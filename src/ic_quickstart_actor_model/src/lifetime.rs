@@ -1,13 +1,35 @@
+use crate::env::{TimestampMillis, MILLIS_TO_SECONDS};
+use crate::management::{IcManagementCanister, ManagementCanister};
+use crate::stable_state::{self, STATE_VERSION};
 use crate::{CanisterEnv, CanisterInstallSendArgs, Data, RuntimeState, RUNTIME_STATE};
 
 #[allow(unused_imports)]
 use ic_cdk_macros::{heartbeat, init, post_upgrade, pre_upgrade};
 
+/// How often the heartbeat mixes fresh entropy from `raw_rand` into the RNG
+const RESEED_INTERVAL: TimestampMillis = 60 * 60 * MILLIS_TO_SECONDS;
+
+/// Tracks the calls to `raw_rand`. Not saved across upgrades: the RNG starts over on every
+/// upgrade, so it needs to be seeded again anyway.
+#[derive(Default)]
+pub struct ReseedSchedule {
+    last_reseeded_at: Option<TimestampMillis>,
+    in_flight: bool,
+}
+
 #[init]
 fn init() {
     let env = Box::new(CanisterEnv::new());
     let data = Data::default();
-    let mut runtime_state = RuntimeState { env, data };
+    let mut runtime_state = RuntimeState {
+        env,
+        data,
+        reseed: Default::default(),
+        snapshots: Default::default(),
+    };
+
+    let installer = runtime_state.env.caller();
+    runtime_state.data.system_settings.admins.push(installer);
 
     let call_arg = ic_cdk::api::call::arg_data::<(Option<CanisterInstallSendArgs>,)>().0;
 
@@ -20,19 +42,80 @@ fn init() {
 
 #[pre_upgrade]
 fn pre_upgrade() {
-    RUNTIME_STATE.with(|state| ic_cdk::storage::stable_save((&state.borrow().data,)).unwrap());
+    RUNTIME_STATE
+        .with(|state| ic_cdk::storage::stable_save((STATE_VERSION, &state.borrow().data)).unwrap());
 }
 
 #[post_upgrade]
 fn post_upgrade() {
     let env = Box::new(CanisterEnv::new());
-    let (data,): (Data,) = ic_cdk::storage::stable_restore().unwrap();
-    let runtime_state = RuntimeState { env, data };
+    let mut data = stable_state::decode(&ic_cdk::api::stable::stable_bytes())
+        .unwrap_or_else(|error| ic_cdk::trap(&format!("Could not restore the state: {}", error)));
+
+    // Canisters from before there were admins get the principal upgrading them as their first one
+    if data.system_settings.admins.is_empty() {
+        data.system_settings.admins.push(ic_cdk::caller());
+    }
+
+    let runtime_state = RuntimeState {
+        env,
+        data,
+        reseed: Default::default(),
+        snapshots: Default::default(),
+    };
 
     RUNTIME_STATE.with(|state| *state.borrow_mut() = runtime_state);
 }
 
-// #[heartbeat]
-// fn heartbeat() {
-//     // print("Hello from heartbeat");
-// }
+// Management canister calls aren't allowed during init and post_upgrade, so the RNG is
+// seeded securely by the first heartbeat that comes after them
+#[heartbeat]
+fn heartbeat() {
+    if RUNTIME_STATE.with(|state| start_reseed_if_due(&mut state.borrow_mut())) {
+        ic_cdk::spawn(async {
+            if let Err(error) = reseed_rng(&IcManagementCanister).await {
+                ic_cdk::print(format!("Could not reseed the RNG: {}", error));
+            }
+        });
+    }
+}
+
+/// Whether it's time to fetch fresh entropy. If it is, the reseed is marked as in flight so the
+/// following heartbeats don't start another one.
+pub(crate) fn start_reseed_if_due(runtime_state: &mut RuntimeState) -> bool {
+    let now = runtime_state.env.now();
+    let seeded = runtime_state.env.is_securely_seeded();
+    let schedule = &mut runtime_state.reseed;
+
+    let due = match schedule.last_reseeded_at {
+        _ if schedule.in_flight => false,
+        // Everything that needs randomness is refused until then, so don't wait for the interval
+        _ if !seeded => true,
+        Some(last_reseeded_at) => now.saturating_sub(last_reseeded_at) >= RESEED_INTERVAL,
+        None => true,
+    };
+
+    if due {
+        schedule.in_flight = true;
+    }
+
+    due
+}
+
+/// Mixes entropy from `raw_rand` into the RNG. Until this succeeds once, everything that needs
+/// randomness is refused.
+pub(crate) async fn reseed_rng<M: ManagementCanister>(management: &M) -> Result<(), String> {
+    let entropy = management.raw_rand().await;
+
+    RUNTIME_STATE.with(|state| {
+        let mut runtime_state = state.borrow_mut();
+        runtime_state.reseed.in_flight = false;
+
+        let entropy = entropy?;
+        let now = runtime_state.env.now();
+        runtime_state.env.add_entropy(&entropy);
+        runtime_state.reseed.last_reseeded_at = Some(now);
+
+        Ok(())
+    })
+}
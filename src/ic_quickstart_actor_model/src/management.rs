@@ -0,0 +1,240 @@
+use candid::{CandidType, Deserialize, Nat, Principal};
+
+/// How `install_code` treats the code a canister already runs
+#[derive(CandidType, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum InstallMode {
+    #[serde(rename = "install")]
+    Install,
+    #[serde(rename = "reinstall")]
+    Reinstall,
+    /// Keeps the canister's state, through its pre_upgrade and post_upgrade
+    #[serde(rename = "upgrade")]
+    Upgrade,
+}
+
+/// Calls to the IC management canister. Expeditions create and install their new colony through
+/// this, and colonies upgrade their children, so both can be tested without a replica.
+pub trait ManagementCanister {
+    /// Creates an empty canister controlled by `controller` and returns its id
+    async fn create_canister(&self, controller: Principal) -> Result<Principal, String>;
+
+    async fn install_code(
+        &self,
+        mode: InstallMode,
+        canister_id: Principal,
+        wasm_module: Vec<u8>,
+        arg: Vec<u8>,
+    ) -> Result<(), String>;
+
+    /// 32 bytes of randomness that can't be predicted by anyone, including the subnet's nodes
+    async fn raw_rand(&self) -> Result<Vec<u8>, String>;
+}
+
+pub struct IcManagementCanister;
+
+impl ManagementCanister for IcManagementCanister {
+    async fn create_canister(&self, controller: Principal) -> Result<Principal, String> {
+        ic_cdk::print("creating new colony...");
+
+        #[derive(CandidType, Debug, Clone, Deserialize)]
+        pub struct CreateCanisterSettings {
+            pub controllers: Option<Vec<Principal>>,
+            pub compute_allocation: Option<Nat>,
+            pub memory_allocation: Option<Nat>,
+            pub freezing_threshold: Option<Nat>,
+        }
+
+        #[derive(CandidType, Clone, Deserialize)]
+        pub struct CreateCanisterArgs {
+            pub cycles: u64,
+            pub settings: CreateCanisterSettings,
+        }
+
+        #[derive(CandidType, Clone, Deserialize, Debug)]
+        pub struct CanisterIdRecord {
+            pub canister_id: Principal,
+        }
+
+        // Add your own principal as a controller, in case manual control is needed
+        let create_args = CreateCanisterArgs {
+            cycles: 1_000_000_000_000,
+            settings: CreateCanisterSettings {
+                controllers: Some(vec![controller]),
+                compute_allocation: None,
+                memory_allocation: None,
+                freezing_threshold: None,
+            },
+        };
+
+        #[derive(CandidType)]
+        struct In {
+            settings: Option<CreateCanisterSettings>,
+        }
+
+        let in_arg = In {
+            settings: Some(create_args.settings),
+        };
+
+        let (create_result,): (CanisterIdRecord,) = ic_cdk::api::call::call_with_payment(
+            Principal::management_canister(),
+            "create_canister",
+            (in_arg,),
+            create_args.cycles,
+        )
+        .await
+        .map_err(|(code, msg)| {
+            let error = format!("An error happened during the call: {}: {}", code as u8, msg);
+            ic_cdk::print(&error);
+            error
+        })?;
+
+        ic_cdk::print(format!(
+            "Created canister {}",
+            create_result.canister_id.to_text()
+        ));
+
+        Ok(create_result.canister_id)
+    }
+
+    async fn install_code(
+        &self,
+        mode: InstallMode,
+        canister_id: Principal,
+        wasm_module: Vec<u8>,
+        arg: Vec<u8>,
+    ) -> Result<(), String> {
+        #[derive(CandidType, Deserialize)]
+        struct CanisterInstall {
+            mode: InstallMode,
+            canister_id: Principal,
+            #[serde(with = "serde_bytes")]
+            wasm_module: Vec<u8>,
+            #[serde(with = "serde_bytes")]
+            arg: Vec<u8>,
+        }
+
+        let install_config: CanisterInstall = CanisterInstall {
+            mode,
+            canister_id,
+            wasm_module,
+            arg,
+        };
+
+        ic_cdk::api::call::call(
+            Principal::management_canister(),
+            "install_code",
+            (install_config,),
+        )
+        .await
+        .map_err(|(code, msg)| {
+            let error = format!("An error happened during the call: {}: {}", code as u8, msg);
+            ic_cdk::print(&error);
+            error
+        })
+    }
+
+    async fn raw_rand(&self) -> Result<Vec<u8>, String> {
+        let (bytes,): (Vec<u8>,) =
+            ic_cdk::api::call::call(Principal::management_canister(), "raw_rand", ())
+                .await
+                .map_err(|(code, msg)| {
+                    format!("An error happened during the call: {}: {}", code as u8, msg)
+                })?;
+
+        Ok(bytes)
+    }
+}
+
+/// How a call to `FakeManagementCanister` ends
+#[cfg(test)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FakeOutcome {
+    Succeed,
+    Fail,
+    /// The call never returns, like a call whose response hasn't arrived yet
+    Hang,
+}
+
+/// A successful `install_code` call seen by `FakeManagementCanister`
+#[cfg(test)]
+pub struct FakeInstall {
+    pub mode: InstallMode,
+    pub canister_id: Principal,
+    pub wasm_module: Vec<u8>,
+    pub arg: Vec<u8>,
+}
+
+/// Keeps the canisters it creates in memory. Each call ends the way the test told it to.
+#[cfg(test)]
+pub struct FakeManagementCanister {
+    pub create: FakeOutcome,
+    pub install: FakeOutcome,
+    /// Succeeds unless a test changes it
+    pub raw_rand: FakeOutcome,
+    pub created: std::cell::RefCell<Vec<Principal>>,
+    pub installed: std::cell::RefCell<Vec<FakeInstall>>,
+}
+
+#[cfg(test)]
+impl FakeManagementCanister {
+    pub fn new(create: FakeOutcome, install: FakeOutcome) -> Self {
+        Self {
+            create,
+            install,
+            raw_rand: FakeOutcome::Succeed,
+            created: Default::default(),
+            installed: Default::default(),
+        }
+    }
+
+    async fn outcome(outcome: FakeOutcome, call: &str) -> Result<(), String> {
+        match outcome {
+            FakeOutcome::Succeed => Ok(()),
+            FakeOutcome::Fail => Err(format!("{} failed", call)),
+            FakeOutcome::Hang => std::future::pending().await,
+        }
+    }
+}
+
+#[cfg(test)]
+impl ManagementCanister for FakeManagementCanister {
+    async fn create_canister(&self, _controller: Principal) -> Result<Principal, String> {
+        Self::outcome(self.create, "create_canister").await?;
+
+        let mut created = self.created.borrow_mut();
+        let canister_id = Principal::from_slice(&[0xc0, created.len() as u8]);
+        created.push(canister_id);
+
+        Ok(canister_id)
+    }
+
+    async fn install_code(
+        &self,
+        mode: InstallMode,
+        canister_id: Principal,
+        wasm_module: Vec<u8>,
+        arg: Vec<u8>,
+    ) -> Result<(), String> {
+        Self::outcome(self.install, "install_code").await?;
+
+        if !self.created.borrow().contains(&canister_id) {
+            return Err(format!("Canister {} doesn't exist", canister_id));
+        }
+
+        self.installed.borrow_mut().push(FakeInstall {
+            mode,
+            canister_id,
+            wasm_module,
+            arg,
+        });
+
+        Ok(())
+    }
+
+    /// Returns the same bytes every time, so tests stay reproducible
+    async fn raw_rand(&self) -> Result<Vec<u8>, String> {
+        Self::outcome(self.raw_rand, "raw_rand").await?;
+
+        Ok(vec![0x5e; 32])
+    }
+}
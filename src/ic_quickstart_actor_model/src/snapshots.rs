@@ -0,0 +1,211 @@
+//! Copies of the whole colony state that admins can download and load back, to back up a colony
+//! or to try production state on a local replica. A snapshot is too big for a single message, so
+//! it's moved in chunks both ways.
+
+use candid::{CandidType, Deserialize};
+
+use crate::utils::sha256_hex;
+use crate::{stable_state, Data};
+
+/// Keeps every chunk well under the 2MB limit of a message
+pub const SNAPSHOT_CHUNK_SIZE: usize = 1_000_000;
+
+#[derive(CandidType, Deserialize, Debug, Clone, PartialEq)]
+pub struct SnapshotInfo {
+    pub size: u64,
+    /// Hex encoded sha256 of the whole snapshot
+    pub sha256: String,
+}
+
+/// Snapshots being exported or imported. Not saved across upgrades, a transfer that was
+/// interrupted by one has to start over.
+#[derive(Default)]
+pub struct Snapshots {
+    export: Option<Vec<u8>>,
+    import: Option<PendingImport>,
+}
+
+struct PendingImport {
+    expected: SnapshotInfo,
+    bytes: Vec<u8>,
+}
+
+impl Snapshots {
+    /// Takes a snapshot of `data` that can then be downloaded with `export_chunk`. It's encoded
+    /// the same way as the stable memory, so snapshots of older versions can still be imported.
+    pub fn start_export(&mut self, data: &Data) -> Result<SnapshotInfo, String> {
+        let bytes = stable_state::encode(data)?;
+        let info = SnapshotInfo {
+            size: bytes.len() as u64,
+            sha256: sha256_hex(&bytes),
+        };
+        self.export = Some(bytes);
+
+        Ok(info)
+    }
+
+    /// Up to `SNAPSHOT_CHUNK_SIZE` bytes of the last snapshot, starting at `offset`
+    pub fn export_chunk(&self, offset: u64) -> Result<Vec<u8>, String> {
+        let bytes = self
+            .export
+            .as_ref()
+            .ok_or_else(|| "No snapshot was exported".to_string())?;
+
+        let start = offset as usize;
+        if start > bytes.len() {
+            return Err(format!("The snapshot is only {} bytes long", bytes.len()));
+        }
+        let end = bytes.len().min(start + SNAPSHOT_CHUNK_SIZE);
+
+        Ok(bytes[start..end].to_vec())
+    }
+
+    /// Drops whatever was uploaded before and waits for the chunks of a new snapshot
+    pub fn start_import(&mut self, expected: SnapshotInfo) {
+        self.import = Some(PendingImport {
+            expected,
+            bytes: vec![],
+        });
+    }
+
+    pub fn add_import_chunk(&mut self, chunk: Vec<u8>) -> Result<(), String> {
+        let import = self
+            .import
+            .as_mut()
+            .ok_or_else(|| "No snapshot import was started".to_string())?;
+
+        if (import.bytes.len() + chunk.len()) as u64 > import.expected.size {
+            return Err(format!(
+                "The snapshot is bigger than the expected {} bytes",
+                import.expected.size
+            ));
+        }
+        import.bytes.extend(chunk);
+
+        Ok(())
+    }
+
+    /// Checks the uploaded snapshot against what `start_import` was told to expect, and decodes
+    /// it. The upload is dropped either way.
+    pub fn finish_import(&mut self) -> Result<Data, String> {
+        let import = self
+            .import
+            .take()
+            .ok_or_else(|| "No snapshot import was started".to_string())?;
+
+        if import.bytes.len() as u64 != import.expected.size {
+            return Err(format!(
+                "Only {} of {} bytes of the snapshot were uploaded",
+                import.bytes.len(),
+                import.expected.size
+            ));
+        }
+
+        if sha256_hex(&import.bytes) != import.expected.sha256 {
+            return Err("The snapshot doesn't match its checksum".to_string());
+        }
+
+        stable_state::decode(&import.bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use candid::Principal;
+
+    fn data() -> Data {
+        let mut data = Data::default();
+        data.business_state
+            .add_player(Principal::from_slice(&[1]), 0)
+            .unwrap();
+        data.business_state.wasm_registry.add(
+            vec![7; 2 * SNAPSHOT_CHUNK_SIZE + 10],
+            None,
+            Principal::anonymous(),
+            0,
+        );
+
+        data
+    }
+
+    fn download(snapshots: &Snapshots) -> Vec<u8> {
+        let mut bytes = vec![];
+        loop {
+            let chunk = snapshots.export_chunk(bytes.len() as u64).unwrap();
+            if chunk.is_empty() {
+                return bytes;
+            }
+            bytes.extend(chunk);
+        }
+    }
+
+    #[test]
+    fn test_export_and_import() -> Result<(), String> {
+        let mut source = Snapshots::default();
+        let info = source.start_export(&data())?;
+        let bytes = download(&source);
+        assert_eq!(bytes.len() as u64, info.size);
+
+        let mut destination = Snapshots::default();
+        destination.start_import(info);
+        for chunk in bytes.chunks(SNAPSHOT_CHUNK_SIZE) {
+            destination.add_import_chunk(chunk.to_vec())?;
+        }
+
+        let imported = destination.finish_import()?;
+        assert!(imported
+            .business_state
+            .is_player_in_world(Principal::from_slice(&[1])));
+        assert_eq!(
+            imported.business_state.wasm_registry.versions(),
+            data().business_state.wasm_registry.versions()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_import_checks() -> Result<(), String> {
+        let mut source = Snapshots::default();
+        let info = source.start_export(&Data::default())?;
+        let bytes = download(&source);
+
+        let mut destination = Snapshots::default();
+        assert_eq!(
+            destination.add_import_chunk(bytes.clone()),
+            Err("No snapshot import was started".to_string())
+        );
+
+        destination.start_import(info.clone());
+        destination.add_import_chunk(bytes[..1].to_vec())?;
+        assert_eq!(
+            destination.finish_import().err(),
+            Some(format!(
+                "Only 1 of {} bytes of the snapshot were uploaded",
+                info.size
+            ))
+        );
+
+        destination.start_import(info.clone());
+        let mut corrupted = bytes.clone();
+        corrupted[0] ^= 1;
+        destination.add_import_chunk(corrupted)?;
+        assert_eq!(
+            destination.finish_import().err(),
+            Some("The snapshot doesn't match its checksum".to_string())
+        );
+
+        destination.start_import(info.clone());
+        destination.add_import_chunk(bytes.clone())?;
+        assert_eq!(
+            destination.add_import_chunk(bytes),
+            Err(format!(
+                "The snapshot is bigger than the expected {} bytes",
+                info.size
+            ))
+        );
+
+        Ok(())
+    }
+}
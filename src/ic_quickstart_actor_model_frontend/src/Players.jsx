@@ -20,6 +20,8 @@ export default Players
 
 import React from 'react'
 
+const errorText = (err) => 'NotRegistered' in err ? "You haven't joined this world yet" : err.Other;
+
 export const Player = ({ lastByte }) => {
 
     const rawBuffer = new Uint8Array([0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, lastByte]);
@@ -27,6 +29,7 @@ export const Player = ({ lastByte }) => {
     const [greet, setGreet] = useState(false);
     const [isPlaying, setIsPlaying] = useState(false);
     const [inventory, setInventory] = useState({});
+    const [inventoryError, setInventoryError] = useState(null);
     const [claimed, setClaimed] = useState(false);
     const [playerStatus, setPlayerStatus] = useState({})
 
@@ -93,7 +96,13 @@ export const Player = ({ lastByte }) => {
             setGreet(data)
         }
         const inv = await test.getPlayerInventory();
-        setInventory(inv);
+        if ('Ok' in inv) {
+            setInventory(inv.Ok);
+            setInventoryError(null);
+        } else {
+            setInventory({});
+            setInventoryError(errorText(inv.Err));
+        }
 
         console.log(inv)
 
@@ -138,6 +147,14 @@ export const Player = ({ lastByte }) => {
                     <>
                         <div className="block">
 
+                            {
+                                inventoryError &&
+                                <>
+                                    <div>
+                                        {inventoryError}
+                                    </div>
+                                </>
+                            }
                             {
                                 inventory.contents?.length == 0 &&
                                 <>
@@ -0,0 +1,145 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use candid::Principal;
+use clap::Parser;
+
+use colony_state::{inspect_snapshot, SnapshotView};
+
+/// Prints what's in a snapshot saved by `wasm_loader save-snapshot`, without a replica
+#[derive(Parser)]
+struct Opts {
+    file: PathBuf,
+    /// Print JSON instead of tables
+    #[clap(long)]
+    json: bool,
+    /// Only show this player, and the expeditions they are a member of
+    #[clap(long)]
+    player: Option<Principal>,
+    /// Only show expeditions in this step, e.g. Proposed
+    #[clap(long)]
+    step: Option<String>,
+}
+
+fn main() {
+    let opts = Opts::parse();
+
+    let bytes = std::fs::read(&opts.file).unwrap_or_else(|e| {
+        eprintln!("Failed to read {:?}: {}", opts.file, e);
+        std::process::exit(1);
+    });
+
+    let mut view = inspect_snapshot(&bytes).unwrap_or_else(|e| {
+        eprintln!("Failed to decode the snapshot: {}", e);
+        std::process::exit(1);
+    });
+
+    if let Some(principal) = &opts.player {
+        view.filter_player(principal);
+    }
+    if let Some(step) = &opts.step {
+        view.filter_step(step);
+    }
+
+    if opts.json {
+        println!("{}", serde_json::to_string_pretty(&view).unwrap());
+    } else {
+        print_tables(&view);
+    }
+}
+
+fn print_tables(view: &SnapshotView) {
+    let colony = &view.colony;
+
+    println!("Colony");
+    print_table(
+        &[
+            "generation",
+            "taxes",
+            "coffers",
+            "expeditions",
+            "remote colonies",
+        ],
+        vec![vec![
+            colony.generation.to_string(),
+            format!("{}%", colony.taxes_percent),
+            resources(&colony.coffers),
+            colony.expeditions_count.to_string(),
+            colony.remote_colonies.join(", "),
+        ]],
+    );
+
+    println!("\nPlayers");
+    print_table(
+        &["principal", "name", "status", "inventory"],
+        view.players
+            .iter()
+            .map(|p| {
+                vec![
+                    p.principal.clone(),
+                    p.display_name.clone().unwrap_or_default(),
+                    p.status.clone(),
+                    resources(&p.inventory),
+                ]
+            })
+            .collect(),
+    );
+
+    println!("\nExpeditions");
+    print_table(
+        &[
+            "id",
+            "step",
+            "detail",
+            "proposed by",
+            "members",
+            "required",
+            "pool",
+        ],
+        view.expeditions
+            .iter()
+            .map(|e| {
+                vec![
+                    e.id.to_string(),
+                    e.step.clone(),
+                    e.step_detail.clone().unwrap_or_default(),
+                    e.proposed_by.clone(),
+                    e.members.len().to_string(),
+                    resources(&e.resources_required),
+                    resources(&e.resources_pool),
+                ]
+            })
+            .collect(),
+    );
+}
+
+fn resources(resources: &BTreeMap<String, u64>) -> String {
+    resources
+        .iter()
+        .map(|(res, val)| format!("{}={}", res, val))
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+fn print_table(headers: &[&str], rows: Vec<Vec<String>>) {
+    let mut widths: Vec<usize> = headers.iter().map(|header| header.len()).collect();
+    for row in rows.iter() {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let line = |cells: Vec<&str>| {
+        let cells: Vec<String> = cells
+            .iter()
+            .zip(widths.iter())
+            .map(|(cell, width)| format!("{:width$}", cell, width = width))
+            .collect();
+        println!("{}", cells.join("  ").trim_end());
+    };
+
+    line(headers.to_vec());
+    for row in rows.iter() {
+        line(row.iter().map(String::as_str).collect());
+    }
+}
@@ -1,3 +1,3 @@
 #!/bin/bash
 
-cargo run http://127.0.0.1:8000 rrkah-fqaaa-aaaaa-aaaaq-cai ../../target/wasm32-unknown-unknown/release/ic_quickstart_actor_model.wasm
+cargo run -- --env local load-wasm ../../target/wasm32-unknown-unknown/release/ic_quickstart_actor_model.wasm
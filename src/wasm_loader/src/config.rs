@@ -0,0 +1,211 @@
+//! Named environments the wasm_loader can talk to, read from `wasm_loader.toml` next to dfx.json
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use ic_types::Principal;
+use serde::Deserialize;
+use url::Url;
+
+pub const CONFIG_FILE: &str = "wasm_loader.toml";
+
+#[derive(Deserialize, Default, Debug)]
+pub struct Config {
+    #[serde(default)]
+    pub environments: HashMap<String, Environment>,
+}
+
+#[derive(Deserialize, Default, Debug, Clone)]
+pub struct Environment {
+    pub url: String,
+    /// The dfx network to look canister ids up for. Defaults to the name of the environment.
+    pub network: Option<String>,
+    /// Only for local replicas, never fetch the root key of the IC. If missing, it's only
+    /// fetched from localhost.
+    pub fetch_root_key: Option<bool>,
+    /// PEM file of the identity to call as
+    pub identity: Option<PathBuf>,
+    /// Name of the dfx identity to call as, if there's no `identity`
+    pub dfx_identity: Option<String>,
+    /// Canister ids by canister name, for canisters dfx doesn't know about
+    #[serde(default)]
+    pub canister_ids: HashMap<String, String>,
+}
+
+impl Config {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {:?}: {}", path, e))?;
+
+        toml::from_str(&text).map_err(|e| format!("Failed to parse {:?}: {}", path, e))
+    }
+
+    pub fn environment(&self, name: &str) -> Result<Environment, String> {
+        self.environments
+            .get(name)
+            .cloned()
+            .ok_or_else(|| format!("There is no environment {} in {}", name, CONFIG_FILE))
+    }
+}
+
+impl Environment {
+    /// A fetched root key makes any certificate the replica returns look valid, so unless the
+    /// environment says otherwise only a replica on this machine is trusted with it
+    pub fn fetch_root_key(&self) -> bool {
+        self.fetch_root_key.unwrap_or_else(|| is_local(&self.url))
+    }
+
+    /// Resolves `canister`, which is either a canister id or the name of a canister. Names are
+    /// looked up in the environment first, then in the canister_ids.json files dfx writes.
+    pub fn canister_id(
+        &self,
+        env_name: &str,
+        canister: &str,
+        project_root: Option<&Path>,
+    ) -> Result<Principal, String> {
+        if let Ok(canister_id) = Principal::from_text(canister) {
+            return Ok(canister_id);
+        }
+
+        let network = self.network.as_deref().unwrap_or(env_name);
+
+        let from_dfx = project_root.into_iter().flat_map(|root| {
+            [
+                root.join("canister_ids.json"),
+                root.join(".dfx").join(network).join("canister_ids.json"),
+            ]
+        });
+
+        let id = match self.canister_ids.get(canister) {
+            Some(id) => id.clone(),
+            None => from_dfx
+                .filter_map(|file| dfx_canister_id(&file, canister, network))
+                .next()
+                .ok_or_else(|| {
+                    format!(
+                        "Can't find the id of the canister {} on the network {}",
+                        canister, network
+                    )
+                })?,
+        };
+
+        Principal::from_text(&id).map_err(|e| format!("Invalid canister id {}: {}", id, e))
+    }
+}
+
+/// Reads a canister id from a canister_ids.json, which maps canister names to their id on each
+/// network
+fn dfx_canister_id(file: &Path, canister: &str, network: &str) -> Option<String> {
+    let text = std::fs::read_to_string(file).ok()?;
+    let mut ids: HashMap<String, HashMap<String, String>> = serde_json::from_str(&text).ok()?;
+
+    ids.get_mut(canister)?.remove(network)
+}
+
+fn is_local(url: &str) -> bool {
+    let host = Url::parse(url)
+        .ok()
+        .and_then(|url| url.host_str().map(str::to_string));
+
+    matches!(host.as_deref(), Some("localhost" | "127.0.0.1"))
+}
+
+/// The closest directory above `start` that holds a dfx.json
+pub fn project_root(start: &Path) -> Option<PathBuf> {
+    start
+        .ancestors()
+        .find(|dir| dir.join("dfx.json").exists())
+        .map(Path::to_path_buf)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = r#"
+        [environments.local]
+        url = "http://127.0.0.1:8000"
+        dfx_identity = "default"
+
+        [environments.staging]
+        url = "https://ic0.app"
+        network = "ic"
+        identity = "staging.pem"
+        canister_ids = { ic_quickstart_actor_model = "ryjl3-tyaaa-aaaaa-aaaba-cai" }
+
+        [environments.boundary]
+        url = "https://icp-api.io"
+
+        [environments.localhost]
+        url = "http://localhost:8000/"
+
+        [environments.tunnel]
+        url = "https://replica.example.com"
+        fetch_root_key = true
+    "#;
+
+    fn project(name: &str) -> PathBuf {
+        let root = std::env::temp_dir().join(format!("wasm_loader_{}", name));
+        std::fs::create_dir_all(root.join(".dfx/local")).unwrap();
+        std::fs::write(root.join("dfx.json"), "{}").unwrap();
+        std::fs::write(
+            root.join(".dfx/local/canister_ids.json"),
+            r#"{ "ic_quickstart_actor_model": { "local": "rrkah-fqaaa-aaaaa-aaaaq-cai" } }"#,
+        )
+        .unwrap();
+
+        root
+    }
+
+    #[test]
+    fn test_environments() {
+        let config: Config = toml::from_str(CONFIG).unwrap();
+
+        let local = config.environment("local").unwrap();
+        assert!(local.fetch_root_key());
+        assert_eq!(local.dfx_identity.as_deref(), Some("default"));
+
+        let staging = config.environment("staging").unwrap();
+        assert!(!staging.fetch_root_key());
+        assert_eq!(staging.identity, Some(PathBuf::from("staging.pem")));
+
+        // Without fetch_root_key only local replicas are trusted with their root key
+        assert!(!config.environment("boundary").unwrap().fetch_root_key());
+        assert!(config.environment("localhost").unwrap().fetch_root_key());
+        assert!(config.environment("tunnel").unwrap().fetch_root_key());
+
+        assert_eq!(
+            config.environment("prod").unwrap_err(),
+            "There is no environment prod in wasm_loader.toml"
+        );
+    }
+
+    #[test]
+    fn test_canister_ids() {
+        let config: Config = toml::from_str(CONFIG).unwrap();
+        let root = project("canister_ids");
+        let nested = root.join("src/wasm_loader");
+        std::fs::create_dir_all(&nested).unwrap();
+
+        let root = project_root(&nested);
+        let local = config.environment("local").unwrap();
+        let staging = config.environment("staging").unwrap();
+
+        assert_eq!(
+            local.canister_id("local", "ic_quickstart_actor_model", root.as_deref()),
+            Ok(Principal::from_text("rrkah-fqaaa-aaaaa-aaaaq-cai").unwrap())
+        );
+        assert_eq!(
+            staging.canister_id("staging", "ic_quickstart_actor_model", root.as_deref()),
+            Ok(Principal::from_text("ryjl3-tyaaa-aaaaa-aaaba-cai").unwrap())
+        );
+        assert_eq!(
+            local.canister_id("local", "aaaaa-aa", None),
+            Ok(Principal::management_canister())
+        );
+        assert_eq!(
+            local.canister_id("local", "frontend", root.as_deref()),
+            Err("Can't find the id of the canister frontend on the network local".to_string())
+        );
+    }
+}
@@ -1,39 +1,89 @@
-use ic_agent::identity::AnonymousIdentity;
+mod config;
+
+use ic_agent::identity::{AnonymousIdentity, BasicIdentity, Secp256k1Identity};
 use sha2::{Digest, Sha256};
-use std::{fs::File, io::Read, path::PathBuf};
+use std::path::{Path, PathBuf};
 
-use candid::Encode;
+use candid::{CandidType, Deserialize, Encode};
 
 use ic_agent::agent::http_transport::ReqwestHttpReplicaV2Transport;
-use ic_agent::Agent;
+use ic_agent::{Agent, Identity};
 use ic_types::Principal;
+use ic_utils::call::SyncCall;
 use ic_utils::Canister;
 
-use clap::Parser;
+use clap::{ArgGroup, Parser, Subcommand};
+
+use config::{Config, Environment, CONFIG_FILE};
+
+/// Has to match what the canister accepts in a single `uploadSnapshotChunk` call
+const SNAPSHOT_CHUNK_SIZE: usize = 1_000_000;
+
+#[derive(CandidType, Deserialize, Debug)]
+struct SnapshotInfo {
+    size: u64,
+    sha256: String,
+}
+
+#[derive(CandidType, Deserialize, Debug)]
+struct ChildrenUpgrade {
+    children: Vec<ChildUpgrade>,
+}
+
+#[derive(CandidType, Deserialize, Debug)]
+struct ChildUpgrade {
+    canister_id: Principal,
+    result: Result<(), String>,
+    children: Option<ChildrenUpgrade>,
+}
+
+#[derive(CandidType, Deserialize, Debug)]
+struct WasmVersion {
+    sha256: String,
+    label: String,
+    size: u64,
+    uploaded_at: u64,
+    uploaded_by: Principal,
+    active: bool,
+}
 
 #[tokio::main]
 async fn main() {
-    let mut dev_mode = true;
     let opts = Opts::parse();
 
-    let wasm = read_file_from_local_bin(&opts.wasm_path);
+    let (env, canister_id) = environment(&opts).unwrap_or_else(|error| {
+        eprintln!("{}", error);
+        std::process::exit(1);
+    });
 
-    if opts.url.contains("ic0.app") {
-        dev_mode = false;
+    let identity = match (&env.identity, &env.dfx_identity) {
+        (Some(pem), _) => load_identity(pem),
+        (_, Some(name)) => dfx_identity_pem(name).and_then(|pem| load_identity(&pem)),
+        _ => Ok(Box::new(AnonymousIdentity) as Box<dyn Identity>),
     }
+    .unwrap_or_else(|error| {
+        eprintln!("{}", error);
+        std::process::exit(1);
+    });
+    println!(
+        "Calling {} at {} as {}",
+        canister_id,
+        env.url,
+        identity.sender().unwrap()
+    );
 
-    let transport = ReqwestHttpReplicaV2Transport::create(opts.url)
+    let transport = ReqwestHttpReplicaV2Transport::create(env.url.clone())
         .expect("Failed to create Reqwest transport");
     let timeout = std::time::Duration::from_secs(60 * 5);
 
     let agent = Agent::builder()
         .with_transport(transport)
-        .with_identity(AnonymousIdentity)
+        .with_boxed_identity(identity)
         .with_ingress_expiry(Some(timeout))
         .build()
         .expect("Failed to build agent");
 
-    if dev_mode {
+    if env.fetch_root_key() {
         agent
             .fetch_root_key()
             .await
@@ -42,63 +92,440 @@ async fn main() {
 
     let canister = Canister::builder()
         .with_agent(&agent)
-        .with_canister_id(opts.canister_id)
+        .with_canister_id(canister_id)
         .build()
         .unwrap();
 
-    let waiter = garcon::Delay::builder()
+    let result = match opts.command {
+        Command::LoadWasm {
+            wasm_path,
+            verify_only,
+            label,
+        } => load_wasm(&canister, &wasm_path, verify_only, label).await,
+        Command::WasmVersions => wasm_versions(&canister).await,
+        Command::ActivateWasm { sha256 } => activate_wasm(&canister, sha256).await,
+        Command::RollbackWasm => rollback_wasm(&canister).await,
+        Command::RemoveWasm { sha256 } => remove_wasm(&canister, sha256).await,
+        Command::UpgradeChildren { sha256, cascade } => {
+            upgrade_children(&canister, sha256, cascade).await
+        }
+        Command::SaveSnapshot { file } => save_snapshot(&canister, &file).await,
+        Command::RestoreSnapshot { file } => restore_snapshot(&canister, &file).await,
+    };
+
+    if let Err(error) = result {
+        eprintln!("{}", error);
+        std::process::exit(1);
+    }
+}
+
+#[derive(Parser)]
+#[clap(group(ArgGroup::new("caller").args(&["identity", "dfx-identity"])))]
+struct Opts {
+    /// Environment from the config file to use
+    #[clap(long, default_value = "local")]
+    env: String,
+    /// Defaults to wasm_loader.toml next to dfx.json
+    #[clap(long)]
+    config: Option<PathBuf>,
+    /// Replica to call instead of the environment's. Its root key is only fetched if it's local.
+    #[clap(long)]
+    url: Option<String>,
+    /// Name or id of the canister to call
+    #[clap(long, default_value = "ic_quickstart_actor_model")]
+    canister: String,
+    /// PEM file with the Ed25519 or secp256k1 key to call as, instead of the environment's.
+    /// Calls as the anonymous principal if neither sets an identity.
+    #[clap(long)]
+    identity: Option<PathBuf>,
+    /// Name of a dfx identity to call as, e.g. default
+    #[clap(long)]
+    dfx_identity: Option<String>,
+    #[clap(subcommand)]
+    command: Command,
+}
+
+/// The environment picked with --env, with everything given on the command line on top, and the
+/// id of the canister to call in it
+fn environment(opts: &Opts) -> Result<(Environment, Principal), String> {
+    let cwd = std::env::current_dir().map_err(|e| e.to_string())?;
+    let project_root = config::project_root(&cwd);
+
+    let config_file = opts
+        .config
+        .clone()
+        .or_else(|| project_root.as_ref().map(|root| root.join(CONFIG_FILE)));
+
+    let mut env = match config_file {
+        Some(file) if opts.config.is_some() || file.exists() => {
+            let mut env = Config::load(&file)?.environment(&opts.env)?;
+            // Paths in the config file are relative to it
+            if let (Some(pem), Some(dir)) = (&env.identity, file.parent()) {
+                env.identity = Some(dir.join(pem));
+            }
+            env
+        }
+        _ => Environment::default(),
+    };
+
+    if let Some(url) = &opts.url {
+        // Whether to fetch the root key was decided for the environment's own URL
+        env.url = url.clone();
+        env.fetch_root_key = None;
+    }
+    if env.url.is_empty() {
+        return Err(format!(
+            "The environment {} has no URL, add it to {} or use --url",
+            opts.env, CONFIG_FILE
+        ));
+    }
+
+    if opts.identity.is_some() || opts.dfx_identity.is_some() {
+        env.identity = opts.identity.clone();
+        env.dfx_identity = opts.dfx_identity.clone();
+    }
+
+    let canister_id = env.canister_id(&opts.env, &opts.canister, project_root.as_deref())?;
+
+    Ok((env, canister_id))
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Uploads the wasm that new colonies are installed with, and checks that the canister stored
+    /// it unchanged
+    LoadWasm {
+        /// A .wasm or .wasm.gz file
+        wasm_path: PathBuf,
+        /// Only check that the canister stores this wasm, without uploading it
+        #[clap(long)]
+        verify_only: bool,
+        /// Name for the version, e.g. v1.2
+        #[clap(long)]
+        label: Option<String>,
+    },
+    /// Lists the wasm versions the canister stores
+    WasmVersions,
+    /// Makes a stored wasm the one new colonies are installed with
+    ActivateWasm { sha256: String },
+    /// Goes back to the wasm that was active before
+    RollbackWasm,
+    /// Deletes a stored wasm that is neither active nor the rollback target
+    RemoveWasm { sha256: String },
+    /// Upgrades every colony the canister started to one of its stored wasms
+    UpgradeChildren {
+        sha256: String,
+        /// Have each child upgrade its own children too
+        #[clap(long)]
+        cascade: bool,
+    },
+    /// Downloads the whole state of the colony into a file
+    SaveSnapshot { file: PathBuf },
+    /// Replaces the whole state of the colony with a snapshot saved by save-snapshot
+    RestoreSnapshot { file: PathBuf },
+}
+
+/// Older versions of dfx create Ed25519 keys, newer ones secp256k1
+fn load_identity(pem: &Path) -> Result<Box<dyn Identity>, String> {
+    if let Ok(identity) = Secp256k1Identity::from_pem_file(pem) {
+        return Ok(Box::new(identity));
+    }
+
+    BasicIdentity::from_pem_file(pem)
+        .map(|identity| Box::new(identity) as Box<dyn Identity>)
+        .map_err(|e| format!("Failed to load the identity from {:?}: {}", pem, e))
+}
+
+/// Where dfx keeps the key of the identity `name`
+fn dfx_identity_pem(name: &str) -> Result<PathBuf, String> {
+    let home = std::env::var("HOME").map_err(|_| "HOME is not set".to_string())?;
+    let pem = PathBuf::from(home)
+        .join(".config/dfx/identity")
+        .join(name)
+        .join("identity.pem");
+
+    if pem.exists() {
+        Ok(pem)
+    } else {
+        Err(format!("There is no dfx identity {} at {:?}", name, pem))
+    }
+}
+
+fn waiter() -> garcon::Delay {
+    garcon::Delay::builder()
         .throttle(std::time::Duration::from_millis(500))
         .timeout(std::time::Duration::from_secs(60 * 5))
-        .build();
+        .build()
+}
+
+async fn load_wasm(
+    canister: &Canister<'_>,
+    wasm_path: &Path,
+    verify_only: bool,
+    label: Option<String>,
+) -> Result<(), String> {
+    let wasm = read_wasm(wasm_path)?;
+    let checksum = sha256_hex(&wasm);
+
+    if !verify_only {
+        let (response,) = canister
+            .update_("load_wasm")
+            .with_arg_raw(Encode!(&wasm, &label).unwrap())
+            .build::<(Result<String, String>,)>()
+            .call_and_wait(waiter())
+            .await
+            .map_err(|e| e.to_string())?;
+
+        println!("Uploaded and activated the wasm {}", response?);
+    }
+
+    verify_wasm(canister, &checksum).await
+}
+
+/// Checks that the canister stores the wasm with the checksum `expected`
+async fn verify_wasm(canister: &Canister<'_>, expected: &str) -> Result<(), String> {
+    let (stored,) = canister
+        .query_("wasm_sha256")
+        .build::<(String,)>()
+        .call()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    if stored != expected {
+        return Err(format!(
+            "The canister stores a wasm with the checksum {}, expected {}",
+            stored, expected
+        ));
+    }
+
+    println!("The canister stores the wasm with the checksum {}", stored);
+
+    Ok(())
+}
 
+async fn wasm_versions(canister: &Canister<'_>) -> Result<(), String> {
+    let (versions,) = canister
+        .query_("getWasmVersions")
+        .build::<(Vec<WasmVersion>,)>()
+        .call()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    for version in versions {
+        println!(
+            "{} {} {:>10} bytes  uploaded at {} by {}  {}",
+            if version.active { "*" } else { " " },
+            version.sha256,
+            version.size,
+            version.uploaded_at,
+            version.uploaded_by,
+            version.label
+        );
+    }
+
+    Ok(())
+}
+
+async fn activate_wasm(canister: &Canister<'_>, sha256: String) -> Result<(), String> {
     let (response,) = canister
-        .update_("load_wasm")
-        .with_arg_raw(Encode!(&wasm).unwrap())
-        .build::<(bool,)>()
-        .call_and_wait(waiter)
+        .update_("activateWasm")
+        .with_arg(&sha256)
+        .build::<(Result<(), String>,)>()
+        .call_and_wait(waiter())
         .await
-        .unwrap();
+        .map_err(|e| e.to_string())?;
+    response?;
 
-    // let response = agent
-    //     .update(&opts.canister_id, "load_wasm")
-    //     .with_arg(&Encode!(&wasm).unwrap())
-    //     .call()
-    //     .await
-    //     .unwrap();
+    println!("New colonies are installed with the wasm {}", sha256);
 
-    // let result = Decode!(response.as_slice(), (bool,)).unwrap();
+    Ok(())
+}
 
-    println!("response: {:?}", response);
+async fn rollback_wasm(canister: &Canister<'_>) -> Result<(), String> {
+    let (response,) = canister
+        .update_("rollbackWasm")
+        .build::<(Result<String, String>,)>()
+        .call_and_wait(waiter())
+        .await
+        .map_err(|e| e.to_string())?;
+
+    println!("New colonies are installed with the wasm {}", response?);
+
+    Ok(())
 }
 
-#[derive(Parser)]
-struct Opts {
-    url: String,
-    canister_id: Principal,
-    wasm_path: String,
+async fn remove_wasm(canister: &Canister<'_>, sha256: String) -> Result<(), String> {
+    let (response,) = canister
+        .update_("removeWasm")
+        .with_arg(&sha256)
+        .build::<(Result<(), String>,)>()
+        .call_and_wait(waiter())
+        .await
+        .map_err(|e| e.to_string())?;
+    response?;
+
+    println!("Removed the wasm {}", sha256);
+
+    Ok(())
 }
 
-pub fn read_file_from_local_bin(file_name: &str) -> Vec<u8> {
-    let mut file_path = PathBuf::from(
-        std::env::var("CARGO_MANIFEST_DIR")
-            .expect("Failed to read CARGO_MANIFEST_DIR env variable"),
-    );
+async fn upgrade_children(
+    canister: &Canister<'_>,
+    sha256: String,
+    cascade: bool,
+) -> Result<(), String> {
+    let (response,) = canister
+        .update_("upgradeChildren")
+        .with_arg(&sha256)
+        .with_arg(Some(cascade))
+        .build::<(Result<ChildrenUpgrade, String>,)>()
+        .call_and_wait(waiter())
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let failed = print_children_upgrade(&response?, 0);
+    if failed > 0 {
+        return Err(format!("{} colonies failed to upgrade", failed));
+    }
+
+    Ok(())
+}
+
+/// Prints the result of each child, indented by their depth, and returns how many failed
+fn print_children_upgrade(upgrade: &ChildrenUpgrade, depth: usize) -> usize {
+    let mut failed = 0;
+
+    for child in upgrade.children.iter() {
+        match &child.result {
+            Ok(()) => println!(
+                "{:depth$}{} upgraded",
+                "",
+                child.canister_id,
+                depth = depth * 2
+            ),
+            Err(error) => {
+                println!(
+                    "{:depth$}{} {}",
+                    "",
+                    child.canister_id,
+                    error,
+                    depth = depth * 2
+                );
+                failed += 1;
+            }
+        }
+
+        if let Some(grandchildren) = &child.children {
+            failed += print_children_upgrade(grandchildren, depth + 1);
+        }
+    }
+
+    failed
+}
+
+async fn save_snapshot(canister: &Canister<'_>, file: &Path) -> Result<(), String> {
+    let (info,) = canister
+        .update_("startSnapshotExport")
+        .build::<(Result<SnapshotInfo, String>,)>()
+        .call_and_wait(waiter())
+        .await
+        .map_err(|e| e.to_string())?;
+    let info = info?;
 
-    file_path.push(&file_name);
+    let mut bytes = Vec::with_capacity(info.size as usize);
+    while (bytes.len() as u64) < info.size {
+        let (chunk,) = canister
+            .query_("getSnapshotChunk")
+            .with_arg(bytes.len() as u64)
+            .build::<(Result<Vec<u8>, String>,)>()
+            .call()
+            .await
+            .map_err(|e| e.to_string())?;
+        let chunk = chunk?;
 
-    let mut file = File::open(&file_path)
-        .unwrap_or_else(|_| panic!("Failed to open file: {}", file_path.to_str().unwrap()));
-    let mut bytes = Vec::new();
-    file.read_to_end(&mut bytes).expect("Failed to read file");
+        if chunk.is_empty() {
+            break;
+        }
+        bytes.extend(chunk);
+        println!("Downloaded {} of {} bytes", bytes.len(), info.size);
+    }
 
+    if sha256_hex(&bytes) != info.sha256 {
+        return Err("The downloaded snapshot doesn't match its checksum".to_string());
+    }
+
+    std::fs::write(file, &bytes).map_err(|e| format!("Failed to write {:?}: {}", file, e))?;
+    println!("Saved snapshot {} to {:?}", info.sha256, file);
+
+    Ok(())
+}
+
+async fn restore_snapshot(canister: &Canister<'_>, file: &Path) -> Result<(), String> {
+    let bytes = std::fs::read(file).map_err(|e| format!("Failed to read {:?}: {}", file, e))?;
+    let info = SnapshotInfo {
+        size: bytes.len() as u64,
+        sha256: sha256_hex(&bytes),
+    };
+    println!("Restoring snapshot {} from {:?}", info.sha256, file);
+
+    let (response,) = canister
+        .update_("startSnapshotImport")
+        .with_arg(info)
+        .build::<(Result<(), String>,)>()
+        .call_and_wait(waiter())
+        .await
+        .map_err(|e| e.to_string())?;
+    response?;
+
+    let mut uploaded = 0;
+    for chunk in bytes.chunks(SNAPSHOT_CHUNK_SIZE) {
+        let (response,) = canister
+            .update_("uploadSnapshotChunk")
+            .with_arg(chunk.to_vec())
+            .build::<(Result<(), String>,)>()
+            .call_and_wait(waiter())
+            .await
+            .map_err(|e| e.to_string())?;
+        response?;
+
+        uploaded += chunk.len();
+        println!("Uploaded {} of {} bytes", uploaded, bytes.len());
+    }
+
+    let (response,) = canister
+        .update_("finishSnapshotImport")
+        .build::<(Result<(), String>,)>()
+        .call_and_wait(waiter())
+        .await
+        .map_err(|e| e.to_string())?;
+    response?;
+
+    println!("Snapshot restored");
+
+    Ok(())
+}
+
+fn sha256_hex(bytes: &[u8]) -> String {
     let mut hasher = Sha256::new();
-    hasher.update(&bytes);
-    let result = hasher.finalize();
+    hasher.update(bytes);
+
+    format!("{:x}", hasher.finalize())
+}
+
+/// Reads a wasm module, or a gzipped one from a .wasm.gz, which is uploaded as is
+pub fn read_wasm(wasm_path: &Path) -> Result<Vec<u8>, String> {
+    let bytes =
+        std::fs::read(wasm_path).map_err(|e| format!("Failed to read {:?}: {}", wasm_path, e))?;
 
     println!(
-        "Loaded a wasm file with the following checksum: {:x}",
-        result
+        "Loaded {} wasm file with the following checksum: {}",
+        if bytes.starts_with(&[0x1f, 0x8b]) {
+            "a gzipped"
+        } else {
+            "a"
+        },
+        sha256_hex(&bytes)
     );
 
-    bytes
+    Ok(bytes)
 }